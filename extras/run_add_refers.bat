@@ -1,6 +1,8 @@
 ..\bin\edimaps add -r cdcodes
 ..\bin\edimaps add -r cddata
+..\bin\edimaps add -r cdfield
 ..\bin\edimaps add -r idoctp
 ..\bin\edimaps add -r index
 ..\bin\edimaps add -r wkflow
+..\bin\edimaps add -r pipeln
 pause
@@ -28,9 +28,13 @@ enum Commands {
     def:   bool,
   },
   /// Add mapping specifications to the repository and to the json files backup
+  #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
   Map {
+    #[command(subcommand)]
+    action: Option<MapCommands>,
     /// Mapping specification file name
-    file:  String,
+    #[arg(required = true)]
+    file:  Option<String>,
     /// Add to repository
     #[arg(short, long)]
     repo:  bool,
@@ -40,6 +44,13 @@ enum Commands {
     /// Generate TXT output file
     #[arg(short, long)]
     text: bool,
+    /// Generate MS Excel output file (common template layout)
+    #[arg(short, long)]
+    xlsx: bool,
+    /// Take the specs from the repository or from the JSON or TXT backup
+    /// [repo|json|text] instead of the specs file
+    #[arg(long)]
+    from: Option<String>,
   },
   /// Generates output of list and counters of records in mapping specifications
   Out {
@@ -52,9 +63,72 @@ enum Commands {
     #[arg(short, long)]
     count: bool,
   },
+  /// Full-text search over rules, conditions, sources and comments of field
+  /// mappings
+  Search {
+    /// Search terms (words, "phrases", prefix*, AND/OR/NOT)
+    terms: String,
+    /// Search only in mapping specifications of this message
+    #[arg(short, long)]
+    messg: Option<String>,
+    /// Maximum number of hits
+    #[arg(short = 'n', long, default_value_t = 20)]
+    limit: usize,
+    /// Output format [text|json]
+    #[arg(short, long, default_value = "text")]
+    format: String,
+    /// Rebuild the search index from the fields in the repository first
+    #[arg(long)]
+    rebuild: bool,
+  },
+  /// Generates reports across the mapping specifications in the repository
+  Report {
+    #[command(subcommand)]
+    kind: ReportCommands,
+  },
+  /// Run a named query of the registry (list the queries if no name is given)
+  Query {
+    /// Query name
+    name:  Option<String>,
+    /// Messages, comma separated (e.g. 830,850,delfor)
+    #[arg(long)]
+    messg: Option<String>,
+    /// Message version
+    #[arg(long)]
+    mvers: Option<String>,
+    /// Target field name (% and _ as wildcards)
+    #[arg(long)]
+    field: Option<String>,
+    /// Mapping specification ID
+    #[arg(long)]
+    mapid: Option<String>,
+    /// IDoc type
+    #[arg(long)]
+    idoc:  Option<String>,
+    /// Output format [table|csv|json]
+    #[arg(short, long, default_value = "table")]
+    format: String,
+    /// Output file name (default: standard output)
+    #[arg(short, long)]
+    output: Option<String>,
+  },
+  /// Run a workflow pipeline: its steps in chain for each file (list the pipelines
+  /// if no name is given)
+  Run {
+    /// Pipeline name
+    pipeline: Option<String>,
+    /// Single file of the input folder of the first step (default: all files)
+    file: Option<String>,
+  },
+  /// View, update and export entries of the reference tables
+  Ref {
+    #[command(subcommand)]
+    action: RefCommands,
+  },
   /// Starts workflow of IDOC processes
   Step {
-    /// Step code to be executed [fixed|json|query]
+    /// Step code to be executed [fixed|json|strjson|query|unjson|xml|unxml|validate|
+    /// x12json|edfjson]
     stage: String,
     /// Idoc file name or Idocs folder name
     file : String,
@@ -64,5 +138,117 @@ enum Commands {
     /// Run the step for a folder (batch of files)
     #[arg(short, long)]
     batch:  bool,
+    /// Keep watching the input folder, processing files as they arrive
+    #[arg(short, long, conflicts_with = "single")]
+    watch:  bool,
+  },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum MapCommands {
+  /// Compare two versions of a mapping specification in the repository
+  Diff {
+    /// Mapping specification ID of the old version (mapid.chgnr)
+    spec1: String,
+    /// Mapping specification ID of the new version (mapid.chgnr)
+    spec2: String,
+    /// Output format [text|json|html]
+    #[arg(short, long, default_value = "text")]
+    format: String,
+    /// Output file name (default: standard output)
+    #[arg(short, long)]
+    output: Option<String>,
+  },
+  /// Check a mapping specification in the repository against its IDoc type
+  Check {
+    /// Mapping specification ID (mapid.chgnr)
+    spec: String,
+    /// IDoc type to check against (default: IDoc type in the index)
+    #[arg(short, long)]
+    idoc: Option<String>,
+    /// Output format [text|json]
+    #[arg(short, long, default_value = "text")]
+    format: String,
+  },
+  /// Generate the IDoc of an inbound EDI X12/EDIFACT file applying a mapping
+  /// specification in the repository
+  Apply {
+    /// Mapping specification ID (mapid.chgnr)
+    spec: String,
+    /// EDI X12 or EDIFACT file name
+    edifile: String,
+    /// IDoc type to generate (default: IDoc type in the index)
+    #[arg(short, long)]
+    idoc: Option<String>,
+    /// Output file name (default: <edi-file>_idoc.txt)
+    #[arg(short, long)]
+    output: Option<String>,
+  },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum ReportCommands {
+  /// Matrix of IDoc segment/field by partner map with the source expressions
+  Coverage {
+    /// IDoc type (as in the index and the IDoc definition)
+    idoc: String,
+    /// Message to report on (default: all messages of the IDoc type)
+    #[arg(short, long)]
+    messg: Option<String>,
+    /// Output format [csv|xlsx]
+    #[arg(short, long, default_value = "csv")]
+    format: String,
+    /// Output file name (default: standard output, csv only)
+    #[arg(short, long)]
+    output: Option<String>,
+    /// Include fields of the IDoc definition not mapped by any map
+    #[arg(short, long)]
+    all: bool,
+  },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum RefCommands {
+  /// List the entries of a reference table
+  List {
+    /// Reference table [cdcodes|cddata|cdfield|idoctp|wkflow|pipeln]
+    table: String,
+  },
+  /// Show the entries of a reference table with the key given (or starting with
+  /// it)
+  Show {
+    /// Reference table [cdcodes|cddata|cdfield|idoctp|wkflow|pipeln]
+    table: String,
+    /// Key values of the entry (e.g. code table and code for cdcodes)
+    #[arg(required = true)]
+    keys: Vec<String>,
+  },
+  /// Add or update an entry of a reference table
+  Set {
+    /// Reference table [cdcodes|cddata|cdfield|idoctp|wkflow|pipeln]
+    table: String,
+    /// Key values of the entry (e.g. code table and code for cdcodes)
+    #[arg(required = true)]
+    keys: Vec<String>,
+    /// Column value to be set, as column=value (repeatable)
+    #[arg(short, long)]
+    value: Vec<String>,
+  },
+  /// Delete an entry of a reference table (a whole code table for cdcodes with
+  /// one key)
+  Delete {
+    /// Reference table [cdcodes|cddata|cdfield|idoctp|wkflow|pipeln]
+    table: String,
+    /// Key values of the entry
+    #[arg(required = true)]
+    keys: Vec<String>,
+  },
+  /// Export a reference table in the JSON format read by 'add -r'
+  Export {
+    /// Reference table [cdcodes|cddata|cdfield|idoctp|wkflow|pipeln]
+    table: String,
+    /// Output file name (default: standard output)
+    #[arg(short, long)]
+    output: Option<String>,
   },
 }
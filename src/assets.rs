@@ -1,80 +1,118 @@
 // assets.rs - Function modules to upload reference information to the edimaps
 // program repository (2021-07-01 bar8tl)
-use calamine::{Reader, Xlsx, open_workbook, RangeDeserializerBuilder, Error};
+use crate::error::{io_error, json_error, repo_error, sheet_error, Result};
+use crate::reposit::add_table;
+use calamine::{Reader, Xlsx, open_workbook, RangeDeserializerBuilder};
 use rusqlite::Connection;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::from_reader;
 use std::fs::File;
 
 // add_cdcodes.rs - Add SAP EDI IDocs codes to the repository (2021-07-01 bar8tl)
 // Command line: emi add -r cdcodes
-#[derive(Debug, Clone, Default, Deserialize)]
-struct CodesTp {
-  key: String,
-  val: String
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CodesTp {
+  pub key: String,
+  pub val: String
 }
 
-#[derive(Debug, Clone, Default, Deserialize)]
-struct SapcdTp {
-  ctype: String,
-  usage: String,
-  codes: Vec<CodesTp>
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SapcdTp {
+  pub ctype: String,
+  pub usage: String,
+  pub codes: Vec<CodesTp>
 }
 
-#[derive(Debug, Clone, Default, Deserialize)]
- struct SapcodesTp {
-   sapcodes: Vec<SapcdTp>
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SapcodesTp {
+  pub sapcodes: Vec<SapcdTp>
 }
 
-pub fn add_cdcodes(dbpath: &String, rfpath: String) {
-  let cnn = Connection::open(dbpath).expect("Error opening DB");
-  cnn.execute("DELETE FROM cdcodes;", ()).expect("Table not reset");
-  cnn.execute("DELETE FROM cdindex;", ()).expect("Table not reset");
-  let f = File::open(rfpath).expect("Input not found");
-  let sapcd: SapcodesTp = from_reader(f).expect("JSON not well-formed");
+pub fn add_cdcodes(dbpath: &String, rfpath: String) -> Result<()> {
+  let cnn = Connection::open(dbpath)?;
+  cnn.execute("DELETE FROM cdcodes;", ()).map_err(|e| repo_error(e, "cdcodes"))?;
+  cnn.execute("DELETE FROM cdindex;", ()).map_err(|e| repo_error(e, "cdindex"))?;
+  let f = File::open(&rfpath).map_err(|e| io_error(e, &rfpath))?;
+  let sapcd: SapcodesTp = from_reader(f).map_err(|e| json_error(e, &rfpath))?;
   for sc in sapcd.sapcodes.iter() {
     cnn.execute("INSERT INTO cdindex VALUES (?1,?2);", (&sc.ctype, &sc.usage))
-      .expect("Row not inserted");
+      .map_err(|e| repo_error(e, "cdindex"))?;
     for st in sc.codes.iter() {
       cnn.execute("INSERT INTO cdcodes VALUES (?1,?2,?3);", (&sc.ctype, &st.key,
-        &st.val)).expect("Row not inserted");
+        &st.val)).map_err(|e| repo_error(e, "cdcodes"))?;
     }
   }
   println!("Table 'cdindex' uploaded.");
   println!("Table 'cdcodes' uploaded.");
+  return Ok(());
 }
 
 // add_cddata.rs - Add specific EDI-SAP Idoc code equivalences to the repository.
 // Example: Standard EDI Transport means codes (2021-07-01 bar8tl)
 // Command line: edimaps add -r cddata
-#[derive(Debug, Clone, Default, Deserialize)]
-struct TranslTp {
-  tmedi: String,
-  tmode: String,
-  tmean: String
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TranslTp {
+  pub tmedi: String,
+  pub tmode: String,
+  pub tmean: String
 }
 
-#[derive(Debug, Clone, Default, Deserialize)]
-struct TranspTp {
-  transp: Vec<TranslTp>
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TranspTp {
+  pub transp: Vec<TranslTp>
 }
 
-pub fn add_cddata(dbpath: &String, rfpath: String) {
-  let cnn = Connection::open(dbpath).expect("DB Open Error");
-  cnn.execute("DELETE FROM cddata;", ()).expect("Table not reset");
-  let f = File::open(rfpath).expect("Input not found");
-  let trnsp: TranspTp = from_reader(f).expect("JSON not well-formed");
+pub fn add_cddata(dbpath: &String, rfpath: String) -> Result<()> {
+  let cnn = Connection::open(dbpath)?;
+  cnn.execute("DELETE FROM cddata;", ()).map_err(|e| repo_error(e, "cddata"))?;
+  let f = File::open(&rfpath).map_err(|e| io_error(e, &rfpath))?;
+  let trnsp: TranspTp = from_reader(f).map_err(|e| json_error(e, &rfpath))?;
   for st in trnsp.transp.iter() {
     cnn.execute("INSERT INTO cddata VALUES (?1,?2,?3,?4)",
-     ("editransp",&st.tmedi,&st.tmode,&st.tmean)).expect("Row not inserted");
+     ("editransp",&st.tmedi,&st.tmode,&st.tmean))
+     .map_err(|e| repo_error(e, "cddata"))?;
   }
   println!("Table 'cddata' uploaded.");
+  return Ok(());
+}
+
+// add_cdfield.rs - Add IDoc coded fields to the repository: code table to be used
+// to describe the values of a field, in any segment (segmn empty) or in segments
+// of a type. The table is created in repositories built before it existed
+// (2021-07-01 bar8tl)
+// Command line: edimaps add -r cdfield
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CdfldTp {
+  #[serde(default)]
+  pub segmn: String,
+  pub field: String,
+  pub tabid: String
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CdfieldTp {
+  pub cdfield: Vec<CdfldTp>
+}
+
+pub fn add_cdfield(dbpath: &String, rfpath: String) -> Result<()> {
+  let cnn = Connection::open(dbpath)?;
+  add_table(&cnn, "cdfield")?;
+  cnn.execute("DELETE FROM cdfield;", ()).map_err(|e| repo_error(e, "cdfield"))?;
+  let f = File::open(&rfpath).map_err(|e| io_error(e, &rfpath))?;
+  let cdfld: CdfieldTp = from_reader(f).map_err(|e| json_error(e, &rfpath))?;
+  for cf in cdfld.cdfield.iter() {
+    cnn.execute("INSERT INTO cdfield VALUES (?1,?2,?3)",
+     (&cf.segmn, &cf.field, &cf.tabid))
+     .map_err(|e| repo_error(e, "cdfield"))?;
+  }
+  println!("Table 'cdfield' uploaded.");
+  return Ok(());
 }
 
 // add_idoctp.rs - Add IDOC type additional data to allow idntification of raw files
 // from SAP systems (2021-07-01 bar8tl)
 // Command line: edimaps add -r idoctp
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct IdoctpTp {
   pub itype: String,
   #[serde(default)]
@@ -88,27 +126,28 @@ pub struct IdoctpTp {
   pub rcvpf: String,
 }
 
-#[derive(Debug, Clone, Default, Deserialize)]
-struct IdoctplTp {
-  idoct: Vec<IdoctpTp>
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IdoctplTp {
+  pub idoct: Vec<IdoctpTp>
 }
 
-pub fn add_idoctp(dbpath: &String, rfpath: String) {
-  let cnn = Connection::open(dbpath).expect("DB Open Error");
-  cnn.execute("DELETE FROM idoctp;", ()).expect("Table not reset");
-  let f = File::open(rfpath).expect("Input not found");
-  let idtpl: IdoctplTp = from_reader(f).expect("JSON not well-formed");
+pub fn add_idoctp(dbpath: &String, rfpath: String) -> Result<()> {
+  let cnn = Connection::open(dbpath)?;
+  cnn.execute("DELETE FROM idoctp;", ()).map_err(|e| repo_error(e, "idoctp"))?;
+  let f = File::open(&rfpath).map_err(|e| io_error(e, &rfpath))?;
+  let idtpl: IdoctplTp = from_reader(f).map_err(|e| json_error(e, &rfpath))?;
   for it in idtpl.idoct.iter() {
     cnn.execute("INSERT INTO idoctp VALUES (?1,?2,?3,?4,?5,?6)",
-     (&it.itype, String::new(), &it.short, String::new(), String::new(), &it.rcvpf))
-     .expect("Row not inserted");
+     (&it.itype, &it.idefn, &it.short, &it.cntrl, &it.clien, &it.rcvpf))
+     .map_err(|e| repo_error(e, "idoctp"))?;
   }
   println!("Table 'idoctp' uploaded.");
+  return Ok(());
 }
 
 // add_wkflow.rs - Add Idoc process workflow configuration file (2021-07-01 bar8tl)
 // Command line: edimaps add -r wkflow
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct StepTp {
   pub step : String,
   pub inpdr: String,
@@ -124,22 +163,60 @@ pub struct StepTp {
   pub ifilt: String
 }
 
-#[derive(Debug, Clone, Default, Deserialize)]
-struct StepsTp {
-  steps: Vec<StepTp>
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StepsTp {
+  pub steps: Vec<StepTp>
 }
 
-pub fn add_wkflow(dbpath: &String, rfpath: String) {
-  let cnn = Connection::open(dbpath).expect("DB Open Error");
-  cnn.execute("DELETE FROM wkflow;", ()).expect("Table not reset");
-  let f = File::open(rfpath).expect("Input not found");
-  let steps: StepsTp = from_reader(f).expect("JSON not well-formed");
+pub fn add_wkflow(dbpath: &String, rfpath: String) -> Result<()> {
+  let cnn = Connection::open(dbpath)?;
+  cnn.execute("DELETE FROM wkflow;", ()).map_err(|e| repo_error(e, "wkflow"))?;
+  let f = File::open(&rfpath).map_err(|e| io_error(e, &rfpath))?;
+  let steps: StepsTp = from_reader(f).map_err(|e| json_error(e, &rfpath))?;
   for st in steps.steps.iter() {
     cnn.execute("INSERT INTO wkflow VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10)",
      (&st.step,  &st.inpdr, &st.inptp, &st.outdr, &st.outtp, &st.refdr, &st.reftp,
-      &st.wkflw, &st.pcddr, &st.ifilt)).expect("Row not inserted");
+      &st.wkflw, &st.pcddr, &st.ifilt)).map_err(|e| repo_error(e, "wkflow"))?;
   }
   println!("Table 'wkflow' uploaded.");
+  return Ok(());
+}
+
+// add_pipeln.rs - Add workflow pipelines: ordered steps run in chain for each file,
+// each step with an optional file name filter (2021-07-01 bar8tl)
+// Command line: edimaps add -r pipeln
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PipstpTp {
+  pub step : String,
+  #[serde(default)]
+  pub ifilt: String
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PipelnTp {
+  pub pipnm: String,
+  pub steps: Vec<PipstpTp>
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PipelnsTp {
+  pub pipelines: Vec<PipelnTp>
+}
+
+pub fn add_pipeln(dbpath: &String, rfpath: String) -> Result<()> {
+  let cnn = Connection::open(dbpath)?;
+  cnn.execute("DELETE FROM pipeln;", ()).map_err(|e| repo_error(e, "pipeln"))?;
+  let f = File::open(&rfpath).map_err(|e| io_error(e, &rfpath))?;
+  let pipls: PipelnsTp = from_reader(f).map_err(|e| json_error(e, &rfpath))?;
+  for pl in pipls.pipelines.iter() {
+    for (i, ps) in pl.steps.iter().enumerate() {
+      cnn.execute("INSERT INTO pipeln VALUES (?1,?2,?3,?4)",
+       (&pl.pipnm, i + 1, &ps.step, &ps.ifilt))
+       .map_err(|e| repo_error(e, "pipeln"))?;
+    }
+  }
+  println!("Table 'pipeln' uploaded.");
+  return Ok(());
 }
 
 // index.rs - Function modules to upload the mapping specification index file to
@@ -159,12 +236,12 @@ pub struct IdxkeyTp {
 
 // add_index.rs - Add index of EDI mapping specification files to the repository
 // (from an external MS-Excel file (2021-07-01 bar8tl)
-pub fn add_index(dbpath: &String, rfpath: &String, tabid: &String) {
-  let cnn  = Connection::open(dbpath).expect("DB Open Error");
-  cnn.execute("DELETE FROM indix;", ()).expect("Table not reset");
+pub fn add_index(dbpath: &String, rfpath: &String, tabid: &String) -> Result<()> {
+  let cnn  = Connection::open(dbpath)?;
+  cnn.execute("DELETE FROM indix;", ()).map_err(|e| repo_error(e, "indix"))?;
   let indx = read_index(IdxkeyTp{
     idxpt: rfpath.clone(), tabid: tabid.clone(), mapid: "".to_string(),
-    chgnr: "".to_string()}, "ALL");
+    chgnr: "".to_string()}, "ALL")?;
   for c in indx {
     cnn.execute("INSERT INTO indix VALUES
       (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16)",
@@ -172,23 +249,28 @@ pub fn add_index(dbpath: &String, rfpath: &String, tabid: &String) {
        c[5] .clone(), c[6] .clone(), c[7] .clone(), c[8] .clone(), c[9] .clone(),
        c[10].clone(), c[11].clone(), c[12].clone(), c[13].clone(), c[14].clone(),
        c[15].clone())
-    ).expect("Row not inserted");
+    ).map_err(|e| repo_error(e, "indix"))?;
   }
   println!("Table 'indix' uploaded.");
+  return Ok(());
 }
 
 // read_index.rs - Retrieves specific detail about a selected mapping specification
 // file from the index MS Excel file (2021-07-01 bar8tl)
-pub fn read_index(p: IdxkeyTp, mode: &str) -> Vec<IdxrowTp> {
+pub fn read_index(p: IdxkeyTp, mode: &str) -> Result<Vec<IdxrowTp>> {
   let mut cell: Vec<IdxrowTp> = vec![];
   let mut cl  : IdxrowTp;
-  let mut workbook: Xlsx<_> = open_workbook(p.idxpt).expect("Input not found");
+  let mut workbook: Xlsx<_> = open_workbook(&p.idxpt)
+    .map_err(|e| sheet_error(e, &p.idxpt, &p.tabid))?;
   let range = workbook.worksheet_range(p.tabid.as_str())
-    .ok_or(Error::Msg("Cannot find specified tab")).unwrap().unwrap();
-  let iter = RangeDeserializerBuilder::new().from_range(&range).unwrap();
-  for i in iter {
+    .ok_or(sheet_error("Cannot find specified tab", &p.idxpt, &p.tabid))?
+    .map_err(|e| sheet_error(e, &p.idxpt, &p.tabid))?;
+  let iter = RangeDeserializerBuilder::new().from_range(&range)
+    .map_err(|e| sheet_error(e, &p.idxpt, &p.tabid))?;
+  for (j, i) in iter.enumerate() {
     let (mapid, ctmrs, ctmrl, messg, mvers, idocm, idoct, mstat, fname, relsd,
-         chgnr, suprt, asgnd, dstat, templ): IdxlinTp = i.expect("Row not mapped");
+         chgnr, suprt, asgnd, dstat, templ): IdxlinTp = i.map_err(|e|
+      sheet_error(format!("row {}: {}", j+2, e), &p.idxpt, &p.tabid))?;
     cl = [mapid.clone(), ctmrs, ctmrl, messg.clone(), mvers, idocm, idoct, mstat,
           fname, relsd, chgnr.clone(), suprt, asgnd, dstat, templ, String::new()];
     cl[15] =
@@ -204,5 +286,29 @@ pub fn read_index(p: IdxkeyTp, mode: &str) -> Vec<IdxrowTp> {
       cell.push(cl.clone());
     }
   }
-  return cell;
+  return Ok(cell);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readidoc::samples::{sample_repo, work_folder};
+  use std::fs::write;
+
+  #[test]
+  fn add_cdfield_creates_table_and_replaces_its_rows() {
+    let wrkdr = work_folder("add-cdfield");
+    let dbopt = sample_repo(&wrkdr);
+    let rfpath = format!("{}_cdfield.json", wrkdr);
+    write(&rfpath, r#"{"cdfield": [
+      {"segmn": "", "field": "QUALF", "tabid": "QUALF"},
+      {"segmn": "E1EDK01", "field": "CURCY", "tabid": "CURCY"}]}"#).unwrap();
+    add_cdfield(&dbopt, rfpath.clone()).unwrap();
+    add_cdfield(&dbopt, rfpath).unwrap();
+    let cnn = Connection::open(&dbopt).unwrap();
+    let count = |table: &str| cnn.query_row(&format!("SELECT count(*) FROM {};",
+      table), [], |r| r.get::<_, i64>(0)).unwrap();
+    assert_eq!(count("cdfield"), 2);
+    assert!(count("items") > 0);
+  }
 }
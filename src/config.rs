@@ -1,5 +1,6 @@
 // config.rs - Reads the edimaps program configuration file and populate the settings
 // to be used by function modules (2021-07-01 bar8tl)
+use crate::error::{ErrorTp, Result};
 use rblib::ownpath::ownpath;
 use serde::Deserialize;
 use std::fs::read_to_string;
@@ -11,9 +12,13 @@ pub const NODAT: &str = "yes";
 pub const OMITE: &str = "no";
 pub const NDCHR: &str = "¤";
 pub const LFCHR: &str = "\\n";
+pub const TPLDR: &str = "~\\_templates\\";
+pub const QRYPT: &str = "~\\_queries.toml";
 pub const CNTRL: &str = "EDI_DC40";
 pub const CLIEN: &str = "011";
 pub const RCVPF: &str = "RE";
+pub const LNEND: &str = "crlf";
+pub const POLLT: u64  = 5;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ConfigTp {
@@ -26,7 +31,9 @@ pub struct ConfigTp {
 #[derive(Clone, Debug, Deserialize)]
 pub struct GeneralTp {
   pub home : String,
-  pub dbopt: String
+  pub dbopt: String,
+  #[serde(default)]
+  pub qrypt: String
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -58,7 +65,9 @@ pub struct MapsTp {
   #[serde(default)]
   pub ndchr: String,
   #[serde(default)]
-  pub lfchr: String
+  pub lfchr: String,
+  #[serde(default)]
+  pub tpldr: String
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -68,25 +77,54 @@ pub struct WkflowTp {
   #[serde(default)]
   pub clien: String,
   #[serde(default)]
-  pub rcvpf: String
+  pub rcvpf: String,
+  #[serde(default)]
+  pub elmsp: String,         // EDI delimiters, override those of the interchange
+  #[serde(default)]
+  pub cmpsp: String,
+  #[serde(default)]
+  pub segtr: String,
+  #[serde(default)]
+  pub rlsch: String,
+  #[serde(default)]
+  pub lnend: String,         // Line terminator of IDoc files: crlf or lf
+  #[serde(default)]
+  pub pollt: u64             // Seconds between scans of input folder, watch mode
 }
 
-pub fn get_config(fname: &str) -> ConfigTp {
-  let confg = read_to_string(fname).expect("Failed to read file");
-  let mut rc: ConfigTp = from_str(&confg).expect("Failed to deserialize");
+pub fn get_config(fname: &str) -> Result<ConfigTp> {
+  let confg = read_to_string(fname).map_err(|e| ErrorTp::Config {
+    file: fname.to_string(), detail: e.to_string() })?;
+  let mut rc: ConfigTp = from_str(&confg).map_err(|e| ErrorTp::Config {
+    file: fname.to_string(), detail: e.to_string() })?;
   if rc.maps  .pmode.len() == 0 { rc.maps  .pmode = PMODE.to_string(); }
   if rc.maps  .trims.len() == 0 { rc.maps  .trims = TRIMS.to_string(); }
   if rc.maps  .nodat.len() == 0 { rc.maps  .nodat = NODAT.to_string(); }
   if rc.maps  .omite.len() == 0 { rc.maps  .omite = OMITE.to_string(); }
   if rc.maps  .ndchr.len() == 0 { rc.maps  .ndchr = NDCHR.to_string(); }
   if rc.maps  .lfchr.len() == 0 { rc.maps  .lfchr = LFCHR.to_string(); }
+  if rc.maps  .tpldr.len() == 0 { rc.maps  .tpldr = TPLDR.to_string(); }
+  if rc.general.qrypt.len() == 0 { rc.general.qrypt = QRYPT.to_string(); }
   if rc.wkflow.cntrl.len() == 0 { rc.wkflow.cntrl = CNTRL.to_string(); }
   if rc.wkflow.clien.len() == 0 { rc.wkflow.clien = CLIEN.to_string(); }
   if rc.wkflow.rcvpf.len() == 0 { rc.wkflow.rcvpf = RCVPF.to_string(); }
+  if rc.wkflow.lnend.len() == 0 { rc.wkflow.lnend = LNEND.to_string(); }
+  if rc.wkflow.pollt       == 0 { rc.wkflow.pollt = POLLT; }
+  rc.general.qrypt = ownpath(&rc.general.home, &rc.general.qrypt);
   rc.refers.refdr = ownpath(&rc.general.home, &rc.refers.refdr);
   rc.refers.idxpt = ownpath(&rc.general.home, &rc.refers.idxpt);
   rc.refers.defdr = ownpath(&rc.general.home, &rc.refers.defdr);
   rc.maps  .mapdr = ownpath(&rc.general.home, &rc.maps  .mapdr);
   rc.maps  .bkpdr = ownpath(&rc.general.home, &rc.maps  .bkpdr);
-  return rc;
+  rc.maps  .tpldr = ownpath(&rc.general.home, &rc.maps  .tpldr);
+  return Ok(rc);
+}
+
+// line_end.rs - Line terminator of output IDoc files for the setting lnend: "lf"
+// for Unix style lines, CRLF otherwise (2021-07-01 bar8tl)
+pub fn line_end(lnend: &str) -> &'static str {
+  return match lnend.to_lowercase().as_str() {
+    "lf" => "\n",
+    _    => "\r\n"
+  };
 }
@@ -1,5 +1,6 @@
 // definitn.rs - Read SAP IDoc parser file, and upload IDoc definition detail and
 // structure into the repository (2021-07-01 bar8tl)
+use crate::error::{format_error, io_error, repo_error, Result};
 use rusqlite::Connection;
 use serde::Deserialize;
 use std::fs::File;
@@ -61,6 +62,7 @@ pub struct GrpdfTp {
   pub name:  String,
   pub typi:  String,
   pub seqn:  usize,
+  pub head:  String,    // First segment in group, stands for its occurrences
   pub cols: [String; 5] // Numb, Levl, Stat, Mnlp, Mxlp
 }
 
@@ -146,7 +148,7 @@ pub struct OutitmTp { // ITEMS fields description (*=key field in DB record)
   pub dname: String, //* Basic-IDoc  Group#      Segm-ID     Field-Name  Field-Name
   pub rclas: String, //  Basic-IDoc  Group#      Segm-ID     'SEGMENT'   'RECORD'
   pub rtype: String, //  'IDOC'      'GROUP'     'SEGMENT'   'FIELDS'    'FIELDS'
-  pub dtype: String, //  ''          ''          Segm-Type    Data-Type   Data-Type
+  pub dtype: String, //  ''          Head-Segm   Segm-Type    Data-Type   Data-Type
   pub dtext: String, //  Extsn-name  Group#      Qualified   Field-Desc  Field-Desc
   pub level: usize,  //  0           Level       Level       0           0
   pub stats: String, //  ''          Status      Status      ''          ''
@@ -188,27 +190,28 @@ pub struct SetgsTp {
 // Main logic to process: 1) Master data for IDoc Items, 2) Structure for groups,
 // and 3) Structure for segments
 // Command line: edimaps add -d <idoc-parser-file>
-pub fn add_definitn(dbopt: &String, refpt: &String) {
-  let cnn = Connection::open(&dbopt).expect("DB Open Error");
+pub fn add_definitn(dbopt: &String, refpt: &String) -> Result<()> {
+  let cnn = Connection::open(&dbopt)?;
   let mut ii = InpitmTp { ..Default::default() };
   let mut ig = InpgrpTp { ..Default::default() };
   let mut is = InpsgmTp { ..Default::default() };
   init_items_master(&mut ii);
   init_group_struct(&mut ig);
   init_segmt_struct(&mut is);
-  let ifile = File::open(refpt).unwrap();
+  let ifile = File::open(refpt).map_err(|e| io_error(e, refpt))?;
   let rdr = BufReader::new(ifile);
-  for wline in rdr.lines() {
-    let wline = wline.unwrap();
+  for (j, wline) in rdr.lines().enumerate() {
+    let wline = wline.map_err(|e| io_error(e, refpt))?;
     let line  = wline.trim();
     if line.len() > 0 {
       let sline = scan_parserfile_line(line);
       proc_items_master(      &sline, &mut ii);
-      proc_group_struct(&cnn, &sline, &mut ig);
-      proc_segmt_struct(&cnn, &sline, &mut is);
+      proc_group_struct(&cnn, &sline, &mut ig).map_err(|e| e.at(refpt, j+1))?;
+      proc_segmt_struct(&cnn, &sline, &mut is).map_err(|e| e.at(refpt, j+1))?;
     }
   }
-  prep_items_output(&cnn, &mut ii);
+  prep_items_output(&cnn, &mut ii).map_err(|e| e.at(refpt, 0))?;
+  return Ok(());
 }
 
 fn init_items_master(ii: &mut InpitmTp) {
@@ -233,8 +236,12 @@ fn init_segmt_struct(is: &mut InpsgmTp) {
 // prep_items_output.rs - Format IDOC item definition detail (idoc, group, segment
 // and field) in internal database layouts and start creation of item records into
 // the local daabase (2021-07-01 bar8tl)
-pub fn prep_items_output(cnn: &Connection, ii: &mut InpitmTp) {
-  clear_items(cnn, ii.lidoc[0].cols[1].clone());
+pub fn prep_items_output(cnn: &Connection, ii: &mut InpitmTp) -> Result<()> {
+  if ii.lidoc.len() == 0 {
+    return Err(format_error("", format!("No {}_{} found in parser file", BEGIN,
+      IDOC)));
+  }
+  clear_items(cnn, ii.lidoc[0].cols[1].clone())?;
 
   // upld_recd(cnn) - Upload IDoc records data
   // /RB04/YP3_DELVRY_RBNA|CONTROL|TABNAM|RECORD|FIELDS|CHARACTER|
@@ -252,11 +259,11 @@ pub fn prep_items_output(cnn: &Connection, ii: &mut InpitmTp) {
     w.stats = Default::default();
     w.minlp = 0;
     w.maxlp = 0;
-    w.lngth = lrecd.cols[3].parse::<usize>().unwrap(); // LENGTH          000010
-    w.seqno = lrecd.cols[4].parse::<usize>().unwrap(); // FIELD_POS       0001
-    w.strps = lrecd.cols[5].parse::<usize>().unwrap(); // CHARACTER_FIRST 000001
-    w.endps = lrecd.cols[6].parse::<usize>().unwrap(); // CHARACTER_LAST  000010
-    write_items(cnn, w.clone());
+    w.lngth = get_number(&lrecd.cols[3])?; // LENGTH          000010
+    w.seqno = get_number(&lrecd.cols[4])?; // FIELD_POS       0001
+    w.strps = get_number(&lrecd.cols[5])?; // CHARACTER_FIRST 000001
+    w.endps = get_number(&lrecd.cols[6])?; // CHARACTER_LAST  000010
+    write_items(cnn, w.clone())?;
   }
 
   // upld_idoc(cnn) - Upload IDoc idoc data
@@ -279,7 +286,7 @@ pub fn prep_items_output(cnn: &Connection, ii: &mut InpitmTp) {
     w.seqno = 0;
     w.strps = 0;
     w.endps = 0;
-    write_items(cnn, w.clone());
+    write_items(cnn, w.clone())?;
   }
 
   // upld_grup(cnn) - Upload IDoc groups data
@@ -292,17 +299,17 @@ pub fn prep_items_output(cnn: &Connection, ii: &mut InpitmTp) {
     w.dname = lgrup.cols[0].clone(); // BEGIN_GROUP     1
     w.rclas = lgrup.name.clone();    // BEGIN_GROUP     1
     w.rtype = lgrup.typi.clone();    // B…_GROUP        GROUP
-    w.dtype = Default::default();
+    w.dtype = lgrup.head.clone();    // BEGIN_SEGMENT   E2EDL20004
     w.dtext = lgrup.cols[0].clone(); // BEGIN_GROUP     1
-    w.level = lgrup.cols[1].parse::<usize>().unwrap(); // LEVEL       02
-    w.stats = lgrup.cols[2].clone();                   // STATUS      MANDATORY
-    w.minlp = lgrup.cols[3].parse::<usize>().unwrap(); // LOOPMIN     0000000001
-    w.maxlp = lgrup.cols[4].parse::<usize>().unwrap(); // LOOPMAX     0000009999
+    w.level = get_number(&lgrup.cols[1])?; // LEVEL       02
+    w.stats = lgrup.cols[2].clone();       // STATUS      MANDATORY
+    w.minlp = get_number(&lgrup.cols[3])?; // LOOPMIN     0000000001
+    w.maxlp = get_number(&lgrup.cols[4])?; // LOOPMAX     0000009999
     w.lngth = 0;
     w.seqno = lgrup.seqn.clone();
     w.strps = 0;
     w.endps = 0;
-    write_items(cnn, w.clone());
+    write_items(cnn, w.clone())?;
   }
 
   // upld_segm(cnn) - Upload IDoc segments data
@@ -318,15 +325,15 @@ pub fn prep_items_output(cnn: &Connection, ii: &mut InpitmTp) {
     w.rtype = lsegm.typi.clone();    // B…_SEGMENT      SEGMENT
     w.dtype = lsegm.cols[1].clone(); // SEGMENTTYPE     E1EDL20
     w.dtext = lsegm.cols[2].clone(); // QUALIFIED       QUAL
-    w.level = lsegm.cols[3].parse::<usize>().unwrap(); // LEVEL       02
-    w.stats = lsegm.cols[4].clone();                   // STATUS      MANDATORY
-    w.minlp = lsegm.cols[5].parse::<usize>().unwrap(); // LOOPMIN     0000000001
-    w.maxlp = lsegm.cols[6].parse::<usize>().unwrap(); // LOOPMAX     0000000001
+    w.level = get_number(&lsegm.cols[3])?; // LEVEL       02
+    w.stats = lsegm.cols[4].clone();       // STATUS      MANDATORY
+    w.minlp = get_number(&lsegm.cols[5])?; // LOOPMIN     0000000001
+    w.maxlp = get_number(&lsegm.cols[6])?; // LOOPMAX     0000000001
     w.lngth = 0;
     w.seqno = lsegm.seqn.clone();
     w.strps = 0;
     w.endps = 0;
-    write_items(cnn, w.clone());
+    write_items(cnn, w.clone())?;
   }
 
   // upld_flds(cnn) - Upload IDoc fields data
@@ -345,12 +352,19 @@ pub fn prep_items_output(cnn: &Connection, ii: &mut InpitmTp) {
     w.stats = Default::default();
     w.minlp = 0;
     w.maxlp = 0;
-    w.lngth = lfild.cols[3].parse::<usize>().unwrap(); // LENGTH          000004
-    w.seqno = lfild.cols[4].parse::<usize>().unwrap(); // FIELD_POS       0005
-    w.strps = lfild.cols[5].parse::<usize>().unwrap(); // CHARACTER_FIRST 000084
-    w.endps = lfild.cols[6].parse::<usize>().unwrap(); // CHARACTER_LAST  000087
-    write_items(cnn, w.clone());
+    w.lngth = get_number(&lfild.cols[3])?; // LENGTH          000004
+    w.seqno = get_number(&lfild.cols[4])?; // FIELD_POS       0005
+    w.strps = get_number(&lfild.cols[5])?; // CHARACTER_FIRST 000084
+    w.endps = get_number(&lfild.cols[6])?; // CHARACTER_LAST  000087
+    write_items(cnn, w.clone())?;
   }
+  return Ok(());
+}
+
+// get_number.rs - Get numeric value of a parser file column (2021-07-01 bar8tl)
+fn get_number(val: &String) -> Result<usize> {
+  return val.parse::<usize>()
+    .map_err(|_e| format_error("", format!("'{}' is not a number", val)));
 }
 
 // scan_parserfile_line.rs - Identify individual tokens in SAP IDOC data in parser
@@ -392,7 +406,8 @@ pub fn scan_parserfile_line(s: &str) -> ParslTp {
 
 // proc_group_struct.rs - Get IDOC groups structure detail and start creation of
 // corresponding structure records into the local database (2021-07-01 bar8tl)
-pub fn proc_group_struct(cnn: &Connection, sline: &ParslTp, ig: &mut InpgrpTp) {
+pub fn proc_group_struct(cnn: &Connection, sline: &ParslTp, ig: &mut InpgrpTp) ->
+  Result<()> {
   if sline.label.ident == BEGIN {
     if sline.label.recnm == IDOC {
       ig.stack.push(KeystTp {
@@ -403,7 +418,7 @@ pub fn proc_group_struct(cnn: &Connection, sline: &ParslTp, ig: &mut InpgrpTp) {
       });
       ig.l += 1;
       ig.idocn = sline.value.clone();
-      clear_struc(cnn, ig.idocn.clone(), ig.strtp.clone());
+      clear_struc(cnn, ig.idocn.clone(), ig.strtp.clone())?;
     } else if sline.label.recnm == GROUP {
       ig.stack[ig.l as usize].seqno += 1;
       ig.stack.push(KeystTp {
@@ -414,7 +429,7 @@ pub fn proc_group_struct(cnn: &Connection, sline: &ParslTp, ig: &mut InpgrpTp) {
       });
       ig.l += 1;
     }
-    return;
+    return Ok(());
   }
   if sline.label.ident == END {
     if sline.label.recnm == IDOC {
@@ -424,24 +439,26 @@ pub fn proc_group_struct(cnn: &Connection, sline: &ParslTp, ig: &mut InpgrpTp) {
       ig.gseqn += 1;
       ig.stack[ig.l as usize-1].pseqn = ig.gseqn;
       write_struc(cnn, ig.idocn.clone(), ig.strtp.clone(),
-        ig.stack[ig.l as usize-1].clone(), ig.stack[ig.l as usize].clone());
+        ig.stack[ig.l as usize-1].clone(), ig.stack[ig.l as usize].clone())?;
       ig.stack = ig.stack[..ig.l as usize].to_vec();
       ig.l -= 1;
     }
-    return;
+    return Ok(());
   }
   if ig.l >= 0 && ig.stack[ig.l as usize].rname == IDOC {
     if sline.label.ident == EXTENSION {
       ig.idocn = sline.value.clone();
-      clear_struc(cnn, ig.idocn.clone(), ig.strtp.clone());
+      clear_struc(cnn, ig.idocn.clone(), ig.strtp.clone())?;
     }
-    return;
+    return Ok(());
   }
+  return Ok(());
 }
 
 // proc_segmt_struct.rs - Get IDOC segments structure data and start creation of
 // corresponding structure records into the local database (2021-07-01 bar8tl)
-pub fn proc_segmt_struct(cnn: &Connection, sline: &ParslTp, is: &mut InpsgmTp) {
+pub fn proc_segmt_struct(cnn: &Connection, sline: &ParslTp, is: &mut InpsgmTp) ->
+  Result<()> {
   if sline.label.ident == BEGIN {
     if sline.label.recnm == IDOC {
       is.stack.push(KeystTp {
@@ -456,7 +473,7 @@ pub fn proc_segmt_struct(cnn: &Connection, sline: &ParslTp, is: &mut InpsgmTp) {
       is.tnode.dqual = Default::default();
       is.tnode.pseqn = 0;
       is.idocn       = sline.value.clone();
-      clear_struc(cnn, is.idocn.clone(), is.strtp.clone());
+      clear_struc(cnn, is.idocn.clone(), is.strtp.clone())?;
     } else if sline.label.recnm == SEGMENT && sline.label.rectp.len() == 0 {
       is.sseqn += 1;
       is.tnode.rname = sline.label.recnm.clone();
@@ -464,11 +481,18 @@ pub fn proc_segmt_struct(cnn: &Connection, sline: &ParslTp, is: &mut InpsgmTp) {
       is.tnode.dqual = Default::default();
       is.tnode.pseqn = is.sseqn.clone();
     }
-    return;
+    return Ok(());
   }
 
   if sline.label.ident == END && is.l >= 0 {
     if sline.label.recnm == IDOC {
+      while is.l > 0 {                 // Segments still open at end of the IDoc
+        write_struc(cnn, is.idocn.clone(), is.strtp.clone(),
+          is.stack[is.l as usize-1].clone(),
+          is.stack[is.l as usize  ].clone())?;
+        is.stack = is.stack[..is.l as usize].to_vec();
+        is.l -= 1;
+      }
       is.stack = is.stack[..is.l as usize].to_vec();
       is.l -= 1;
     } else if sline.label.recnm == SEGMENT && sline.label.rectp.len() == 0 {
@@ -488,7 +512,7 @@ pub fn proc_segmt_struct(cnn: &Connection, sline: &ParslTp, is: &mut InpsgmTp) {
         while is.tnode.level <= is.stack[is.l as usize].level {
           write_struc(cnn, is.idocn.clone(), is.strtp.clone(),
             is.stack[is.l as usize-1].clone(),
-            is.stack[is.l as usize  ].clone());
+            is.stack[is.l as usize  ].clone())?;
           is.stack = is.stack[..is.l as usize].to_vec();
           is.l -= 1;
         }
@@ -521,7 +545,7 @@ pub fn proc_segmt_struct(cnn: &Connection, sline: &ParslTp, is: &mut InpsgmTp) {
       is.fnode.dname = Default::default();
       is.fnode.dqual = Default::default();
     }
-    return;
+    return Ok(());
   }
 
   if is.tnode.rname == SEGMENT && is.tnode.dname.len() > 0 {
@@ -532,19 +556,20 @@ pub fn proc_segmt_struct(cnn: &Connection, sline: &ParslTp, is: &mut InpsgmTp) {
       is.tnode.dqual = QUALF.to_string();
     }
     if sline.label.ident == LEVEL {
-      let l = sline.value.parse::<usize>().unwrap();
+      let l = get_number(&sline.value)?;
       is.tnode.level = l;
     }
-    return;
+    return Ok(());
   }
 
   if is.tnode.rname == IDOC {
     if sline.label.ident == EXTENSION {
       is.idocn = sline.value.clone();
-      clear_struc(cnn, is.idocn.clone(), is.strtp.clone());
+      clear_struc(cnn, is.idocn.clone(), is.strtp.clone())?;
     }
-    return;
+    return Ok(());
   }
+  return Ok(());
 }
 
 // proc_items_master.rs - Get IDOC item detail (records, groups, segments and
@@ -571,6 +596,7 @@ pub fn proc_items_master(sline: &ParslTp, ii: &mut InpitmTp) {
       } else if sline.label.recnm == SEGMENT {
         ii.colss[0] = sline.value.clone();
         ii.colss[2] = String::new();
+        set_group_head(ii, &sline.value);
       }
     }
     return;
@@ -608,6 +634,7 @@ pub fn proc_items_master(sline: &ParslTp, ii: &mut InpitmTp) {
             name: ii.colsg[0].clone(),
             typi: ii.stack[ii.l as usize].label.recnm.clone(),
             seqn: ii.gseqn.clone(),
+            head: String::new(),
             cols: ii.colsg.clone()
           });
         }
@@ -669,44 +696,63 @@ pub fn proc_items_master(sline: &ParslTp, ii: &mut InpitmTp) {
   }
 }
 
+// set_group_head.rs - Keep first segment found within a group as the group head
+// segment, the one whose occurrences are ruled by group loop limits
+// (2021-07-01 bar8tl)
+fn set_group_head(ii: &mut InpitmTp, segmn: &String) {
+  if ii.l < 1 || ii.stack[ii.l as usize-1].label.recnm != GROUP {
+    return;
+  }
+  let grpnm = ii.stack[ii.l as usize-1].value.clone();
+  if let Some(g) = ii.lgrup.iter_mut().rev().find(|g| g.name == grpnm) {
+    if g.head.len() == 0 {
+      g.head = segmn.clone();
+    }
+  }
+}
+
 // write_items_indb.rs - Functions to clear/write IDOC item detail records (idoc,
 // group, segment and field) into the local DB (2021-07-01 bar8tl)
-pub fn write_items(cnn: &Connection, w: OutitmTp) {
+pub fn write_items(cnn: &Connection, w: OutitmTp) -> Result<()> {
   cnn.execute(
     "INSERT INTO items VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15)",
     (w.idocn, w.rname, w.dname, w.rclas, w.rtype, w.dtype, w.dtext, w.level,
      w.stats, w.minlp, w.maxlp, w.lngth, w.seqno, w.strps, w.endps,))
-    .expect("Items insertion error");
+    .map_err(|e| repo_error(e, "items"))?;
+  return Ok(());
 }
 
-pub fn clear_items(cnn: &Connection, idocn: String) {
+pub fn clear_items(cnn: &Connection, idocn: String) -> Result<()> {
   cnn.execute("DELETE FROM items WHERE idocn=?1", (idocn,))
-    .expect("Items clearing error");
+    .map_err(|e| repo_error(e, "items"))?;
+  return Ok(());
 }
 
 // write_struc_indb.rs - Functions to clear/write IDOC structure records (idoc,
 // group and segment levels) into the local DB (2021-07-01 bar8tl)
 pub fn write_struc(cnn: &Connection, idocn: String, strtp: String, pnode: KeystTp,
-  cnode: KeystTp) {
+  cnode: KeystTp) -> Result<()> {
   let mut pdnam = String::new();
   let mut cdnam = String::new();
   if strtp == GRP.to_uppercase() {
     let test = pnode.dname.parse::<usize>();
     match test {
-      Ok(_ok) => pdnam = format!("{:02}", pnode.dname.parse::<usize>().unwrap()),
-      Err(_e) => pdnam = pnode.dname.clone(),
+      Ok(pnumb) => pdnam = format!("{:02}", pnumb),
+      Err(_e)   => pdnam = pnode.dname.clone(),
     }
-    cdnam = format!("{:02}", cnode.dname.parse::<usize>().unwrap());
+    cdnam = format!("{:02}", get_number(&cnode.dname)?);
   }
   cnn.execute(
     "INSERT INTO struc VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13)",
     (idocn, strtp,       pnode.level, pnode.rname, pnode.pseqn,
      pdnam, pnode.dname, pnode.dqual, cnode.rname, pnode.seqno,
      cdnam, cnode.dname, cnode.dqual,))
-    .expect("Struc insertion error");
+    .map_err(|e| repo_error(e, "struc"))?;
+  return Ok(());
 }
 
-pub fn clear_struc(cnn: &Connection, idocn: String, strtp: String) {
+pub fn clear_struc(cnn: &Connection, idocn: String, strtp: String) -> Result<()> {
   cnn.execute("DELETE FROM struc WHERE idocn=?1 and strtp=?2", (idocn, strtp,))
-    .expect("Struc clearing error");
+    .map_err(|e| repo_error(e, "struc"))?;
+  return Ok(());
 }
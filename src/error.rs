@@ -0,0 +1,194 @@
+// error.rs - Error type used across the edimaps program. Public functions return
+// Result<T> and errors travel up to main, which prints them with the file, line,
+// segment or table they refer to (2021-07-01 bar8tl)
+use std::fmt;
+
+// types.rs - Error variants and run summary (2021-07-01 bar8tl)
+#[derive(Debug)]
+pub enum ErrorTp {
+  Config      { file: String, detail: String },
+  Repository  { table: String, detail: String },
+  Spreadsheet { file: String, sheet: String, detail: String },
+  IdocFormat  { file: String, line: usize, segmn: String, detail: String },
+  DefNotFound { file: String, line: usize, idocn: String, segmn: String,
+                field: String },
+  Io          { file: String, detail: String },
+  Json        { file: String, detail: String },
+  Backup      { file: String, line: usize, detail: String },
+  EdiFormat   { file: String, segno: usize, segmn: String, detail: String }
+}
+
+pub type Result<T> = std::result::Result<T, ErrorTp>;
+
+#[derive(Debug)]
+pub struct FailrTp {         // File failed within a run
+  pub flide: String,
+  pub error: ErrorTp
+}
+
+#[derive(Debug, Default)]
+pub struct RunsmTp {         // Run summary
+  pub nfile: usize,          // Files processed
+  pub nokay: usize,          // Files concluded with return code OKAY
+  pub nrjct: usize,          // Files concluded with other return codes
+  pub fails: Vec<FailrTp>    // Files failed with an error
+}
+
+impl fmt::Display for ErrorTp {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ErrorTp::Config { file, detail } =>
+        write!(f, "Config error{}: {}", at_file(file, 0), detail),
+      ErrorTp::Repository { table, detail } =>
+        write!(f, "Repository error [{}]: {}", table, detail),
+      ErrorTp::Spreadsheet { file, sheet, detail } =>
+        write!(f, "Spreadsheet error{} sheet '{}': {}", at_file(file, 0), sheet,
+          detail),
+      ErrorTp::IdocFormat { file, line, segmn, detail } =>
+        write!(f, "IDoc format error{}{}: {}", at_file(file, *line),
+          at_segmt(segmn), detail),
+      ErrorTp::DefNotFound { file, line, idocn, segmn, field } =>
+        write!(f, "Definition not found{}: IDoc type {}{}{}", at_file(file, *line),
+          idocn, at_segmt(segmn), if field.len() > 0 { format!(" field {}", field) }
+          else { String::new() }),
+      ErrorTp::Io { file, detail } =>
+        write!(f, "I/O error{}: {}", at_file(file, 0), detail),
+      ErrorTp::Json { file, detail } =>
+        write!(f, "JSON error{}: {}", at_file(file, 0), detail),
+      ErrorTp::Backup { file, line, detail } =>
+        write!(f, "Backup format error{}: {}", at_file(file, *line), detail),
+      ErrorTp::EdiFormat { file, segno, segmn, detail } =>
+        write!(f, "EDI format error{}{}{}: {}", at_file(file, 0), if *segno > 0 {
+          format!(" segment {}", segno) } else { String::new() }, if segmn.len() > 0
+          { format!(" [{}]", segmn) } else { String::new() }, detail)
+    }
+  }
+}
+
+impl std::error::Error for ErrorTp {}
+
+impl ErrorTp {
+  // at - Complete the error with file and line being processed, when the function
+  // raising it did not know them
+  pub fn at(mut self, flide: &str, lineno: usize) -> ErrorTp {
+    match &mut self {
+      ErrorTp::IdocFormat  { file, line, .. } |
+      ErrorTp::DefNotFound { file, line, .. } |
+      ErrorTp::Backup      { file, line, .. } => {
+        if file.len() == 0 { *file = flide.to_string(); }
+        if *line == 0 { *line = lineno; }
+      },
+      ErrorTp::EdiFormat { file, .. } => {
+        if file.len() == 0 { *file = flide.to_string(); }
+      },
+      ErrorTp::Config { file, .. } | ErrorTp::Io { file, .. } |
+      ErrorTp::Json   { file, .. } | ErrorTp::Spreadsheet { file, .. } => {
+        if file.len() == 0 { *file = flide.to_string(); }
+      },
+      ErrorTp::Repository { .. } => {}
+    }
+    return self;
+  }
+}
+
+impl From<rusqlite::Error> for ErrorTp {
+  fn from(e: rusqlite::Error) -> ErrorTp {
+    return ErrorTp::Repository { table: String::new(), detail: e.to_string() };
+  }
+}
+
+impl From<std::io::Error> for ErrorTp {
+  fn from(e: std::io::Error) -> ErrorTp {
+    return ErrorTp::Io { file: String::new(), detail: e.to_string() };
+  }
+}
+
+impl From<serde_json::Error> for ErrorTp {
+  fn from(e: serde_json::Error) -> ErrorTp {
+    return ErrorTp::Json { file: String::new(), detail: e.to_string() };
+  }
+}
+
+// defn_error.rs - Map a failed lookup into the IDoc definition: no rows means the
+// segment or field is not defined for the IDoc type (2021-07-01 bar8tl)
+pub fn defn_error(e: rusqlite::Error, idocn: &str, segmn: &str, field: &str) ->
+  ErrorTp {
+  match e {
+    rusqlite::Error::QueryReturnedNoRows => ErrorTp::DefNotFound {
+      file: String::new(), line: 0, idocn: idocn.to_string(),
+      segmn: segmn.to_string(), field: field.to_string() },
+    _ => ErrorTp::Repository { table: "items".to_string(), detail: e.to_string() }
+  }
+}
+
+// repo_error.rs - Map a failed statement on a repository table (2021-07-01 bar8tl)
+pub fn repo_error(e: rusqlite::Error, table: &str) -> ErrorTp {
+  return ErrorTp::Repository { table: table.to_string(), detail: e.to_string() };
+}
+
+// io_error.rs - Map a failed file operation (2021-07-01 bar8tl)
+pub fn io_error(e: std::io::Error, file: &str) -> ErrorTp {
+  return ErrorTp::Io { file: file.to_string(), detail: e.to_string() };
+}
+
+// json_error.rs - Map a failed JSON read or write (2021-07-01 bar8tl)
+pub fn json_error(e: serde_json::Error, file: &str) -> ErrorTp {
+  return ErrorTp::Json { file: file.to_string(), detail: e.to_string() };
+}
+
+// sheet_error.rs - Map a failed MS Excel workbook operation (2021-07-01 bar8tl)
+pub fn sheet_error<E: fmt::Display>(e: E, file: &str, sheet: &str) -> ErrorTp {
+  return ErrorTp::Spreadsheet { file: file.to_string(), sheet: sheet.to_string(),
+    detail: e.to_string() };
+}
+
+// format_error.rs - Build an IDoc format error (2021-07-01 bar8tl)
+pub fn format_error(segmn: &str, detail: String) -> ErrorTp {
+  return ErrorTp::IdocFormat { file: String::new(), line: 0,
+    segmn: segmn.to_string(), detail: detail };
+}
+
+// edi_error.rs - Build an EDI (X12/EDIFACT) format error (2021-07-01 bar8tl)
+pub fn edi_error(segno: usize, segmn: &str, detail: String) -> ErrorTp {
+  return ErrorTp::EdiFormat { file: String::new(), segno: segno,
+    segmn: segmn.to_string(), detail: detail };
+}
+
+// print_summary.rs - Print run summary and errors, returning the process exit
+// code: 0 when all went fine, 1 when the run or any of its files failed
+// (2021-07-01 bar8tl)
+pub fn print_summary(rslt: &Result<RunsmTp>) -> i32 {
+  match rslt {
+    Ok(rs) => {
+      if rs.nfile > 0 {
+        println!("Files processed: {}, okay: {}, rejected: {}, failed: {}",
+          rs.nfile, rs.nokay, rs.nrjct, rs.fails.len());
+      }
+      for fl in &rs.fails {
+        eprintln!("  {}: {}", fl.flide, fl.error);
+      }
+      return if rs.fails.len() > 0 { 1 } else { 0 };
+    },
+    Err(e) => {
+      eprintln!("Error: {}", e);
+      return 1;
+    }
+  }
+}
+
+fn at_file(file: &str, line: usize) -> String {
+  if file.len() == 0 {
+    return String::new();
+  }
+  if line == 0 {
+    return format!(" in {}", file);
+  }
+  return format!(" in {} line {}", file, line);
+}
+
+fn at_segmt(segmn: &str) -> String {
+  if segmn.len() == 0 {
+    return String::new();
+  }
+  return format!(" segment {}", segmn);
+}
@@ -3,17 +3,34 @@
 mod assets;
 mod config;
 mod definitn;
+mod error;
 mod maps;
+mod queries;
+mod readedi;
 mod readidoc;
+mod refdata;
 mod reposit;
 
-use crate::assets::{add_cdcodes, add_cddata, add_idoctp, add_index, add_wkflow};
+use crate::assets::{add_cdcodes, add_cddata, add_cdfield, add_idoctp, add_index,
+  add_pipeln, add_wkflow};
 use crate::config::get_config;
 use crate::definitn::add_definitn;
-use crate::maps::proc_maps::proc_maps;
+use crate::error::{print_summary, Result, RunsmTp};
+use crate::maps::check_maps::check_maps;
+use crate::maps::coverage::coverage_report;
+use crate::maps::diff_maps::{diff_maps, split_specid};
+use crate::maps::proc_maps::{from_maps, proc_maps};
+use crate::maps::search_maps::search_maps;
 use crate::maps::out_maps::out_maps;
+use crate::queries::run_query;
+use crate::readedi::toidoc::apply_maps;
+use crate::readidoc::pipeline::run_pipeline;
 use crate::readidoc::read_idocs::read_idocs;
+use crate::readidoc::watch::watch_idocs;
+use crate::refdata::{delete_refdata, export_refdata, list_refdata, set_refdata,
+  show_refdata};
 use crate::reposit::ini_repo;
+use std::collections::HashMap;
 
 include!("args.rs");
 
@@ -21,44 +38,109 @@ const CONFIG_FILENAME: &str = ".\\.edimapsrc";
 
 fn main() {
   let cli = Cli::parse();
-  let mut rc = get_config(CONFIG_FILENAME);
+  let rslt = run_command(&cli);
+  std::process::exit(print_summary(&rslt));
+}
+
+// run_command.rs - Dispatch the command line subcommand (2021-07-01 bar8tl)
+fn run_command(cli: &Cli) -> Result<RunsmTp> {
+  let mut rc = get_config(CONFIG_FILENAME)?;
   match &cli.command {
     Some(Commands::Init{ file }) => {
-      ini_repo(&rc.general.dbopt, file);
+      ini_repo(&rc.general.dbopt, file)?;
     }
     Some(Commands::Add{ file, refer, def }) => {
              if *refer && *file == "cdcodes".to_string() {
-        add_cdcodes (&rc.general.dbopt, format!("{}_codes.json",  rc.refers.refdr));
+        add_cdcodes (&rc.general.dbopt, format!("{}_codes.json",  rc.refers.refdr))?;
       } else if *refer && *file == "cddata".to_string()  {
-        add_cddata  (&rc.general.dbopt, format!("{}_transp.json", rc.refers.refdr));
+        add_cddata  (&rc.general.dbopt, format!("{}_transp.json", rc.refers.refdr))?;
+      } else if *refer && *file == "cdfield".to_string() {
+        add_cdfield (&rc.general.dbopt, format!("{}_cdfield.json",rc.refers.refdr))?;
       } else if *refer && *file == "index".to_string()   {
-        add_index   (&rc.general.dbopt, &rc.refers.idxpt,        &rc.refers.tabid);
+        add_index   (&rc.general.dbopt, &rc.refers.idxpt,        &rc.refers.tabid)?;
       } else if *refer && *file == "idoctp".to_string()  {
-        add_idoctp  (&rc.general.dbopt, format!("{}idoctp.json",  rc.refers.refdr));
+        add_idoctp  (&rc.general.dbopt, format!("{}idoctp.json",  rc.refers.refdr))?;
       } else if *refer && *file == "wkflow".to_string()  {
-        add_wkflow  (&rc.general.dbopt, format!("{}wkflow.json",  rc.refers.refdr));
+        add_wkflow  (&rc.general.dbopt, format!("{}wkflow.json",  rc.refers.refdr))?;
+      } else if *refer && *file == "pipeln".to_string()  {
+        add_pipeln  (&rc.general.dbopt, format!("{}pipeln.json",  rc.refers.refdr))?;
       } else if *def {
-        add_definitn(&rc.general.dbopt, &format!("{}{}",          rc.refers.defdr, *file));
+        add_definitn(&rc.general.dbopt, &format!("{}{}",          rc.refers.defdr, *file))?;
       }
     }
-    Some(Commands::Map{ file, repo, json, text }) => {
-      let mapid = file.to_string();
+    Some(Commands::Map{ action: Some(MapCommands::Diff{ spec1, spec2, format,
+      output }), .. }) => {
+      diff_maps(&rc.general.dbopt, spec1, spec2, format, output)?;
+    }
+    Some(Commands::Map{ action: Some(MapCommands::Check{ spec, idoc, format }),
+      .. }) => {
+      (rc.maps.mapid, rc.maps.chgnr) = split_specid(spec);
+      check_maps(&rc.general.dbopt, &rc.refers, &rc.maps, idoc, format)?;
+    }
+    Some(Commands::Map{ action: Some(MapCommands::Apply{ spec, edifile, idoc,
+      output }), .. }) => {
+      (rc.maps.mapid, rc.maps.chgnr) = split_specid(spec);
+      apply_maps(&rc.general.dbopt, &rc.refers, &rc.maps, &rc.wkflow, edifile, idoc,
+        output)?;
+    }
+    Some(Commands::Map{ action: None, file, repo, json, text, xlsx, from }) => {
+      let mapid = file.clone().unwrap_or_default();
       let flds: Vec<&str> = mapid.split('.').collect();
       rc.maps.mapid = flds[0].to_string();
       if flds.len() > 1 {
         rc.maps.chgnr = flds[1].to_string();
       }
-      proc_maps(&rc.general.dbopt, &rc.refers, &rc.maps, *repo, *json, *text);
+      if let Some(from) = from {
+        from_maps(&rc.general.dbopt, &rc.refers, &rc.maps, from, *repo, *json,
+          *text, *xlsx)?;
+      } else {
+        proc_maps(&rc.general.dbopt, &rc.refers, &rc.maps, *repo, *json, *text,
+          *xlsx)?;
+      }
     }
     Some(Commands::Out{ templ, list, count }) => {
       let mut omode: String = "count".to_string();
       if *list  { omode = "list" .to_string(); }
       if *count { omode = "count".to_string(); }
-      out_maps(&rc.general.dbopt, &rc.refers, &rc.maps, templ.to_string(), omode);
+      out_maps(&rc.general.dbopt, &rc.refers, &rc.maps, templ.to_string(), omode)?;
+    }
+    Some(Commands::Query{ name, messg, mvers, field, mapid, idoc, format,
+      output }) => {
+      let parms: HashMap<String, String> = [("messg", messg), ("mvers", mvers),
+        ("field", field), ("mapid", mapid), ("idoc", idoc)].iter()
+        .filter_map(|(k, v)| (*v).clone().map(|v| (k.to_string(), v))).collect();
+      run_query(&rc.general.dbopt, &rc.general.qrypt, name, &parms, format,
+        output)?;
+    }
+    Some(Commands::Search{ terms, messg, limit, format, rebuild }) => {
+      search_maps(&rc.general.dbopt, terms, messg, *limit, format, *rebuild)?;
+    }
+    Some(Commands::Report{ kind: ReportCommands::Coverage{ idoc, messg, format,
+      output, all } }) => {
+      coverage_report(&rc.general.dbopt, idoc, messg, format, output, *all)?;
+    }
+    Some(Commands::Run{ pipeline, file }) => {
+      return run_pipeline(&rc.general.dbopt, &rc.wkflow, pipeline, file);
+    }
+    Some(Commands::Ref{ action }) => match action {
+      RefCommands::List{ table } => list_refdata(&rc.general.dbopt, table)?,
+      RefCommands::Show{ table, keys } =>
+        show_refdata(&rc.general.dbopt, table, keys)?,
+      RefCommands::Set{ table, keys, value } =>
+        set_refdata(&rc.general.dbopt, table, keys, value)?,
+      RefCommands::Delete{ table, keys } =>
+        delete_refdata(&rc.general.dbopt, table, keys)?,
+      RefCommands::Export{ table, output } =>
+        export_refdata(&rc.general.dbopt, table, output)?,
+    },
+    Some(Commands::Step{ stage, file, single:_, batch:_, watch:true }) => {
+      return watch_idocs(&rc.general.dbopt, stage, &rc.wkflow, &file.to_string());
     }
-    Some(Commands::Step{ stage, file, single, batch:_ }) => {
-      read_idocs(&rc.general.dbopt, stage, &rc.wkflow, &file.to_string(), *single);
+    Some(Commands::Step{ stage, file, single, batch:_, watch:_ }) => {
+      return read_idocs(&rc.general.dbopt, stage, &rc.wkflow, &file.to_string(),
+        *single);
     }
     None => {}
   }
+  return Ok(RunsmTp { ..Default::default() });
 }
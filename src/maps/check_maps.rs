@@ -0,0 +1,192 @@
+// check_maps.rs - Cross-check a mapping specification in the repository against
+// the definition of the IDoc type it maps: segments not defined in the IDoc type,
+// target fields not defined in their segment, segments out of the definition
+// order and mandatory segments never mapped (2021-07-01 bar8tl)
+use crate::config::{MapsTp, RefersTp};
+use crate::error::{json_error, ErrorTp, Result};
+use crate::maps::fromrepo::read_cr_repo;
+use crate::maps::tojson::SpecsTp;
+use crate::maps::proc_maps::get_mapdetail;
+use crate::readidoc::validate::{load_definition, ValidTp, MANDATORY,
+  MISSING_MANDATORY, UNKNOWN_FIELD, UNKNOWN_SEGMENT};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashSet;
+
+// symbols.rs - Constants used to check mapping specs (2021-07-01 bar8tl)
+pub const WRONG_ORDER: &str = "WRONG_ORDER";
+const CTRL   : &str = "CTRL";    // Group of control record
+const CONTROL: &str = "CONTROL"; // Control record definition
+const EMPTY  : &str = "<empty>";
+
+// types.rs - Data types of mapping specs check (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CfindTp {         // Check finding
+  pub group: String,
+  pub segmn: String,
+  pub field: String,
+  pub rulid: String,
+  pub dtext: String
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CheckTp {         // Check report of a mapping specs
+  pub specs: String,         // mapid.chgnr
+  pub idocn: String,
+  pub nfind: usize,
+  pub findg: Vec<CfindTp>
+}
+
+// check_maps.rs - Check a mapping specs against its IDoc type, taken from the
+// index (idoct) unless given, and print findings as text or json
+// (2021-07-01 bar8tl)
+// Command line: edimaps map check <mapid.chgnr> [-i <idoc-type>] [-f text|json]
+pub fn check_maps(dbopt: &String, rfr: &RefersTp, map: &MapsTp,
+  idocn: &Option<String>, outfm: &String) -> Result<()> {
+  let cnn = Connection::open(dbopt)?;
+  let idocn = match idocn {
+    Some(i) => i.clone(),
+    None    => get_mapdetail(rfr, map)?.idoct
+  };
+  let mut v = ValidTp { ..Default::default() };
+  load_definition(&cnn, &mut v, &idocn)?;
+  let sp = read_cr_repo(&cnn, &map.mapid, &map.chgnr)?;
+  let mut ck = CheckTp { specs: format!("{}.{}", map.mapid, map.chgnr),
+    idocn: idocn.clone(), findg: check_specs(&v, &sp), ..Default::default() };
+  ck.nfind = ck.findg.len();
+  match outfm.as_str() {
+    "text" => {
+      println!("Mapping specs {} against IDoc type {}", ck.specs, ck.idocn);
+      for f in &ck.findg {
+        println!("{:18}|{:12}|{:12}|{:10}|{}", f.rulid, f.group, f.segmn, f.field,
+          f.dtext);
+      }
+      println!("Findings: {}", ck.nfind);
+    },
+    "json" => println!("{}", serde_json::to_string_pretty(&ck)
+      .map_err(|e| json_error(e, ""))?),
+    _      => return Err(ErrorTp::Config { file: String::new(), detail: format!(
+      "Output format '{}' not supported, use text or json", outfm) })
+  }
+  return Ok(());
+}
+
+// check_specs.rs - Findings of a mapping specs against the definition of its IDoc
+// type. Mandatory segments are looked for only under the segments mapped
+// (2021-07-01 bar8tl)
+pub fn check_specs(v: &ValidTp, sp: &SpecsTp) -> Vec<CfindTp> {
+  let mut findg: Vec<CfindTp> = Vec::new();
+  let mut mappd: HashSet<usize> = HashSet::new();
+  for g in &sp.groups {
+    let mut lastd: Option<(usize, String)> = None;
+    for s in &g.segments {
+      let find = |rulid: &str, field: &str, dtext: String| CfindTp {
+        group: g.group.clone(), segmn: if s.segment.len() > 0 { s.segment.clone() }
+        else { s.name.clone() }, field: field.to_string(), rulid: rulid.to_string(),
+        dtext: dtext };
+      let fdefs = if g.group == CTRL {
+        v.rcdef.get(CONTROL)
+      } else {
+        let i = match get_segment(v, &s.segment, &s.name) {
+          Ok(i)  => i,
+          Err(e) => { findg.push(find(UNKNOWN_SEGMENT, "", e)); continue; }
+        };
+        if let Some((l, lname)) = &lastd {
+          if i < *l {
+            findg.push(find(WRONG_ORDER, "", format!(
+              "Segment placed after {}, ahead of it in the IDoc definition",
+              lname)));
+          }
+        }
+        lastd = Some((i, v.sgdef[i].dname.clone()));
+        mappd.insert(i);
+        v.rcdef.get(&v.sgdef[i].dname)
+      };
+      for f in s.fields.iter().filter(|f| f.field.len() > 0 && f.field != EMPTY) {
+        let known = fdefs.map(|l| l.iter().any(|d| d.dname == f.field))
+          .unwrap_or(false);
+        if !known {
+          findg.push(find(UNKNOWN_FIELD, &f.field, format!(
+            "Field not defined in {}", if g.group == CTRL { CONTROL } else {
+            "segment" })));
+        }
+      }
+    }
+  }
+  for (i, d) in v.sgdef.iter().enumerate() {
+    let reqrd = d.prnt.map(|p| mappd.contains(&p)).unwrap_or(true);
+    if d.stats == MANDATORY && reqrd && !mappd.contains(&i) {
+      findg.push(CfindTp { segmn: d.dname.clone(), rulid:
+        MISSING_MANDATORY.to_string(), dtext: format!(
+        "Mandatory segment {} not mapped", d.dtype), ..Default::default() });
+    }
+  }
+  return findg;
+}
+
+// get_segment.rs - Get segment definition by segment ID (E2...) or, when the spec
+// has no ID, by segment type (E1...). An unknown ID whose type is defined is
+// reported with the ID of the definition (2021-07-01 bar8tl)
+fn get_segment(v: &ValidTp, segmn: &String, sgmtp: &String) ->
+  std::result::Result<usize, String> {
+  if segmn.len() > 0 {
+    if let Some(i) = v.sgidx.get(segmn) {
+      return Ok(*i);
+    }
+    if let Some(i) = v.sgtyp.get(sgmtp) {
+      return Err(format!("Segment {} not defined, type {} is {}", segmn, sgmtp,
+        v.sgdef[*i].dname));
+    }
+    return Err(format!("Segment {} not defined", segmn));
+  }
+  return v.sgtyp.get(sgmtp).copied().ok_or(format!("Segment type {} not defined",
+    sgmtp));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::maps::tojson::{FieldTp, GroupTp, SegmentTp};
+  use crate::readidoc::samples::{strict_repo, work_folder, ITYPE};
+
+  fn sgm(segment: &str, name: &str, fields: &[&str]) -> SegmentTp {
+    return SegmentTp { segment: segment.to_string(), name: name.to_string(),
+      fields: fields.iter().map(|f| FieldTp { field: f.to_string(),
+      ..Default::default() }).collect(), ..Default::default() };
+  }
+
+  #[test]
+  fn check_specs_finds_specs_out_of_idoc_definition() {
+    let dbopt = strict_repo(&work_folder("maps-check"));
+    let mut v = ValidTp { ..Default::default() };
+    load_definition(&Connection::open(&dbopt).unwrap(), &mut v, &ITYPE.to_string())
+      .unwrap();
+    // a mandatory child of the item segment, not mapped as its parent is not
+    let i = v.sgidx["E2EDPA1003"];
+    v.sgdef[i].stats = MANDATORY.to_string();
+    let sp = SpecsTp { groups: vec![
+      GroupTp { group: CTRL.to_string(), segments: vec![sgm("", "EDI_DC40",
+        &["DOCNUM", "ZZCTRL"])], ..Default::default() },
+      GroupTp { group: "MAIN".to_string(), segments: vec![
+        sgm("E2EDK01005", "E1EDK01", &["CURCY", "ZZFIELD", EMPTY]),
+        sgm("E2EDK14", "E1EDK14", &["QUALF"]),
+        sgm("E2EDKA1003", "E1EDKA1", &["PARVW"]),
+        sgm("E2EDP01010", "E1EDP01", &["POSEX"]),
+        sgm("", "E1EDS01", &["SUMID"])], ..Default::default() }],
+      ..Default::default() };
+    let findg = check_specs(&v, &sp);
+    let rows: Vec<[&str; 5]> = findg.iter().map(|f| [f.rulid.as_str(),
+      f.group.as_str(), f.segmn.as_str(), f.field.as_str(), f.dtext.as_str()])
+      .collect();
+    assert_eq!(rows, vec![
+      [UNKNOWN_FIELD, CTRL, "EDI_DC40", "ZZCTRL", "Field not defined in CONTROL"],
+      [UNKNOWN_FIELD, "MAIN", "E2EDK01005", "ZZFIELD",
+        "Field not defined in segment"],
+      [WRONG_ORDER, "MAIN", "E2EDKA1003", "",
+        "Segment placed after E2EDK14, ahead of it in the IDoc definition"],
+      [UNKNOWN_SEGMENT, "MAIN", "E2EDP01010", "",
+        "Segment E2EDP01010 not defined, type E1EDP01 is E2EDP01011"],
+      [MISSING_MANDATORY, "", "E2EDP01011", "",
+        "Mandatory segment E1EDP01 not mapped"]]);
+  }
+}
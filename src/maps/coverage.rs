@@ -0,0 +1,268 @@
+// coverage.rs - Generates coverage report of an IDoc type across the mapping
+// specifications in the repository: matrix of segment/field by partner map with
+// the source expression each one maps, as CSV or MS Excel (2021-07-01 bar8tl)
+use crate::error::{io_error, repo_error, sheet_error, ErrorTp, Result};
+use rusqlite::Connection;
+use rust_xlsxwriter::{Format, Workbook};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+// symbols.rs - Constants used for coverage report (2021-07-01 bar8tl)
+const COVERAGE: &str = "Coverage";   // Worksheet name
+const NODEF   : &str = "(not in definition)";
+
+// types.rs - Data types of coverage report (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default)]
+pub struct CovrgTp {
+  pub colmn: Vec<String>,                    // Partner maps (mapid[.chgnr])
+  pub rows : Vec<(String, String, String)>,  // Segment, field, field text
+  pub cells: HashMap<(usize, usize), String> // (row, map) -> source expression
+}
+
+// coverage_report.rs - Build coverage matrix of an IDoc type, optionally for one
+// message, and write it as CSV (to stdout or file) or MS Excel (to file). Rows are
+// the fields of the IDoc definition in its order, only those mapped by some map
+// unless all are requested; fields mapped but not defined are added at the end
+// (2021-07-01 bar8tl)
+// Command line: edimaps report coverage <idoc-type> [-m <messg>] [-f csv|xlsx]
+//   [-o <file>] [-a]
+pub fn coverage_report(dbopt: &String, idocn: &String, messg: &Option<String>,
+  outfm: &String, opath: &Option<String>, all: bool) -> Result<()> {
+  let cnn = Connection::open(dbopt)?;
+  let cv = coverage_matrix(&cnn, idocn, &messg.clone().unwrap_or_default())?;
+  let shown = shown_rows(&cv, all);
+  match outfm.as_str() {
+    "csv"  => write_coverage_csv(&cv, &shown, opath)?,
+    "xlsx" => {
+      let opath = opath.clone().ok_or(ErrorTp::Config { file: String::new(),
+        detail: "Output file required for xlsx format".to_string() })?;
+      write_coverage_xlsx(&cv, &shown, &opath)?;
+    },
+    _      => return Err(ErrorTp::Config { file: String::new(), detail: format!(
+      "Output format '{}' not supported, use csv or xlsx", outfm) })
+  }
+  if opath.is_some() {
+    println!("Coverage |{:5} fields|{:4} maps|", shown.len(), cv.colmn.len());
+  }
+  return Ok(());
+}
+
+// coverage_matrix.rs - Build coverage matrix of an IDoc type from the maps in the
+// index for it, all or those of a message (when not empty). Expressions of a
+// field mapped several times in a map are joined in one cell (2021-07-01 bar8tl)
+pub fn coverage_matrix(cnn: &Connection, idocn: &String, messg: &String) ->
+  Result<CovrgTp> {
+  let mut cv = CovrgTp { ..Default::default() };
+  let mut mapix: HashMap<(String, String), usize> = HashMap::new();
+  let mut stmt = cnn.prepare("SELECT mapid, chgnr FROM indix WHERE idoct=?1 and
+    (?2='' or messg=?2) ORDER BY mapid, chgnr;").map_err(|e| repo_error(e, "indix"))?;
+  let mut rows = stmt.query([idocn, messg]).map_err(|e| repo_error(e, "indix"))?;
+  while let Some(row) = rows.next().map_err(|e| repo_error(e, "indix"))? {
+    let (mapid, chgnr): (String, String) = (row.get(0)?, row.get(1)?);
+    mapix.insert((mapid.clone(), chgnr.clone()), cv.colmn.len());
+    cv.colmn.push(if chgnr.len() > 0 { format!("{}.{}", mapid, chgnr) } else {
+      mapid });
+  }
+  if cv.colmn.len() == 0 {
+    return Err(ErrorTp::Repository { table: "indix".to_string(), detail: format!(
+      "No mapping specs for IDoc type {}{}", idocn, if messg.len() > 0 {
+      format!(" and message {}", messg) } else { String::new() }) });
+  }
+  let mut rowix: HashMap<(String, String), usize> = HashMap::new();
+  let mut stmt = cnn.prepare("SELECT s.dname, f.dname, f.dtext FROM items AS s
+    JOIN items AS f ON f.idocn=s.idocn and f.rname=s.dname and f.rtype=\"FIELDS\"
+    WHERE s.idocn=?1 and s.rname=\"SEGMENT\" ORDER BY s.seqno, f.seqno;")
+    .map_err(|e| repo_error(e, "items"))?;
+  let mut rows = stmt.query([idocn]).map_err(|e| repo_error(e, "items"))?;
+  while let Some(row) = rows.next().map_err(|e| repo_error(e, "items"))? {
+    let (segmn, field, dtext): (String, String, String) = (row.get(0)?, row.get(1)?,
+      row.get(2)?);
+    rowix.insert((segmn.clone(), field.clone()), cv.rows.len());
+    cv.rows.push((segmn, field, dtext));
+  }
+  let mut stmt = cnn.prepare("SELECT a.mapid, a.chgnr, a.sgmid, a.targt, a.sourc,
+    a.rcond FROM fields AS a JOIN indix AS b ON a.mapid=b.mapid and a.chgnr=b.chgnr
+    WHERE b.idoct=?1 and (?2='' or b.messg=?2) ORDER BY a.mapid, a.chgnr, a.rowno;")
+    .map_err(|e| repo_error(e, "fields"))?;
+  let mut rows = stmt.query([idocn, messg]).map_err(|e| repo_error(e, "fields"))?;
+  while let Some(row) = rows.next().map_err(|e| repo_error(e, "fields"))? {
+    let (mapid, chgnr, segmn, field, sourc, rcond): (String, String, String, String,
+      String, String) = (row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?,
+      row.get(4)?, row.get(5)?);
+    if field.len() == 0 {
+      continue;
+    }
+    let r = *rowix.entry((segmn.clone(), field.clone())).or_insert_with(|| {
+      cv.rows.push((segmn.clone(), field.clone(), NODEF.to_string()));
+      cv.rows.len() - 1
+    });
+    let m = mapix[&(mapid, chgnr)];
+    let exprs = if sourc.len() > 0 { sourc } else { rcond };
+    let cell = cv.cells.entry((r, m)).or_insert(String::new());
+    if cell.len() > 0 {
+      cell.push_str(" | ");
+    }
+    cell.push_str(if exprs.len() > 0 { &exprs } else { "x" });
+  }
+  return Ok(cv);
+}
+
+// shown_rows.rs - Rows of coverage matrix to report: those mapped by some map,
+// or all of them (2021-07-01 bar8tl)
+fn shown_rows(cv: &CovrgTp, all: bool) -> Vec<usize> {
+  return (0..cv.rows.len()).filter(|r| all ||
+    (0..cv.colmn.len()).any(|m| cv.cells.contains_key(&(*r, m)))).collect();
+}
+
+// write_coverage_csv.rs - Write coverage matrix as CSV (2021-07-01 bar8tl)
+fn write_coverage_csv(cv: &CovrgTp, shown: &Vec<usize>, opath: &Option<String>) ->
+  Result<()> {
+  let s = coverage_csv(cv, shown);
+  match opath {
+    Some(p) => {
+      let mut file = File::create(p).map_err(|e| io_error(e, p))?;
+      file.write_all(s.as_bytes()).map_err(|e| io_error(e, p))?;
+    },
+    None => print!("{}", s)
+  }
+  return Ok(());
+}
+
+// coverage_csv.rs - Format rows of coverage matrix as CSV, headed by the column
+// names (2021-07-01 bar8tl)
+fn coverage_csv(cv: &CovrgTp, shown: &Vec<usize>) -> String {
+  let mut s = String::new();
+  let mut hdr = vec!["segment".to_string(), "field".to_string(), "text".to_string(),
+    "maps".to_string()];
+  hdr.extend(cv.colmn.iter().cloned());
+  s.push_str(&csv_line(&hdr));
+  for r in shown {
+    let (segmn, field, dtext) = &cv.rows[*r];
+    let cells: Vec<String> = (0..cv.colmn.len())
+      .map(|m| cv.cells.get(&(*r, m)).cloned().unwrap_or_default()).collect();
+    let mut line = vec![segmn.clone(), field.clone(), dtext.clone(),
+      cells.iter().filter(|c| c.len() > 0).count().to_string()];
+    line.extend(cells);
+    s.push_str(&csv_line(&line));
+  }
+  return s;
+}
+
+// csv_line.rs - Format values as a CSV line, quoting those which need it
+// (2021-07-01 bar8tl)
+pub fn csv_line(vals: &Vec<String>) -> String {
+  let l: Vec<String> = vals.iter().map(|v| {
+    if v.contains(',') || v.contains('"') || v.contains('\n') {
+      format!("\"{}\"", v.replace('"', "\"\""))
+    } else {
+      v.clone()
+    }
+  }).collect();
+  return format!("{}\n", l.join(","));
+}
+
+// write_coverage_xlsx.rs - Write coverage matrix as MS Excel workbook
+// (2021-07-01 bar8tl)
+fn write_coverage_xlsx(cv: &CovrgTp, shown: &Vec<usize>, opath: &String) ->
+  Result<()> {
+  let mut wb = Workbook::new();
+  let ws = wb.add_worksheet();
+  let serr = |e: rust_xlsxwriter::XlsxError| sheet_error(e, opath, COVERAGE);
+  ws.set_name(COVERAGE).map_err(serr)?;
+  let bold = Format::new().set_bold();
+  for (c, h) in ["Segment", "Field", "Text", "Maps"].iter().enumerate() {
+    ws.write_string_with_format(0, c as u16, *h, &bold).map_err(serr)?;
+  }
+  for (m, h) in cv.colmn.iter().enumerate() {
+    ws.write_string_with_format(0, 4 + m as u16, h, &bold).map_err(serr)?;
+  }
+  for (i, r) in shown.iter().enumerate() {
+    let (segmn, field, dtext) = &cv.rows[*r];
+    let x = 1 + i as u32;
+    ws.write_string(x, 0, segmn).map_err(serr)?;
+    ws.write_string(x, 1, field).map_err(serr)?;
+    ws.write_string(x, 2, dtext).map_err(serr)?;
+    let mut nmaps = 0;
+    for m in 0..cv.colmn.len() {
+      if let Some(cell) = cv.cells.get(&(*r, m)) {
+        ws.write_string(x, 4 + m as u16, cell).map_err(serr)?;
+        nmaps += 1;
+      }
+    }
+    ws.write_number(x, 3, nmaps as f64).map_err(serr)?;
+  }
+  ws.set_freeze_panes(1, 4).map_err(serr)?;
+  ws.set_column_width(0, 14).map_err(serr)?;
+  ws.set_column_width(1, 14).map_err(serr)?;
+  ws.set_column_width(2, 36).map_err(serr)?;
+  wb.save(opath).map_err(serr)?;
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readidoc::samples::{sample_repo, work_folder, ITYPE};
+  use crate::reposit::ini_repo;
+
+  // coverage_repo.rs - Sample IDoc definition with two specs mapping it and one
+  // mapping another IDoc type
+  fn coverage_repo(wrkdr: &String) -> Connection {
+    let dbopt = sample_repo(wrkdr);
+    for table in ["indix", "fields"] {
+      ini_repo(&dbopt, &table.to_string()).unwrap();
+    }
+    let cnn = Connection::open(&dbopt).unwrap();
+    for (mapid, chgnr, messg, idoct) in [("tst810", "", "810", ITYPE),
+      ("tst811", "2", "INVOIC", ITYPE), ("tst856", "", "856", "DELVRY07")] {
+      cnn.execute("INSERT INTO indix (mapid, chgnr, messg, idoct) VALUES
+        (?1,?2,?3,?4);", [mapid, chgnr, messg, idoct]).unwrap();
+    }
+    for (j, (mapid, chgnr, sgmid, targt, sourc, rcond)) in [
+      ("tst810", "",  "E2EDK01005", "CURCY",   "CUR02", ""),
+      ("tst810", "",  "E2EDK01005", "ZZFIELD", "REF02", ""),
+      ("tst810", "",  "E2EDK14",    "QUALF",   "",      "Constant 011"),
+      ("tst810", "",  "E2EDK01005", "CURCY",   "CUR03", ""),
+      ("tst810", "",  "E2EDK01005", "",        "N101",  ""),
+      ("tst811", "2", "E2EDK01005", "CURCY",   "CUR02, default \"USD\"", ""),
+      ("tst811", "2", "E2EDK14",    "ORGID",   "",      ""),
+      ("tst856", "",  "E2EDL20",    "VBELN",   "BSN02", "")].iter().enumerate() {
+      cnn.execute("INSERT INTO fields (mapid, chgnr, sgmid, targt, sourc, rcond,
+        rowno) VALUES (?1,?2,?3,?4,?5,?6,?7);", [*mapid, *chgnr, *sgmid, *targt,
+        *sourc, *rcond, &format!("{:04}", j)]).unwrap();
+    }
+    return cnn;
+  }
+
+  #[test]
+  fn coverage_matrix_joins_expressions_of_each_map() {
+    let cnn = coverage_repo(&work_folder("maps-coverage"));
+    let cv = coverage_matrix(&cnn, &ITYPE.to_string(), &String::new()).unwrap();
+    assert_eq!(cv.colmn, vec!["tst810", "tst811.2"]);
+    let shown = shown_rows(&cv, false);
+    assert_eq!(coverage_csv(&cv, &shown), [
+      "segment,field,text,maps,tst810,tst811.2",
+      "E2EDK01005,CURCY,Currency,2,CUR02 | CUR03,\"CUR02, default \"\"USD\"\"\"",
+      "E2EDK14,QUALF,IDOC qualifer organization,1,Constant 011,",
+      "E2EDK14,ORGID,IDOC organization,1,,x",
+      "E2EDK01005,ZZFIELD,(not in definition),1,REF02,",
+      ""].join("\n"));
+    let all = shown_rows(&cv, true);
+    assert_eq!(all.len(), cv.rows.len());
+    assert!(all.len() > shown.len());
+    assert_eq!(cv.rows.last().unwrap().2, NODEF);
+    let cv = coverage_matrix(&cnn, &ITYPE.to_string(), &"INVOIC".to_string())
+      .unwrap();
+    assert_eq!(cv.colmn, vec!["tst811.2"]);
+    assert!(coverage_matrix(&cnn, &ITYPE.to_string(), &"856".to_string()).is_err());
+  }
+
+  #[test]
+  fn csv_line_quotes_values_which_need_it() {
+    let vals = ["plain", "a,b", "say \"hi\"", "two\nlines", ""]
+      .map(|v| v.to_string()).to_vec();
+    assert_eq!(csv_line(&vals),
+      "plain,\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\n");
+  }
+}
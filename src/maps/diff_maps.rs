@@ -0,0 +1,361 @@
+// diff_maps.rs - Compare two versions of a mapping specification held in the
+// repository (mapid plus change number), reporting added, removed and changed
+// groups, segments and fields as text, json or html (2021-07-01 bar8tl)
+use crate::error::{io_error, json_error, ErrorTp, Result};
+use crate::maps::fromrepo::read_cr_repo;
+use crate::maps::tojson::{GroupTp, SegmentTp, SpecsTp};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+// symbols.rs - Constants used to compare mapping specs (2021-07-01 bar8tl)
+const ADDED  : &str = "added";
+const REMOVED: &str = "removed";
+const CHANGED: &str = "changed";
+
+// types.rs - Data types of mapping specs comparison (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiffsTp {         // Comparison result
+  pub old  : String,         // Specs version compared (mapid.chgnr)
+  pub new  : String,         // Specs version compared against
+  pub diffs: Vec<DifrwTp>
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DifrwTp {         // Row added, removed or changed
+  pub dtype: String,         // added, removed, changed
+  pub level: String,         // header, group, segment, field
+  pub group: String,
+  #[serde(skip_serializing_if = "String::is_empty")]
+  pub segmt: String,
+  #[serde(skip_serializing_if = "String::is_empty")]
+  pub field: String,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub chngs: Vec<ChngsTp>
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChngsTp {         // Attribute changed
+  pub attrb: String,
+  pub old  : String,
+  pub new  : String
+}
+
+// Record of a specs identified by its group, segment and field path. Repeated
+// IDs in the same parent are told apart by their occurrence (#2, #3...)
+#[derive(Debug, Clone, Default)]
+struct SprecTp {
+  level: String,
+  group: String,
+  segmt: String,
+  field: String,
+  attrs: Vec<(&'static str, String)>
+}
+
+// diff_maps.rs - Compare two versions of a mapping specs and write the report
+// to stdout or to an output file (2021-07-01 bar8tl)
+// Command line: edimaps map diff <mapid.chg1> <mapid.chg2> [-f text|json|html]
+pub fn diff_maps(dbopt: &String, spec1: &String, spec2: &String, outfm: &String,
+  opath: &Option<String>) -> Result<()> {
+  let cnn = Connection::open(dbopt)?;
+  let (m1, c1) = split_specid(spec1);
+  let (m2, c2) = split_specid(spec2);
+  let sp1 = read_cr_repo(&cnn, &m1, &c1)?;
+  let sp2 = read_cr_repo(&cnn, &m2, &c2)?;
+  let df = DiffsTp { old: spec1.clone(), new: spec2.clone(),
+    diffs: diff_specs(&sp1, &sp2) };
+  let outpt = match outfm.as_str() {
+    "text" => diff_text(&df),
+    "json" => serde_json::to_string_pretty(&df)
+      .map_err(|e| json_error(e, opath.as_deref().unwrap_or_default()))?,
+    "html" => diff_html(&df),
+    _      => return Err(ErrorTp::Config { file: String::new(), detail: format!(
+      "Output format '{}' not supported, use text, json or html", outfm) })
+  };
+  match opath {
+    Some(p) => {
+      let mut file = File::create(p).map_err(|e| io_error(e, p))?;
+      file.write_all(outpt.as_bytes()).map_err(|e| io_error(e, p))?;
+      println!("Differences: {}", df.diffs.len());
+    },
+    None => println!("{}", outpt)
+  }
+  return Ok(());
+}
+
+// split_specid.rs - Split specs ID into mapid and change number (2021-07-01 bar8tl)
+pub fn split_specid(specid: &String) -> (String, String) {
+  match specid.split_once('.') {
+    Some((mapid, chgnr)) => return (mapid.to_string(), chgnr.to_string()),
+    None                 => return (specid.clone(), String::new())
+  }
+}
+
+// diff_specs.rs - Align records of both specs by identity and compare their
+// attributes. Change marks are not compared (2021-07-01 bar8tl)
+pub fn diff_specs(sp1: &SpecsTp, sp2: &SpecsTp) -> Vec<DifrwTp> {
+  let mut diffs: Vec<DifrwTp> = Vec::new();
+  let h1 = header_attrs(sp1);
+  let h2 = header_attrs(sp2);
+  let chngs = diff_attrs(&h1, &h2);
+  if chngs.len() > 0 {
+    diffs.push(DifrwTp { dtype: CHANGED.to_string(), level: "header".to_string(),
+      chngs: chngs, ..Default::default() });
+  }
+  let r1 = specs_records(sp1);
+  let r2 = specs_records(sp2);
+  let ix2: HashMap<String, usize> = r2.iter().enumerate()
+    .map(|(i, r)| (record_key(r), i)).collect();
+  let ix1: HashMap<String, usize> = r1.iter().enumerate()
+    .map(|(i, r)| (record_key(r), i)).collect();
+  let mut j = 0;
+  for r in &r1 {
+    match ix2.get(&record_key(r)) {
+      Some(&i) => {
+        // records only in new specs placed before this one are added
+        while j < i {
+          if !ix1.contains_key(&record_key(&r2[j])) {
+            diffs.push(diff_row(ADDED, &r2[j], Vec::new()));
+          }
+          j += 1;
+        }
+        j = j.max(i + 1);
+        let chngs = diff_attrs(&r.attrs, &r2[i].attrs);
+        if chngs.len() > 0 {
+          diffs.push(diff_row(CHANGED, r, chngs));
+        }
+      },
+      None => diffs.push(diff_row(REMOVED, r, Vec::new()))
+    }
+  }
+  for r in &r2[j.min(r2.len())..] {
+    if !ix1.contains_key(&record_key(r)) {
+      diffs.push(diff_row(ADDED, r, Vec::new()));
+    }
+  }
+  return diffs;
+}
+
+// specs_records.rs - List group, segment and field records of a specs in order,
+// with their identity path (2021-07-01 bar8tl)
+fn specs_records(sp: &SpecsTp) -> Vec<SprecTp> {
+  let mut recs: Vec<SprecTp> = Vec::new();
+  let mut ngrp: HashMap<String, usize> = HashMap::new();
+  for g in &sp.groups {
+    let group = occurrence(&mut ngrp, &g.group);
+    recs.push(SprecTp { level: "group".to_string(), group: group.clone(),
+      attrs: group_attrs(g), ..Default::default() });
+    let mut nsgm: HashMap<String, usize> = HashMap::new();
+    for s in &g.segments {
+      let sgmid = if s.segment.len() > 0 { &s.segment } else { &s.name };
+      let segmt = occurrence(&mut nsgm, sgmid);
+      recs.push(SprecTp { level: "segment".to_string(), group: group.clone(),
+        segmt: segmt.clone(), attrs: segmt_attrs(s), ..Default::default() });
+      let mut nfld: HashMap<String, usize> = HashMap::new();
+      for f in &s.fields {
+        recs.push(SprecTp { level: "field".to_string(), group: group.clone(),
+          segmt: segmt.clone(), field: occurrence(&mut nfld, &f.field),
+          attrs: vec![("source", f.source.clone()), ("rule/condition",
+          f.r_cond.clone()), ("comment", f.comment.clone()), ("sample",
+          f.sample.clone()), ("text", f.text.clone())] });
+      }
+    }
+  }
+  return recs;
+}
+
+fn header_attrs(sp: &SpecsTp) -> Vec<(&'static str, String)> {
+  let h = &sp.header;
+  return vec![("title", h.title.clone()), ("last update", h.last_update.clone()),
+    ("author", h.author.clone()), ("version", h.version.clone()),
+    ("customer", h.customer.clone()), ("target format", h.target_format.clone()),
+    ("source format", h.source_format.clone())];
+}
+
+fn group_attrs(g: &GroupTp) -> Vec<(&'static str, String)> {
+  return vec![("text", g.text.clone()), ("loop max", g.loop_max.clone()),
+    ("status", g.status.clone()), ("descr", g.descr.clone())];
+}
+
+fn segmt_attrs(s: &SegmentTp) -> Vec<(&'static str, String)> {
+  return vec![("name", s.name.clone()), ("loop max", s.loop_max.clone()),
+    ("status", s.status.clone()), ("descr", s.descr.clone())];
+}
+
+fn diff_attrs(a1: &Vec<(&'static str, String)>, a2: &Vec<(&'static str, String)>)
+  -> Vec<ChngsTp> {
+  return a1.iter().zip(a2.iter()).filter(|(x, y)| x.1 != y.1)
+    .map(|(x, y)| ChngsTp { attrb: x.0.to_string(), old: x.1.clone(),
+      new: y.1.clone() }).collect();
+}
+
+fn diff_row(dtype: &str, r: &SprecTp, chngs: Vec<ChngsTp>) -> DifrwTp {
+  return DifrwTp { dtype: dtype.to_string(), level: r.level.clone(),
+    group: r.group.clone(), segmt: r.segmt.clone(), field: r.field.clone(),
+    chngs: chngs };
+}
+
+fn record_key(r: &SprecTp) -> String {
+  return format!("{}|{}|{}|{}", r.level, r.group, r.segmt, r.field);
+}
+
+fn occurrence(count: &mut HashMap<String, usize>, id: &String) -> String {
+  let n = count.entry(id.clone()).or_insert(0);
+  *n += 1;
+  if *n == 1 {
+    return id.clone();
+  }
+  return format!("{}#{}", id, n);
+}
+
+fn diff_path(d: &DifrwTp) -> String {
+  return [d.group.as_str(), d.segmt.as_str(), d.field.as_str()].iter()
+    .filter(|s| s.len() > 0).cloned().collect::<Vec<&str>>().join(" / ");
+}
+
+// diff_text.rs - Format comparison as text: one line per record prefixed by
+// +, - or ~, followed by its changed attributes (2021-07-01 bar8tl)
+fn diff_text(df: &DiffsTp) -> String {
+  let mut s = format!("--- {}\n+++ {}\n", df.old, df.new);
+  for d in &df.diffs {
+    let mark = match d.dtype.as_str() { ADDED => "+", REMOVED => "-", _ => "~" };
+    s.push_str(&format!("{} {:8} {}\n", mark, d.level, diff_path(d)));
+    for c in &d.chngs {
+      s.push_str(&format!("    {:15} '{}' -> '{}'\n", c.attrb, c.old, c.new));
+    }
+  }
+  s.push_str(&format!("Differences: {}", df.diffs.len()));
+  return s;
+}
+
+// diff_html.rs - Format comparison as an html report (2021-07-01 bar8tl)
+fn diff_html(df: &DiffsTp) -> String {
+  let mut s = String::new();
+  s.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"UTF-8\">\n");
+  s.push_str(&format!("<title>{} vs {}</title>\n", esc(&df.old), esc(&df.new)));
+  s.push_str("<style>\nbody { font-family: sans-serif; font-size: 10pt; }\n");
+  s.push_str("table { border-collapse: collapse; }\n");
+  s.push_str("td, th { border: 1px solid #999; padding: 2px 6px; }\n");
+  s.push_str(".added { background: #dfd; }\n.removed { background: #fdd; }\n");
+  s.push_str(".changed { background: #ffd; }\n</style>\n</head>\n<body>\n");
+  s.push_str(&format!("<h2>Mapping specs {} vs {}</h2>\n", esc(&df.old),
+    esc(&df.new)));
+  s.push_str(&format!("<p>Differences: {}</p>\n", df.diffs.len()));
+  s.push_str("<table>\n<tr><th>Change</th><th>Level</th><th>Group</th>");
+  s.push_str("<th>Segment</th><th>Field</th><th>Attribute</th>");
+  s.push_str(&format!("<th>{}</th><th>{}</th></tr>\n", esc(&df.old),
+    esc(&df.new)));
+  for d in &df.diffs {
+    let n = d.chngs.len().max(1);
+    for i in 0..n {
+      s.push_str(&format!("<tr class=\"{}\">", d.dtype));
+      if i == 0 {
+        for v in [&d.dtype, &d.level, &d.group, &d.segmt, &d.field] {
+          s.push_str(&format!("<td rowspan=\"{}\">{}</td>", n, esc(v)));
+        }
+      }
+      match d.chngs.get(i) {
+        Some(c) => s.push_str(&format!("<td>{}</td><td>{}</td><td>{}</td>",
+          esc(&c.attrb), esc(&c.old), esc(&c.new))),
+        None    => s.push_str("<td></td><td></td><td></td>")
+      }
+      s.push_str("</tr>\n");
+    }
+  }
+  s.push_str("</table>\n</body>\n</html>\n");
+  return s;
+}
+
+fn esc(v: &str) -> String {
+  return v.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+    .replace('"', "&quot;");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::maps::tojson::{FieldTp, HeaderTp};
+
+  fn fld(field: &str, source: &str) -> FieldTp {
+    return FieldTp { field: field.to_string(), source: source.to_string(),
+      ..Default::default() };
+  }
+
+  fn sgm(segment: &str, fields: Vec<FieldTp>) -> SegmentTp {
+    return SegmentTp { segment: segment.to_string(), fields: fields,
+      ..Default::default() };
+  }
+
+  fn grp(group: &str, segments: Vec<SegmentTp>) -> GroupTp {
+    return GroupTp { group: group.to_string(), segments: segments,
+      ..Default::default() };
+  }
+
+  // sample_specs.rs - Two versions of a specs: the new one adds a segment before
+  // a matching one, removes a field, moves a segment up, changes sources and adds
+  // a third occurrence of a repeated segment
+  fn sample_specs() -> (SpecsTp, SpecsTp) {
+    let ctrl = || grp("CTRL", vec![sgm("EDI_DC40", vec![fld("SNDPRN", "ISA06")])]);
+    let head = |version: &str| HeaderTp { title: "RBNA Common Mapping".to_string(),
+      version: version.to_string(), ..Default::default() };
+    let old = SpecsTp { header: head("1.0"), groups: vec![ctrl(), grp("MAIN", vec![
+      sgm("E1EDK01", vec![fld("CURCY", "CUR02"), fld("BELNR", "BEG03")]),
+      sgm("E1EDP01", vec![fld("POSEX", "PO101")]),
+      sgm("E1EDP01", vec![fld("POSEX", "PO101")]),
+      sgm("E1EDKA1", vec![fld("PARVW", "N101")])])] };
+    let new = SpecsTp { header: head("1.1"), groups: vec![ctrl(), grp("MAIN", vec![
+      sgm("E1EDK02", vec![fld("QUALF", "REF01")]),
+      sgm("E1EDK01", vec![fld("BELNR", "BEG03")]),
+      sgm("E1EDKA1", vec![fld("PARVW", "N102")]),
+      sgm("E1EDP01", vec![fld("POSEX", "PO101")]),
+      sgm("E1EDP01", vec![fld("POSEX", "PO102")]),
+      sgm("E1EDP01", vec![fld("POSEX", "PO101")])])] };
+    return (old, new);
+  }
+
+  #[test]
+  fn diff_specs_aligns_records_by_identity() {
+    let (old, new) = sample_specs();
+    let diffs = diff_specs(&old, &new);
+    let rows: Vec<(&str, &str, String, Vec<(&str, &str, &str)>)> = diffs.iter()
+      .map(|d| (d.dtype.as_str(), d.level.as_str(), diff_path(d), d.chngs.iter()
+      .map(|c| (c.attrb.as_str(), c.old.as_str(), c.new.as_str())).collect()))
+      .collect();
+    assert_eq!(rows, vec![
+      ("changed", "header",  "".to_string(), vec![("version", "1.0", "1.1")]),
+      ("added",   "segment", "MAIN / E1EDK02".to_string(), vec![]),
+      ("added",   "field",   "MAIN / E1EDK02 / QUALF".to_string(), vec![]),
+      ("removed", "field",   "MAIN / E1EDK01 / CURCY".to_string(), vec![]),
+      ("changed", "field",   "MAIN / E1EDP01#2 / POSEX".to_string(),
+        vec![("source", "PO101", "PO102")]),
+      ("changed", "field",   "MAIN / E1EDKA1 / PARVW".to_string(),
+        vec![("source", "N101", "N102")]),
+      ("added",   "segment", "MAIN / E1EDP01#3".to_string(), vec![]),
+      ("added",   "field",   "MAIN / E1EDP01#3 / POSEX".to_string(), vec![])]);
+    assert!(diff_specs(&old, &old).is_empty());
+  }
+
+  #[test]
+  fn diff_text_lists_records_and_changed_attributes() {
+    let (old, new) = sample_specs();
+    let df = DiffsTp { old: "tst810.1".to_string(), new: "tst810.2".to_string(),
+      diffs: diff_specs(&old, &new) };
+    assert_eq!(diff_text(&df), [
+      "--- tst810.1",
+      "+++ tst810.2",
+      "~ header   ",
+      "    version         '1.0' -> '1.1'",
+      "+ segment  MAIN / E1EDK02",
+      "+ field    MAIN / E1EDK02 / QUALF",
+      "- field    MAIN / E1EDK01 / CURCY",
+      "~ field    MAIN / E1EDP01#2 / POSEX",
+      "    source          'PO101' -> 'PO102'",
+      "~ field    MAIN / E1EDKA1 / PARVW",
+      "    source          'N101' -> 'N102'",
+      "+ segment  MAIN / E1EDP01#3",
+      "+ field    MAIN / E1EDP01#3 / POSEX",
+      "Differences: 8"].join("\n"));
+  }
+}
@@ -0,0 +1,12 @@
+// fromjson.rs - Function modules being used to get EDI mapping specification
+// from its json backup file (2021-07-01 bar8tl)
+use crate::error::{io_error, json_error, Result};
+use crate::maps::tojson::SpecsTp;
+use std::fs::read_to_string;
+
+// read_cr_json.rs - Read json backup of a mapping specs (2021-07-01 bar8tl)
+pub fn read_cr_json(ipath: &String) -> Result<SpecsTp> {
+  let fdata = read_to_string(ipath).map_err(|e| io_error(e, ipath))?;
+  let sp: SpecsTp = serde_json::from_str(&fdata).map_err(|e| json_error(e, ipath))?;
+  return Ok(sp);
+}
@@ -0,0 +1,58 @@
+// fromrepo.rs - Function modules being used to get EDI mapping specification
+// records from the repository back into the json file structure (2021-07-01 bar8tl)
+use crate::error::{repo_error, sheet_error, ErrorTp, Result};
+use crate::maps::tojson::{FieldTp, GroupTp, HeaderTp, SegmentTp, SpecsTp};
+use rusqlite::Connection;
+
+// read_cr_repo.rs - Get header, groups, segments and fields of a mapping specs
+// from the repository. Records are taken in the order of their rows in the specs
+// file, so each segment goes to the last group and each field to the last segment
+// (2021-07-01 bar8tl)
+pub fn read_cr_repo(cnn: &Connection, mapid: &String, chgnr: &String) ->
+  Result<SpecsTp> {
+  let mut sp = SpecsTp { ..Default::default() };
+  sp.header = cnn.query_row("SELECT mptit, lupdt, authr, bvers, cstmr, tgtfm,
+    srcfm, rowno FROM headers WHERE mapid=?1 and chgnr=?2;", [mapid, chgnr], |row|
+    Ok(HeaderTp { title: row.get(0)?, last_update: row.get(1)?, author: row.get(2)?,
+      version: row.get(3)?, customer: row.get(4)?, target_format: row.get(5)?,
+      source_format: row.get(6)?, rowno: row.get(7)? }))
+    .map_err(|e| match e {
+      rusqlite::Error::QueryReturnedNoRows => ErrorTp::Repository {
+        table: "headers".to_string(), detail: format!(
+        "Mapping specs {}.{} not found", mapid, chgnr) },
+      _ => repo_error(e, "headers")
+    })?;
+  let mut stmt = cnn.prepare("
+    SELECT rowno, 1, grpid, gtext, lpmax, stats, dtext, chang, '' FROM groups
+      WHERE mapid=?1 and chgnr=?2 UNION ALL
+    SELECT rowno, 2, sgmid, sgmtp, lpmax, stats, dtext, chang, '' FROM segments
+      WHERE mapid=?1 and chgnr=?2 UNION ALL
+    SELECT rowno, 3, targt, sourc, rcond, commt, dtext, chang, sampl FROM fields
+      WHERE mapid=?1 and chgnr=?2
+    ORDER BY 1, 2;").map_err(|e| repo_error(e, "mapspecs"))?;
+  let mut rows = stmt.query([mapid, chgnr]).map_err(|e| repo_error(e, "mapspecs"))?;
+  while let Some(row) = rows.next().map_err(|e| repo_error(e, "mapspecs"))? {
+    let rowno: String = row.get(0)?;
+    let rtype: i64 = row.get(1)?;
+    let c: Vec<String> = (2..9).map(|i| row.get(i)).collect::<rusqlite::Result<_>>()?;
+    if rtype == 1 {
+      sp.groups.push(GroupTp { group: c[0].clone(), text: c[1].clone(),
+        loop_max: c[2].clone(), status: c[3].clone(), descr: c[4].clone(),
+        change: c[5].clone(), rowno: rowno, segments: Vec::new() });
+    } else if rtype == 2 {
+      let g = sp.groups.last_mut().ok_or(sheet_error(format!(
+        "Segment {} found before any group", c[0]), "", ""))?;
+      g.segments.push(SegmentTp { segment: c[0].clone(), name: c[1].clone(),
+        loop_max: c[2].clone(), status: c[3].clone(), descr: c[4].clone(),
+        change: c[5].clone(), rowno: rowno, fields: Vec::new() });
+    } else {
+      let s = sp.groups.last_mut().and_then(|g| g.segments.last_mut())
+        .ok_or(sheet_error(format!("Field {} found before any segment", c[0]), "",
+        ""))?;
+      s.fields.push(FieldTp { field: c[0].clone(), source: c[1].clone(),
+        r_cond: c[2].clone(), comment: c[3].clone(), sample: c[6].clone(),
+        text: c[4].clone(), change: c[5].clone(), rowno: rowno });
+    }
+  }
+  return Ok(sp);
+}
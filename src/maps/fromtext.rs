@@ -0,0 +1,108 @@
+// fromtext.rs - Function modules being used to get EDI mapping specification from
+// its flat text backup file (BEGIN_MAPPING_SPECS format) (2021-07-01 bar8tl)
+use crate::config::MapsTp;
+use crate::error::{io_error, ErrorTp, Result};
+use crate::maps::tojson::{FieldTp, GroupTp, SegmentTp, SpecsTp};
+use std::fs::read_to_string;
+
+// symbols.rs - Constants used to read text backup (2021-07-01 bar8tl)
+const VALCL: usize = 24;     // Column where values start after keyword
+
+// read_cr_text.rs - Read text backup of a mapping specs. Each line holds a keyword
+// and, from column 24, its value; values written as 'no data' character are taken
+// as empty. BEGIN_GROUP, BEGIN_SEGMENT and FIELD open a new record; following
+// keywords fill it in (2021-07-01 bar8tl)
+pub fn read_cr_text(ipath: &String, map: &MapsTp) -> Result<SpecsTp> {
+  let tdata = read_to_string(ipath).map_err(|e| io_error(e, ipath))?;
+  let mut sp = SpecsTp { ..Default::default() };
+  let mut level = "";
+  for (j, line) in tdata.lines().enumerate() {
+    let keywd = line.trim_start().split(' ').next().unwrap_or_default();
+    let mut v = String::new();
+    if let (Some(k), Some(val)) = (line.get(..VALCL), line.get(VALCL..)) {
+      if k.trim() == keywd && val != map.ndchr {
+        v = val.to_string();
+      }
+    }
+    let err = |detail: &str| ErrorTp::Backup { file: ipath.clone(), line: j+1,
+      detail: format!("{} {}", keywd, detail) };
+    match keywd {
+      ""|"BEGIN_MAPPING_SPECS"|"END_MAPPING_SPECS"|"BEGIN_HEADER_RECORD"|
+      "END_HEADER_RECORD"|"END_FIELDS"|"END_SEGMENT"|"END_GROUP" => {},
+      "TITLE"         => sp.header.title         = v,
+      "LAST_UPDATE"   => sp.header.last_update   = v,
+      "AUTHOR"        => sp.header.author        = v,
+      "VERSION"       => sp.header.version       = v,
+      "CUSTOMER"      => sp.header.customer      = v,
+      "TARGET_FORMAT" => sp.header.target_format = v,
+      "SOURCE_FORMAT" => sp.header.source_format = v,
+      "ROWNO" if level == "" => sp.header.rowno  = v,
+      "BEGIN_GROUP"   => {
+        sp.groups.push(GroupTp { group: v, ..Default::default() });
+        level = "GROUP";
+      },
+      "BEGIN_SEGMENT" => {
+        let g = sp.groups.last_mut().ok_or(err("found before any group"))?;
+        g.segments.push(SegmentTp { segment: v, ..Default::default() });
+        level = "SEGMENT";
+      },
+      "BEGIN_FIELDS"  => {
+        // segment with empty ID was omitted in backup written with omite=yes
+        let g = sp.groups.last_mut().ok_or(err("found before any group"))?;
+        if level != "SEGMENT" {
+          g.segments.push(SegmentTp { ..Default::default() });
+        }
+        level = "FIELD";
+      },
+      "FIELD"         => {
+        let s = sp.groups.last_mut().and_then(|g| g.segments.last_mut())
+          .ok_or(err("found before any segment"))?;
+        s.fields.push(FieldTp { field: v, ..Default::default() });
+        level = "FIELD";
+      },
+      _ => set_value(&mut sp, level, keywd, v).ok_or(err("not expected here"))?
+    }
+  }
+  return Ok(sp);
+}
+
+// set_value.rs - Set value of a keyword into the record being read: group, segment
+// or field (2021-07-01 bar8tl)
+fn set_value(sp: &mut SpecsTp, level: &str, keywd: &str, v: String) -> Option<()> {
+  let g = sp.groups.last_mut()?;
+  match (level, keywd) {
+    ("GROUP",   "TEXT"     ) => g.text     = v,
+    ("GROUP",   "LOOP_MAX" ) => g.loop_max = v,
+    ("GROUP",   "STATUS"   ) => g.status   = v,
+    ("GROUP",   "DESCR"    ) => g.descr    = v,
+    ("GROUP",   "CHANGE"   ) => g.change   = v,
+    ("GROUP",   "ROWNO"    ) => g.rowno    = v,
+    ("SEGMENT", _          ) => {
+      let s = g.segments.last_mut()?;
+      match keywd {
+        "NAME"     => s.name     = v,
+        "LOOP_MAX" => s.loop_max = v,
+        "STATUS"   => s.status   = v,
+        "DESCR"    => s.descr    = v,
+        "CHANGE"   => s.change   = v,
+        "ROWNO"    => s.rowno    = v,
+        _          => return None
+      }
+    },
+    ("FIELD",   _          ) => {
+      let f = g.segments.last_mut()?.fields.last_mut()?;
+      match keywd {
+        "SOURCE"    => f.source  = v,
+        "RULE_COND" => f.r_cond  = v,
+        "COMMENT"   => f.comment = v,
+        "SAMPLE"    => f.sample  = v,
+        "TEXT"      => f.text    = v,
+        "CHANGE"    => f.change  = v,
+        "ROWNO"     => f.rowno   = v,
+        _           => return None
+      }
+    },
+    _ => return None
+  }
+  return Some(());
+}
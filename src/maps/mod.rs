@@ -1,8 +1,19 @@
 // mod.rs - Function modules used to process mapping specification file. For example
 // to add mapping specifications to the repository or to generate json files as
 // backup in text format (2021-07-01 bar8tl)
+pub mod check_maps;
+pub mod coverage;
+pub mod diff_maps;
+mod fromjson;
+pub mod fromrepo;
+mod fromtext;
 pub mod out_maps;
 pub mod proc_maps;
-mod tojson;
+#[cfg(test)]
+pub mod samples;
+pub mod search_maps;
+pub mod templates;
+pub mod tojson;
 mod totext;
 mod torepo;
+mod toxlsx;
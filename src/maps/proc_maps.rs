@@ -8,14 +8,31 @@ use crate::maps::totext::{              isrt_crhdr_text, isrt_cregrp_text,
   isrt_crgrps_text, isrt_crsgms_text, isrt_crflds_text, write_cr_text};
 use crate::maps::torepo::{init_cr_repo, isrt_crhdr_repo, isrt_cregrp_repo,
   isrt_crgrps_repo, isrt_crsgms_repo, isrt_crflds_repo};
-use calamine::{Reader, Xlsx, open_workbook, RangeDeserializerBuilder, Error};
+use crate::maps::fromjson::read_cr_json;
+use crate::maps::fromrepo::read_cr_repo;
+use crate::maps::fromtext::read_cr_text;
+use crate::maps::search_maps::FLDSRCH;
+use crate::maps::toxlsx::{specs_rows, write_cr_xlsx};
+use crate::maps::templates::{get_template, get_roles, is_match, list_templates,
+  match_text, TemplTp};
+use crate::error::{sheet_error, ErrorTp, Result};
+use crate::reposit::add_table;
+use calamine::{Reader, Xlsx, open_workbook, RangeDeserializerBuilder};
 use chrono::{NaiveDate, Datelike, Duration};
 use rusqlite::Connection;
 
+// symbols.rs - Constants used in processing mapping specification
+// (2021-07-01 bar8tl)
+pub const OUTCM  : &str = "outcm";   // RBNA common template ID
+pub const INBCM  : &str = "inbcm";   // RBNA inbound common template ID
+pub const CRL    : &str = "crl";     // Customer release specs message type
+pub const ASN    : &str = "asn";     // ASN specs message type (856,DESADV)
+
 // types.rs - Data types required for processing mapping specification
 // (2021-07-01 bar8tl)
-pub type CrlinTp = (String, String, String, String, String, String, String);
+pub type CrlinTp = Vec<String>;
 pub type CrrowTp = [String; 7];
+pub type SprowTp = (String, CrrowTp); // Row number in specs file and its line
 
 #[derive(Debug, Clone, Default)]
 pub struct CrhdrTp {
@@ -41,6 +58,7 @@ pub struct CrTp {
   pub trims: String,
   pub lfchr: String,
   pub templ: String,
+  pub tpdef: TemplTp,
   pub sqhdr: i16,
   pub sqgrp: i16,
   pub sqsgm: i16,
@@ -55,44 +73,93 @@ pub struct CrTp {
 
 // proc_maps.rs - Starts processes for EDI messages mapping specifications
 // (2021-07-01 bar8tl)
-// Command line: edimaps map -r -j -t <mapping-specs-id>
+// Command line: edimaps map -r -j -t -x <mapping-specs-id>
 pub fn proc_maps(dbopt: &String, rfr: &RefersTp, map: &MapsTp, repo: bool,
-  json: bool, text: bool) {
-  let d = get_mapdetail(rfr, map);
-  let mtyp = ["crl", "inv", "asn"];
+  json: bool, text: bool, xlsx: bool) -> Result<()> {
+  let d = get_mapdetail(rfr, map)?;
+  let mtyp = [CRL, "inv", ASN];
   let fncs = [proc_mapcrl, proc_mapinv, proc_mapasn];
-  fncs[mtyp.iter().position(|&x| x == d.msgtp).unwrap()] (dbopt, map, &d, repo, json, text);
+  let i = mtyp.iter().position(|&x| x == d.msgtp).ok_or(sheet_error(
+    format!("Message type '{}' not supported", d.msgtp), &rfr.idxpt, &rfr.tabid))?;
+  return fncs[i] (dbopt, map, &d, repo, json, text, xlsx);
+}
+
+// proc_mapcrl.rs - Process CR (Customer Release) mapping specs. Worksheet, column
+// roles and line detection are taken from the specs template definition
+// (2021-07-01 bar8tl)
+fn proc_mapcrl(dbopt: &String, map: &MapsTp, d: &IdxdatTp, repo: bool, json: bool,
+  text: bool, xlsx: bool) -> Result<()> {
+  let tpdef = spec_template(&map.tpldr, d)?;
+  let sheet = tpdef.sheet.clone();
+  let mpath = format!("{}{}\\{}", map.mapdr, d.ctmrl, d.fname);
+  let mut workbook: Xlsx<_> = open_workbook(&mpath)
+    .map_err(|e| sheet_error(e, &mpath, &sheet))?;
+  let range = workbook.worksheet_range(&sheet)
+    .ok_or(sheet_error("Cannot find specified tab", &mpath, &sheet))?
+    .map_err(|e| sheet_error(e, &mpath, &sheet))?;
+  let iter = RangeDeserializerBuilder::new().has_headers(false).from_range(&range)
+    .map_err(|e| sheet_error(e, &mpath, &sheet))?;
+  let mut rows: Vec<SprowTp> = Vec::new();
+  for (j, i) in iter.enumerate() {
+    let  l: CrlinTp = i.map_err(|e| sheet_error(format!("row {}: {}", j+1, e),
+      &mpath, &sheet))?;
+    rows.push((format!("{:04}", j), fmt_columns(get_roles(&tpdef, &l), &map.trims,
+      &map.lfchr)));
+  }
+  return proc_specrows(dbopt, map, d, tpdef, &rows, &mpath, repo, json, text, xlsx);
 }
 
-// proc_mapcrl.rs - Process CR (Customer Release) mapping specs (2021-07-01 bar8tl)
-fn proc_mapcrl(dbopt: &String, map: &MapsTp, d: &IdxdatTp, repo: bool, json: bool, text: bool) {
+// spec_template.rs - Get definition of the template of a mapping specs. CR specs
+// were always read in the common layout: those with no template, or with one not
+// defined, keep it. Lines of hierarchical levels are looked for in ASN specs only
+// (2021-07-01 bar8tl)
+pub fn spec_template(tpldr: &String, d: &IdxdatTp) -> Result<TemplTp> {
+  let templ = if d.msgtp == CRL && !list_templates(tpldr).contains(&d.templ) {
+    OUTCM.to_string()
+  } else {
+    d.templ.clone()
+  };
+  let mut tpdef = get_template(tpldr, &templ, &d.mapid)?;
+  if d.msgtp != ASN {
+    tpdef.detect.level.clear();
+  }
+  return Ok(tpdef);
+}
+
+// proc_specrows.rs - Process lines of mapping specs, given in role order with
+// their row number in the specs file, adding them to the repository and to the
+// json, text and MS Excel outputs. Lines without row number, as the ones laid out
+// from backups written before row numbers were kept, take their position
+// (2021-07-01 bar8tl)
+fn proc_specrows(dbopt: &String, map: &MapsTp, d: &IdxdatTp, tpdef: TemplTp,
+  rows: &Vec<SprowTp>, spath: &String, repo: bool, json: bool, text: bool,
+  xlsx: bool) -> Result<()> {
   let mut cr = CrTp    { ..Default::default() };
   let mut sp = SpecsTp { ..Default::default() };
   let mut ln = String::new();
-  let cnn = Connection::open(dbopt).unwrap();
+  let cnn = Connection::open(dbopt)?;
+  if repo {                    // Search index, for repositories created before it
+    add_table(&cnn, FLDSRCH)?;
+  }
   init_crdata(&d.mapid, &d.chgnr, map.trims.clone(), map.lfchr.clone(), &d.templ,
-    &cnn, &mut cr, repo, json, &mut sp);
-  let mut workbook: Xlsx<_> = open_workbook(format!("{}{}\\{}", map.mapdr, d.ctmrl,
-    d.fname)).expect("Input not found");
-  let range = workbook.worksheet_range("Mapping")
-    .ok_or(Error::Msg("Cannot find specified tab")).unwrap().unwrap();
-  let iter = RangeDeserializerBuilder::new().has_headers(false).from_range(&range)
-    .unwrap();
-  for (j, i) in iter.enumerate() {
-    let  l: CrlinTp = i.expect("Row not mapped");
-    let cl: CrrowTp = fmt_columns([l.0,l.1,l.2,l.3,l.4,l.5,l.6], &cr.trims,
-      &cr.lfchr);
-    cr.rowno = format!("{:04}", j);
-    proc_linebyline(&cnn, &mut cr, &cl, repo, json, text, &mut sp, &mut ln, map);
+    &cnn, &mut cr, repo, json || xlsx, &mut sp)?;
+  cr.tpdef = tpdef;
+  for (j, (rowno, cl)) in rows.iter().enumerate() {
+    cr.rowno = if rowno.len() > 0 { rowno.clone() } else { format!("{:04}", j) };
+    proc_linebyline(&cnn, &mut cr, cl, repo, json || xlsx, text, &mut sp, &mut ln,
+      map)
+      .map_err(|e| e.at(spath, j+1))?;
   }
-  if json { write_cr_json(&map.bkpdr, &d, &sp);    }
-  if text { write_cr_text(&map.bkpdr, &d, &mut ln);}
+  if json { write_cr_json(&map.bkpdr, &d, &sp)?;    }
+  if text { write_cr_text(&map.bkpdr, &d, &mut ln)?;}
+  if xlsx { write_cr_xlsx(map, &d, &sp)?;           }
   println!("Records |{:4}|{:4}|{:4}|{:4}|", cr.sqhdr, cr.sqgrp, cr.sqsgm, cr.sqfld);
+  return Ok(());
 }
 
 fn init_crdata(mapid: &String, chgnr: &String, trims: String, lfchr: String,
   templ: &String, cnn: &Connection, cr: &mut CrTp, repo: bool, json: bool,
-  sp: &mut SpecsTp) {
+  sp: &mut SpecsTp) -> Result<()> {
   cr.mapid = mapid.clone();
   cr.chgnr = chgnr.clone();
   cr.trims = trims.clone();
@@ -100,100 +167,105 @@ fn init_crdata(mapid: &String, chgnr: &String, trims: String, lfchr: String,
   cr.templ = templ.clone();
   cr.endcl = false;
   cr.strdt = NaiveDate::from_ymd_opt(1900, 1, 1).expect("Error in start date");
-  if repo { init_cr_repo(cnn, cr); }
-  if json { init_cr_json(sp);      }
+  if repo { init_cr_repo(cnn, cr)?; }
+  if json { init_cr_json(sp);       }
+  return Ok(());
 }
 
-// proc_linebyline.rs - Process CR mapping specs in MS Excel file line by line
+// proc_linebyline.rs - Process CR mapping specs in MS Excel file line by line.
+// Columns come in role order: descr, chang, field, sourc, rcond, cmmnt, sampl
 // (2021-07-01 bar8tl)
 fn proc_linebyline(cnn: &Connection, cr: &mut CrTp, cl: &[String; 7], repo: bool,
-  json: bool, text: bool, sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) {
+  json: bool, text: bool, sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) ->
+  Result<()> {
   // Header lines
   if cr.inhdr.len() == 0 { // if no hdr still then take line 1 and flag group HDR
-    if cl[1].to_lowercase().contains("common mapping") {
-      cr.hdr.mptit = cl[1].clone();
+    if let Some(mptit) = match_text(&cr.tpdef.header.title, cl) {
+      cr.hdr.mptit = mptit;
       cr.inhdr = cr.mapid.clone();
       cr.ingrp = "HDR".to_string();
       cr.frgrp = false;
-      return();
+      return Ok(());
     }
   }
   if cr.ingrp == "HDR" {
     if !cr.endcl {
-      isrt_crhdr(cnn, cl, cr, repo, json, text, sp, ln, map);
+      isrt_crhdr(cnn, cl, cr, repo, json, text, sp, ln, map)?;
 
   // Control record lines
-    } else if cl[2].to_lowercase().contains("control record"   ) ||
-              cl[2].to_lowercase().contains("edi segment/field") {
+    } else if is_match(&cr.tpdef.detect.cntrl, cl) {
       cr.ingrp = "CTRL".to_string();
-      isrt_cregrp(cnn, cr, repo, json, text, sp, ln, map);
-      isrt_crsgms(cnn, cl, cr, repo, json, text, sp, ln, map);
+      isrt_cregrp(cnn, cr, repo, json, text, sp, ln, map)?;
+      isrt_crsgms(cnn, cl, cr, repo, json, text, sp, ln, map)?;
 
   // First section or first segment lines after Header (mapsp without Control record)
     } else {
-      if (cl[2].to_lowercase().contains("section") &&
-         !cl[2].to_lowercase().starts_with("segment")) ||
-          cl[2].to_lowercase().starts_with("group"  )  {
+      if is_group(cr, cl) {
         cr.frgrp = true;
-        isrt_crgrps(cnn, cl, cr, repo, json, text, sp, ln, map);
+        isrt_crgrps(cnn, cl, cr, repo, json, text, sp, ln, map)?;
       } else
-      if cl[2].to_lowercase().starts_with("segment") {
+      if is_match(&cr.tpdef.detect.frsgm, cl) {
         if !cr.frgrp {
           cr.ingrp = "MAIN".to_string();
-          isrt_cregrp(cnn, cr, repo, json, text, sp, ln, map);
+          isrt_cregrp(cnn, cr, repo, json, text, sp, ln, map)?;
         }
-        isrt_crsgms(cnn, cl, cr, repo, json, text, sp, ln, map);
+        isrt_crsgms(cnn, cl, cr, repo, json, text, sp, ln, map)?;
       } else {
-        isrt_crflds(cnn, cl, cr, repo, json, text, sp, ln, map);
+        isrt_crflds(cnn, cl, cr, repo, json, text, sp, ln, map)?;
         cr.frgrp = false;
       }
     }
 
   // First section or first segment lines after Control record
   } else if cr.ingrp == "CTRL" {
-    if (cl[2].to_lowercase().contains("section") &&
-       !cl[2].to_lowercase().starts_with("segment")) ||
-        cl[2].to_lowercase().starts_with("group"  )  {
+    if is_group(cr, cl) {
       cr.frgrp = true;
-      isrt_crgrps(cnn, cl, cr, repo, json, text, sp, ln, map);
+      isrt_crgrps(cnn, cl, cr, repo, json, text, sp, ln, map)?;
     } else
-    if cl[2].to_lowercase().starts_with("segment") {
+    if is_match(&cr.tpdef.detect.frsgm, cl) {
       if !cr.frgrp {
         cr.ingrp = "MAIN".to_string();
-        isrt_cregrp(cnn, cr, repo, json, text, sp, ln, map);
+        isrt_cregrp(cnn, cr, repo, json, text, sp, ln, map)?;
       }
-      isrt_crsgms(cnn, cl, cr, repo, json, text, sp, ln, map);
+      isrt_crsgms(cnn, cl, cr, repo, json, text, sp, ln, map)?;
     } else {
-      isrt_crflds(cnn, cl, cr, repo, json, text, sp, ln, map);
+      isrt_crflds(cnn, cl, cr, repo, json, text, sp, ln, map)?;
       cr.frgrp = false;
     }
 
   // Subsequent section and segment lines
   } else {
-    if (cl[2].to_lowercase().contains("section") &&
-       !cl[2].to_lowercase().starts_with("segment")) ||
-        cl[2].to_lowercase().starts_with("group"  )  {
-      isrt_crgrps(cnn, cl, cr, repo, json, text, sp, ln, map);
+    if is_group(cr, cl) {
+      isrt_crgrps(cnn, cl, cr, repo, json, text, sp, ln, map)?;
     } else
-    if cl[2].to_lowercase().starts_with("segment:") {
-      isrt_crsgms(cnn, cl, cr, repo, json, text, sp, ln, map);
+    if is_match(&cr.tpdef.detect.segmt, cl) {
+      isrt_crsgms(cnn, cl, cr, repo, json, text, sp, ln, map)?;
     } else
-    if !cl[5].to_lowercase().contains("end of mapping") {
-      isrt_crflds(cnn, cl, cr, repo, json, text, sp, ln, map);
+    if !is_match(&cr.tpdef.detect.endsp, cl) {
+      isrt_crflds(cnn, cl, cr, repo, json, text, sp, ln, map)?;
     }
   }
+  return Ok(());
+}
+
+// is_group.rs - Check whether a line opens a group: section, group or
+// hierarchical level (2021-07-01 bar8tl)
+fn is_group(cr: &CrTp, cl: &[String; 7]) -> bool {
+  return is_match(&cr.tpdef.detect.group, cl) ||
+         is_match(&cr.tpdef.detect.level, cl);
 }
 
 // isrt_crhdr.rs - Insert header records (2021-07-01 bar8tl)
 fn isrt_crhdr(cnn: &Connection, cl: &[String; 7], cr: &mut CrTp, repo: bool,
-  json: bool, text: bool, sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) {
-  if cl[3].contains("Author") {
+  json: bool, text: bool, sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) ->
+  Result<()> {
+  if is_match(&cr.tpdef.header.authr, cl) {
     cr.hdr.lstup = cl[2].clone();
     cr.hdr.authr = cl[4].clone();
-  } else if cl[3].contains("Customer") {
+  } else if is_match(&cr.tpdef.header.custm, cl) {
     cr.hdr.bvers = cl[2].clone();
     cr.hdr.custm = cl[4].clone();
-  } else if cl[0].contains("Field") {
+  } else if is_match(&cr.tpdef.header.frmts, cl) {
     cr.nextr = true;
   } else if cl[2].len() > 0 && cr.nextr {
     cr.hdr.tform = cl[2].clone();
@@ -204,10 +276,13 @@ fn isrt_crhdr(cnn: &Connection, cl: &[String; 7], cr: &mut CrTp, repo: bool,
     cr.ixsgm  = 0;
     cr.ixfld  = 0;
     let seqno = format!("{:04}", cr.sqhdr);
-    let lupdt = cr.hdr.lstup.parse::<i64>().unwrap();
-    let hdrdt = cr.strdt.checked_add_signed(Duration::days(lupdt-2)).unwrap();
+    let lupdt = cr.hdr.lstup.parse::<i64>().map_err(|_e| sheet_error(
+      format!("Last update '{}' is not a date", cr.hdr.lstup), "", &cr.tpdef.sheet))?;
+    let hdrdt = cr.strdt.checked_add_signed(Duration::days(lupdt-2))
+      .ok_or(sheet_error(format!("Last update '{}' out of range", lupdt), "",
+      &cr.tpdef.sheet))?;
     let lstup = format!("{}-{:02}-{:02}", hdrdt.year(), hdrdt.month(), hdrdt.day());
-    if repo { isrt_crhdr_repo(cnn, cr, &lstup, &seqno); }
+    if repo { isrt_crhdr_repo(cnn, cr, &lstup, &seqno)?; }
     if json { isrt_crhdr_json(cr, &lstup, sp);          }
     if text { isrt_crhdr_text(cr, &lstup, ln, map)      }
     //if cr.chgnr.len() > 0 {
@@ -215,41 +290,64 @@ fn isrt_crhdr(cnn: &Connection, cl: &[String; 7], cr: &mut CrTp, repo: bool,
     //}
     cr.endcl = true;
   }
+  return Ok(());
 }
 
 // isrt_cregrp.rs - Insert first group record (2021-07-01 bar8tl)
 fn isrt_cregrp(cnn: &Connection, cr: &mut CrTp, repo: bool, json: bool, text: bool,
-  sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) {
+  sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) -> Result<()> {
   cr.sqgrp += 1;
   cr.ixgrp += 1;
   cr.ixsgm  = 0;
   cr.ixfld  = 0;
   let seqno = format!("{:04}", cr.sqgrp);
-  if repo { isrt_cregrp_repo(cnn, cr, &seqno); }
-  if json { isrt_cregrp_json(cr, sp);          }
-  if text { isrt_cregrp_text(cr, ln, map);     }
+  if repo { isrt_cregrp_repo(cnn, cr, &seqno)?; }
+  if json { isrt_cregrp_json(cr, sp);           }
+  if text { isrt_cregrp_text(cr, ln, map);      }
+  return Ok(());
 }
 
 // isrt_crgrps.rs - Insert subsequent group records (2021-07-01 bar8tl)
 fn isrt_crgrps(cnn: &Connection, cl: &[String; 7], cr: &mut CrTp, repo: bool,
-  json: bool, text: bool, sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) {
+  json: bool, text: bool, sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) ->
+  Result<()> {
   cr.sqgrp += 1;
   cr.ixgrp += 1;
   cr.ixsgm  = 0;
   cr.ixfld  = 0;
   let seqno = format!("{:04}", cr.sqgrp);
   cr.ingrp = "MAIN".to_string();
-  if let Some(idx) = cl[2].find(": ") {
+  if is_match(&cr.tpdef.detect.level, cl) {
+    cr.ingrp = level_id(&cl[2]).ok_or(sheet_error(format!(
+      "Hierarchical level '{}' without level code", cl[2]), "", &cr.tpdef.sheet))?;
+  } else if let Some(idx) = cl[2].find(": ") {
     cr.ingrp = cl[2][idx+2..cl[2].len()].to_string();
   }
-  if repo { isrt_crgrps_repo(cnn, cl, cr, &seqno); }
-  if json { isrt_crgrps_json(cl, cr, sp);          }
-  if text { isrt_crgrps_text(cl, cr, ln, map)      }
+  if repo { isrt_crgrps_repo(cnn, cl, cr, &seqno)?; }
+  if json { isrt_crgrps_json(cl, cr, sp);           }
+  if text { isrt_crgrps_text(cl, cr, ln, map)       }
+  return Ok(());
+}
+
+// level_id.rs - Group ID of a hierarchical level line: the level code (HL03)
+// in parentheses, as in "Level: Shipment (S)", else the text following ": "
+// (2021-07-01 bar8tl)
+fn level_id(text: &String) -> Option<String> {
+  let name = text.split_once(": ").map(|(_, n)| n.trim()).unwrap_or("");
+  let code = match (name.rfind('('), name.ends_with(')')) {
+    (Some(idx), true) => name[idx+1..name.len()-1].trim(),
+    _                 => name
+  };
+  if code.len() == 0 {
+    return None;
+  }
+  return Some(code.to_string());
 }
 
 // isrt_crsgms.rs - Insert segment records (2021-07-01 bar8tl)
 fn isrt_crsgms(cnn: &Connection, cl: &[String; 7], cr: &mut CrTp, repo: bool,
-  json: bool, text: bool, sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) {
+  json: bool, text: bool, sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) ->
+  Result<()> {
   cr.sqsgm += 1;
   cr.ixsgm += 1;
   cr.ixfld  = 0;
@@ -262,38 +360,75 @@ fn isrt_crsgms(cnn: &Connection, cl: &[String; 7], cr: &mut CrTp, repo: bool,
   if let Some(idx) = cl[3].find(": ") {
     sgmtp = cl[3][idx+2..cl[3].len()].to_string();
   }
-  if repo { isrt_crsgms_repo(cnn, cl, cr, &sgmtp, &seqno); }
-  if json { isrt_crsgms_json(cl, cr, &sgmtp, sp);          }
-  if text { isrt_crsgms_text(cl, cr, &sgmtp, ln, map);     }
+  if repo { isrt_crsgms_repo(cnn, cl, cr, &sgmtp, &seqno)?; }
+  if json { isrt_crsgms_json(cl, cr, &sgmtp, sp)?;          }
+  if text { isrt_crsgms_text(cl, cr, &sgmtp, ln, map);      }
+  return Ok(());
 }
 
 // isrt_crflds.rs - Insert field records (2021-07-01 bar8tl)
 fn isrt_crflds(cnn: &Connection, cl: &[String; 7], cr: &mut CrTp, repo: bool,
-  json: bool, text: bool, sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) {
+  json: bool, text: bool, sp: &mut SpecsTp, ln: &mut String, map: &MapsTp) ->
+  Result<()> {
   if cl[0].len() > 0 || cl[1].len() > 0 || cl[2].len() > 0 || cl[3].len() > 0 ||
      cl[4].len() > 0 || cl[5].len() > 0 || cl[6].len() > 0 {
     cr.sqfld += 1;
     cr.ixfld += 1;
     let seqno = format!("{:04}", cr.sqfld);
-    if repo { isrt_crflds_repo(cnn, cl, cr, &seqno); }
-    if json { isrt_crflds_json(cl, cr, sp);          }
-    if text { isrt_crflds_text(cl, cr, ln, map);     }
+    if repo { isrt_crflds_repo(cnn, cl, cr, &seqno)?; }
+    if json { isrt_crflds_json(cl, cr, sp)?;          }
+    if text { isrt_crflds_text(cl, cr, ln, map);      }
   }
+  return Ok(());
 }
 
 // proc_mapinv.rs - Process INVOICE mapping specs (2021-07-01 bar8tl)
 // in=EDI Invoices (810,INVOICE). New and changes
-fn proc_mapinv(dbopt: &String, map: &MapsTp, d: &IdxdatTp, repo: bool, json: bool, text: bool) {
-  if d.templ == "outcm" { // specs using rbna common template can use crl procedure
-    proc_mapcrl(dbopt, map, d, repo, json, text);
-  }
+fn proc_mapinv(dbopt: &String, map: &MapsTp, d: &IdxdatTp, repo: bool, json: bool,
+  text: bool, xlsx: bool) -> Result<()> {
+  // invoice specs share the crl procedure, driven by their template definition
+  return proc_mapcrl(dbopt, map, d, repo, json, text, xlsx);
 }
 
 // proc_mapasn.rs - Process ASN mapping specs (2021-07-01 bar8tl)
 // as=EDI ASNs (856,DESADV). New and changes
-fn proc_mapasn(dbopt: &String, map: &MapsTp, d: &IdxdatTp, repo: bool, json: bool, text: bool) {
-  // pending to develop (consider different specs format templates being used)
-  println!("|{}|{:?}|{:?}|{}|{}|{}|", dbopt, map, d, repo, json, text);
+fn proc_mapasn(dbopt: &String, map: &MapsTp, d: &IdxdatTp, repo: bool, json: bool,
+  text: bool, xlsx: bool) -> Result<()> {
+  // ASN specs share the crl procedure, driven by their template definition;
+  // hierarchical levels are taken as groups identified by the level code
+  return proc_mapcrl(dbopt, map, d, repo, json, text, xlsx);
+}
+
+// from_maps.rs - Process a mapping specs taken from the repository or from its
+// json or text backup, instead of from the specs file: it is laid out as lines of
+// the common template and processed as such. Allows restoring the repository from
+// the backups and rendering the MS Excel workbook (2021-07-01 bar8tl)
+// Command line: edimaps map -r -j -t -x --from <repo|json|text> <mapping-specs-id>
+pub fn from_maps(dbopt: &String, rfr: &RefersTp, map: &MapsTp, from: &String,
+  repo: bool, json: bool, text: bool, xlsx: bool) -> Result<()> {
+  let d = get_mapdetail(rfr, map)?;
+  let bpath = |ext: &str| format!("{}{}", map.bkpdr, d.fname.replace(".xlsx", ext));
+  let (sp, spath) = match from.as_str() {
+    "repo" => (read_cr_repo(&Connection::open(dbopt)?, &d.mapid, &d.chgnr)?,
+      dbopt.clone()),
+    "json" => (read_cr_json(&bpath(".json"))?, bpath(".json")),
+    "text" => (read_cr_text(&bpath(".txt"), map)?, bpath(".txt")),
+    _      => return Err(ErrorTp::Config { file: String::new(), detail: format!(
+      "Source '{}' not supported, use repo, json or text", from) })
+  };
+  return reload_specs(dbopt, map, &d, &sp, &spath, repo, json, text, xlsx);
+}
+
+// reload_specs.rs - Process mapping specs taken from the repository or a backup,
+// laid out as lines of the common template with the row numbers their records
+// had in the specs file (2021-07-01 bar8tl)
+fn reload_specs(dbopt: &String, map: &MapsTp, d: &IdxdatTp, sp: &SpecsTp,
+  spath: &String, repo: bool, json: bool, text: bool, xlsx: bool) -> Result<()> {
+  let rows: Vec<SprowTp> = specs_rows(sp, &d.msgtp).into_iter()
+    .map(|(cl, _, rowno)| (rowno, cl)).collect();
+  let tpdef = get_template(&map.tpldr, &OUTCM.to_string(), &d.mapid)?;
+  return proc_specrows(dbopt, map, d, tpdef, &rows, spath, repo, json, text,
+    xlsx);
 }
 
 // get_mapdetail.rs - Get EDI mapping specs detail into an arrangement from internal
@@ -306,6 +441,7 @@ pub struct IdxdatTp {
   pub messg: String,
   pub mvers: String,
   pub idocm: String,
+  pub idoct: String,
   pub mstat: String,
   pub fname: String,
   pub relsd: String,
@@ -317,12 +453,16 @@ pub struct IdxdatTp {
   pub msgtp: String
 }
 
-fn get_mapdetail(rfr: &RefersTp, map: &MapsTp) -> IdxdatTp {
+pub fn get_mapdetail(rfr: &RefersTp, map: &MapsTp) -> Result<IdxdatTp> {
   let mut d = IdxdatTp { ..Default::default() };
   let indx = read_index(IdxkeyTp{
     mapid: map.mapid.clone(), chgnr: map.chgnr.clone(), idxpt: rfr.idxpt.clone(),
-    tabid: rfr.tabid.clone()}, "SINGLE");
-  (d.mapid, d.ctmrs, d.ctmrl, d.messg, d.mvers, d.idocm, d.idocm, d.mstat,
+    tabid: rfr.tabid.clone()}, "SINGLE")?;
+  if indx.len() == 0 {
+    return Err(sheet_error(format!("Mapping specs {}.{} not found in index",
+      map.mapid, map.chgnr), &rfr.idxpt, &rfr.tabid));
+  }
+  (d.mapid, d.ctmrs, d.ctmrl, d.messg, d.mvers, d.idocm, d.idoct, d.mstat,
    d.fname, d.relsd, d.chgnr, d.suprt, d.asgnd, d.dstat, d.templ, d.msgtp) =
   (indx[0][0] .clone(), indx[0][1] .clone(), indx[0][2] .clone(),
    indx[0][3] .clone(), indx[0][4] .clone(), indx[0][5] .clone(),
@@ -330,7 +470,7 @@ fn get_mapdetail(rfr: &RefersTp, map: &MapsTp) -> IdxdatTp {
    indx[0][9] .clone(), indx[0][10].clone(), indx[0][11].clone(),
    indx[0][12].clone(), indx[0][13].clone(), indx[0][14].clone(),
    indx[0][15].clone());
-   return d;
+   return Ok(d);
 }
 
 // fmt_columns.rs - Format mapping fields of all columns (2021-07-01 bar8tl)
@@ -344,3 +484,191 @@ fn fmt_columns(cl: [String; 7], trims: &String, lfchr: &String) -> [String; 7] {
   }
   return c;
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::maps::samples::{asn_rows, sample_detail, sample_maps, specs_repo};
+  use crate::maps::search_maps::find_maps;
+  use crate::readidoc::samples::work_folder;
+  use std::fs::{copy, read_to_string};
+
+  // load_specs.rs - Load mapping specs lines into a repository and read them back
+  fn load_specs(wrkdr: &String, mapid: &str, msgtp: &str) -> SpecsTp {
+    let dbopt = specs_repo(wrkdr);
+    let map = sample_maps(wrkdr);
+    let d = sample_detail(mapid, msgtp);
+    let tpdef = spec_template(&map.tpldr, &d).unwrap();
+    proc_specrows(&dbopt, &map, &d, tpdef, &asn_rows(), &d.fname, true, false,
+      false, false).unwrap();
+    return read_cr_repo(&Connection::open(&dbopt).unwrap(), &d.mapid, &d.chgnr)
+      .unwrap();
+  }
+
+  #[test]
+  fn proc_specrows_takes_asn_levels_as_groups_by_level_code() {
+    let sp = load_specs(&work_folder("maps-asn"), "tst856", ASN);
+    let grps: Vec<(&str, &str)> = sp.groups.iter().map(|g| (g.group.as_str(),
+      g.text.as_str())).collect();
+    assert_eq!(grps, vec![("CTRL", ""), ("S", "HL*1**S"), ("O", "HL*2*1*O"),
+      ("P", "HL*3*2*P"), ("I", "HL*4*3*I")]);
+    let sgms: Vec<(&str, &str, usize)> = sp.groups[1..].iter().map(|g| (
+      g.segments[0].segment.as_str(), g.segments[0].name.as_str(),
+      g.segments[0].fields.len())).collect();
+    assert_eq!(sgms, vec![("E1EDL20", "E2EDL20", 2), ("E1EDL41", "E2EDL41", 1),
+      ("E1EDL37", "E2EDL37", 1), ("E1EDL24", "E2EDL24", 2)]);
+    assert_eq!(sp.header.target_format, "DELVRY07");
+    assert_eq!(sp.header.last_update, "2023-01-01");
+  }
+
+  #[test]
+  fn proc_specrows_ignores_levels_out_of_asn_specs() {
+    let sp = load_specs(&work_folder("maps-inv"), "tst810", "inv");
+    let grps: Vec<&str> = sp.groups.iter().map(|g| g.group.as_str()).collect();
+    assert_eq!(grps, vec!["CTRL", "MAIN"]);
+    let flds: Vec<&str> = sp.groups[1].segments.iter().flat_map(|s| s.fields.iter()
+      .map(|f| f.field.as_str())).collect();
+    assert!(flds.contains(&"Level: Order (O)"));
+    assert!(flds.contains(&"Hierarchical Level: I"));
+  }
+
+  #[test]
+  fn level_id_takes_level_code() {
+    let id = |t: &str| level_id(&t.to_string());
+    assert_eq!(id("Level: Shipment (S)"), Some("S".to_string()));
+    assert_eq!(id("Hierarchical Level: T"), Some("T".to_string()));
+    assert_eq!(id("Level: Tare ( T )"), Some("T".to_string()));
+    assert_eq!(id("Level:"), None);
+    assert_eq!(id("Level: ()"), None);
+  }
+
+  #[test]
+  fn spec_template_keeps_common_layout_for_cr_specs() {
+    let wrkdr = work_folder("maps-template");
+    let outcm = get_template(&wrkdr, &OUTCM.to_string(), &"tst830".to_string())
+      .unwrap();
+    for templ in ["", "cst830", OUTCM] {
+      let d = IdxdatTp { templ: templ.to_string(), ..sample_detail("tst830", CRL) };
+      let tpdef = spec_template(&wrkdr, &d).unwrap();
+      assert_eq!(tpdef.sheet, outcm.sheet);
+      assert_eq!(format!("{:?}", tpdef.colmn), format!("{:?}", outcm.colmn));
+      assert!(tpdef.detect.level.is_empty());
+    }
+    for templ in ["", "cst810"] {
+      let d = IdxdatTp { templ: templ.to_string(), ..sample_detail("tst810", "inv") };
+      assert!(spec_template(&wrkdr, &d).is_err());
+    }
+    let d = sample_detail("tst856", ASN);
+    assert!(!spec_template(&wrkdr, &d).unwrap().detect.level.is_empty());
+  }
+
+  // repo_tables.rs - Records of mapping specs tables, in row order
+  fn repo_tables(dbopt: &String) -> Vec<Vec<String>> {
+    let cnn = Connection::open(dbopt).unwrap();
+    let mut recs: Vec<Vec<String>> = Vec::new();
+    for table in ["headers", "groups", "segments", "fields", "mapspecs"] {
+      let mut stmt = cnn.prepare(&format!(
+        "SELECT * FROM {} ORDER BY rowno, seqno;", table)).unwrap();
+      let ncols = stmt.column_count();
+      let mut rows = stmt.query([]).unwrap();
+      while let Some(row) = rows.next().unwrap() {
+        let mut rec = vec![table.to_string()];
+        rec.extend((0..ncols).map(|i| row.get::<_, String>(i).unwrap()));
+        recs.push(rec);
+      }
+    }
+    return recs;
+  }
+
+  #[test]
+  fn reload_specs_restores_repository_with_row_numbers() {
+    let wrkdr = work_folder("maps-reload");
+    let dbopt = specs_repo(&wrkdr);
+    let map = sample_maps(&wrkdr);
+    let d = sample_detail("tst856", ASN);
+    let tpdef = spec_template(&map.tpldr, &d).unwrap();
+    proc_specrows(&dbopt, &map, &d, tpdef, &asn_rows(), &d.fname, true, true,
+      true, false).unwrap();
+    let loaded = repo_tables(&dbopt);
+    assert!(loaded.contains(&["fields", "tst856", "", "I", "E1EDL24", "MATNR",
+      "LIN03", "", "Part number", "", "", "0022", "0006", "P-100"]
+      .map(|c| c.to_string()).to_vec()));
+    let cnn = Connection::open(&dbopt).unwrap();
+    let specs = [
+      ("repo", read_cr_repo(&cnn, &d.mapid, &d.chgnr).unwrap()),
+      ("json", read_cr_json(&format!("{}tst856.json", wrkdr)).unwrap()),
+      ("text", read_cr_text(&format!("{}tst856.txt", wrkdr), &map).unwrap())];
+    for (from, sp) in specs {
+      reload_specs(&dbopt, &map, &d, &sp, &from.to_string(), true, false, false,
+        false).unwrap();
+      assert_eq!(repo_tables(&dbopt), loaded, "reloaded from {}", from);
+    }
+  }
+
+  #[test]
+  fn xlsx_backup_loads_again_through_its_template() {
+    let wrkdr = work_folder("maps-xlsx");
+    let dbopt = specs_repo(&wrkdr);
+    let map = sample_maps(&wrkdr);
+    let d = sample_detail("tst856", ASN);
+    let tpdef = spec_template(&map.tpldr, &d).unwrap();
+    proc_specrows(&dbopt, &map, &d, tpdef, &asn_rows(), &d.fname, true, false,
+      false, true).unwrap();
+    let loaded = repo_tables(&dbopt);
+    assert!(loaded.contains(&["groups", "tst856", "", "S", "HL*1**S", "1", "M", "",
+      "", "0007", "0002"].map(|c| c.to_string()).to_vec()));
+    let sp = read_cr_repo(&Connection::open(&dbopt).unwrap(), &d.mapid, &d.chgnr)
+      .unwrap();
+    let grps: Vec<String> = specs_rows(&sp, &d.msgtp).into_iter()
+      .filter(|(cl, _, _)| cl[2].contains(": ") && !cl[2].starts_with("Segment"))
+      .map(|(cl, _, _)| cl[2].clone()).collect();
+    assert_eq!(grps, vec!["Level: S", "Level: O", "Level: P", "Level: I"]);
+    copy(format!("{}{}", map.bkpdr, d.fname), format!("{}{}\\{}", map.mapdr,
+      d.ctmrl, d.fname)).unwrap();
+    proc_mapcrl(&dbopt, &map, &d, true, false, false, false).unwrap();
+    assert_eq!(repo_tables(&dbopt), loaded);
+  }
+
+  #[test]
+  fn text_backup_closes_each_group_once() {
+    let wrkdr = work_folder("maps-text");
+    let map = sample_maps(&wrkdr);
+    let d = sample_detail("tst856", ASN);
+    let noctl: Vec<SprowTp> = asn_rows().into_iter()
+      .filter(|(_, cl)| cl[3] != "Name: EDI_DC40" && cl[2] != "SNDPRN").collect();
+    for (rows, ngrps) in [(asn_rows(), 5), (noctl, 4)] {
+      let tpdef = spec_template(&map.tpldr, &d).unwrap();
+      proc_specrows(&":memory:".to_string(), &map, &d, tpdef, &rows, &d.fname,
+        false, false, true, false).unwrap();
+      let tdata = read_to_string(format!("{}tst856.txt", wrkdr)).unwrap();
+      let mut opend: Vec<&str> = Vec::new();
+      let mut begns = 0;
+      for line in tdata.lines() {
+        let keywd = line.trim_start().split(' ').next().unwrap();
+        if let Some(k) = keywd.strip_prefix("BEGIN_") {
+          opend.push(k);
+          begns += (k == "GROUP") as usize;
+        } else if let Some(k) = keywd.strip_prefix("END_") {
+          assert_eq!(opend.pop(), Some(k), "{}", tdata);
+        }
+      }
+      assert!(opend.is_empty(), "{}", tdata);
+      assert_eq!(begns, ngrps);
+    }
+  }
+
+  #[test]
+  fn proc_specrows_keeps_search_index_of_specs_loaded_before() {
+    let wrkdr = work_folder("maps-search");
+    load_specs(&wrkdr, "tst856", ASN);
+    load_specs(&wrkdr, "tst857", ASN);
+    let cnn = Connection::open(format!("{}edimaps.db", wrkdr)).unwrap();
+    add_table(&cnn, "indix").unwrap();
+    let hits = find_maps(&cnn, &"part".to_string(), &None, 10).unwrap();
+    let mut found: Vec<(&str, &str, &str)> = hits.iter().map(|h| (
+      h.mapid.as_str(), h.group.as_str(), h.targt.as_str())).collect();
+    found.sort();
+    assert_eq!(found, vec![("tst856", "I", "MATNR"), ("tst857", "I", "MATNR")]);
+    assert!(hits[0].snipt.contains("[Part]"), "{}", hits[0].snipt);
+  }
+}
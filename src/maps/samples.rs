@@ -0,0 +1,86 @@
+// samples.rs - Test fixtures: mapping specs lines laid out in the common template,
+// their index entry and a repository to load them into (2021-07-01 bar8tl)
+use crate::config::MapsTp;
+use crate::maps::proc_maps::{CrrowTp, IdxdatTp, SprowTp};
+use crate::reposit::ini_repo;
+
+// symbols.rs - Repository tables of mapping specs (2021-07-01 bar8tl)
+pub const MTABLES: [&str; 5] =
+  ["mapspecs", "headers", "groups", "segments", "fields"];
+
+// specs_repo.rs - Repository in a work folder with the mapping specs tables
+// (2021-07-01 bar8tl)
+pub fn specs_repo(wrkdr: &String) -> String {
+  let dbopt = format!("{}edimaps.db", wrkdr);
+  for table in MTABLES {
+    ini_repo(&dbopt, &table.to_string()).unwrap();
+  }
+  return dbopt;
+}
+
+// sample_maps.rs - Mapping specs settings writing backups to a work folder, with
+// the built-in templates (2021-07-01 bar8tl)
+pub fn sample_maps(wrkdr: &String) -> MapsTp {
+  return MapsTp { mapid: String::new(), chgnr: String::new(),
+    mapdr: wrkdr.clone(), bkpdr: wrkdr.clone(), pmode: String::new(),
+    trims: "yes".to_string(), nodat: "no".to_string(), omite: "yes".to_string(),
+    ndchr: String::new(), lfchr: String::new(), tpldr: wrkdr.clone() };
+}
+
+// sample_detail.rs - Index entry of a mapping specs (2021-07-01 bar8tl)
+pub fn sample_detail(mapid: &str, msgtp: &str) -> IdxdatTp {
+  return IdxdatTp { mapid: mapid.to_string(), chgnr: String::new(),
+    fname: format!("{}.xlsx", mapid), templ: "outcm".to_string(),
+    msgtp: msgtp.to_string(), ..Default::default() };
+}
+
+// row.rs - Line of mapping specs in role order (2021-07-01 bar8tl)
+pub fn row(cl: [&str; 7]) -> CrrowTp {
+  return cl.map(|c| c.to_string());
+}
+
+// numbered.rs - Lines of mapping specs with their row number in the specs file
+// (2021-07-01 bar8tl)
+pub fn numbered(rows: Vec<CrrowTp>) -> Vec<SprowTp> {
+  return rows.into_iter().enumerate().map(|(j, cl)| (format!("{:04}", j), cl))
+    .collect();
+}
+
+// header_rows.rs - Header block of a mapping specs (2021-07-01 bar8tl)
+pub fn header_rows(title: &str, tform: &str, sform: &str) -> Vec<CrrowTp> {
+  return vec![
+    row(["", title, "", "", "", "", ""]),
+    row(["", "", "44927", "Author:", "J. Smith", "", ""]),
+    row(["", "", "1.0", "Customer:", "ACME", "", ""]),
+    row(["Field", "", "Target", "Source", "", "", ""]),
+    row(["", "", tform, sform, "", "", ""])];
+}
+
+// asn_rows.rs - Mapping specs of an X12 856 ASN into a DESADV IDoc, organized by
+// hierarchical levels: shipment, order, pack and item. Blank lines separate the
+// levels, as in the specs files (2021-07-01 bar8tl)
+pub fn asn_rows() -> Vec<SprowTp> {
+  let mut rows = header_rows("RBNA Common Mapping - ASN", "DELVRY07", "X12 856");
+  rows.extend([
+    row(["", "", "Control Record", "Name: EDI_DC40", "1", "M", ""]),
+    row(["", "", "SNDPRN", "ISA06", "", "Sender ID", "ACME"]),
+    row(["", "", "Level: Shipment (S)", "HL*1**S", "1", "M", ""]),
+    row(["", "", "Segment: E1EDL20", "Name: E2EDL20", "1", "M", ""]),
+    row(["", "", "VBELN", "BSN02", "", "Shipment ID", "SH001"]),
+    row(["", "", "BTGEW", "TD107", "", "Gross weight", "120"]),
+    row(["", "", "", "", "", "", ""]),
+    row(["", "", "Level: Order (O)", "HL*2*1*O", "999", "M", ""]),
+    row(["", "", "Segment: E1EDL41", "Name: E2EDL41", "1", "M", ""]),
+    row(["", "", "BSTNR", "PRF01", "", "Purchase order", "PO42"]),
+    row(["", "", "", "", "", "", ""]),
+    row(["", "", "Level: Pack (P)", "HL*3*2*P", "999", "O", ""]),
+    row(["", "", "Segment: E1EDL37", "Name: E2EDL37", "1", "M", ""]),
+    row(["", "", "EXIDV", "MAN02", "", "Package label", "L77"]),
+    row(["", "", "", "", "", "", ""]),
+    row(["", "", "Hierarchical Level: I", "HL*4*3*I", "999", "M", ""]),
+    row(["", "", "Segment: E1EDL24", "Name: E2EDL24", "1", "M", ""]),
+    row(["", "", "MATNR", "LIN03", "", "Part number", "P-100"]),
+    row(["", "", "LFIMG", "SN102", "", "Quantity shipped", "10"]),
+    row(["", "", "", "", "", "End of mapping", ""])]);
+  return numbered(rows);
+}
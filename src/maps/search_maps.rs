@@ -0,0 +1,82 @@
+// search_maps.rs - Full-text search over the field mappings in the repository:
+// target, source, rule/condition, comment and description of fields, kept in the
+// FTS5 index fldsrch as specs are added to the repository (2021-07-01 bar8tl)
+use crate::error::{json_error, repo_error, ErrorTp, Result};
+use crate::reposit::add_table;
+use rusqlite::Connection;
+use serde::Serialize;
+
+// symbols.rs - Constants used for full-text search (2021-07-01 bar8tl)
+pub const FLDSRCH: &str = "fldsrch";       // FTS5 index of field mappings
+
+// types.rs - Data types of full-text search (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SrchtTp {         // Search hit
+  pub mapid: String,
+  pub chgnr: String,
+  pub group: String,
+  pub segmn: String,
+  pub targt: String,
+  pub snipt: String,         // Matching text, terms in [ ]
+  pub score: f64             // bm25 rank, lower is better
+}
+
+// search_maps.rs - Search field mappings matching the terms (FTS5 query syntax:
+// words, "phrases", prefix*, AND/OR/NOT, column:term) and print hits ranked by
+// relevance. Optionally rebuild the index from the fields table first, as for
+// repositories loaded before the index existed (2021-07-01 bar8tl)
+// Command line: edimaps search <terms> [-m <messg>] [-n <limit>] [-f text|json]
+//   [--rebuild]
+pub fn search_maps(dbopt: &String, terms: &String, messg: &Option<String>,
+  limit: usize, outfm: &String, rebuild: bool) -> Result<()> {
+  let cnn = Connection::open(dbopt)?;
+  if rebuild {
+    add_table(&cnn, FLDSRCH)?;
+    cnn.execute("DELETE FROM fldsrch;", ()).map_err(|e| repo_error(e, FLDSRCH))?;
+    let n = cnn.execute("INSERT INTO fldsrch SELECT mapid, chgnr, grpid, sgmid,
+      targt, sourc, rcond, commt, dtext FROM fields;", ())
+      .map_err(|e| repo_error(e, FLDSRCH))?;
+    println!("Index {} rebuilt |{:6} fields|", FLDSRCH, n);
+  }
+  let hits = find_maps(&cnn, terms, messg, limit)?;
+  match outfm.as_str() {
+    "text" => {
+      for h in &hits {
+        println!("{:12}|{:4}|{:12}|{:12}|{:10}|{}", h.mapid, h.chgnr, h.group,
+          h.segmn, h.targt, h.snipt.replace('\n', " "));
+      }
+      println!("Hits: {}", hits.len());
+    },
+    "json" => println!("{}", serde_json::to_string_pretty(&hits)
+      .map_err(|e| json_error(e, ""))?),
+    _      => return Err(ErrorTp::Config { file: String::new(), detail: format!(
+      "Output format '{}' not supported, use text or json", outfm) })
+  }
+  return Ok(());
+}
+
+// find_maps.rs - Get field mappings matching the terms, most relevant first,
+// optionally of a message only (2021-07-01 bar8tl)
+pub fn find_maps(cnn: &Connection, terms: &String, messg: &Option<String>,
+  limit: usize) -> Result<Vec<SrchtTp>> {
+  let messg = messg.clone().unwrap_or_default();
+  let mut stmt = cnn.prepare("SELECT s.mapid, s.chgnr, s.grpid, s.sgmid, s.targt,
+    snippet(fldsrch, -1, '[', ']', '...', 12), bm25(fldsrch) FROM fldsrch AS s
+    LEFT JOIN indix AS b ON s.mapid=b.mapid and s.chgnr=b.chgnr
+    WHERE fldsrch MATCH ?1 and (?2='' or b.messg=?2) ORDER BY bm25(fldsrch)
+    LIMIT ?3;").map_err(|e| match e.to_string().contains("no such table") {
+      true  => ErrorTp::Repository { table: FLDSRCH.to_string(), detail:
+        "Search index not found, run search with --rebuild".to_string() },
+      false => repo_error(e, FLDSRCH)
+    })?;
+  let mut rows = stmt.query((terms, &messg, limit as i64))
+    .map_err(|e| repo_error(e, FLDSRCH))?;
+  let mut hits: Vec<SrchtTp> = Vec::new();
+  while let Some(row) = rows.next().map_err(|e| repo_error(e, FLDSRCH))? {
+    hits.push(SrchtTp { mapid: row.get(0)?, chgnr: row.get(1)?, group: row.get(2)?,
+      segmn: row.get(3)?, targt: row.get(4)?, snipt: row.get(5)?,
+      score: row.get(6)? });
+  }
+  return Ok(hits);
+}
+
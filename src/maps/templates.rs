@@ -0,0 +1,182 @@
+// templates.rs - Definitions of MS Excel templates used for EDI mapping
+// specifications: worksheet name, column roles and patterns to detect header,
+// control record, group, segment and end lines. Read from file <templ>.toml in the
+// templates folder, keyed by the index column 'templ' (2021-07-01 bar8tl)
+use crate::error::{ErrorTp, Result};
+use crate::maps::proc_maps::{INBCM, OUTCM};
+use serde::Deserialize;
+use std::fs::{read_dir, read_to_string};
+use std::path::Path;
+
+// symbols.rs - Constants used for mapping specs templates (2021-07-01 bar8tl)
+const BUILTIN: [(&str, &str); 2] = [          // Built-in template definitions
+  (INBCM, include_str!("inbcm.toml")),        // Inbound common template
+  (OUTCM, include_str!("outcm.toml"))];       // Common template
+
+// types.rs - Data types of mapping specs template definition (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TemplTp {
+  pub sheet : String,
+  pub colmn : ColmnTp,
+  pub header: HeadrTp,
+  pub detect: DetctTp
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ColmnTp {         // Spreadsheet column of each role
+  pub descr: usize,
+  pub chang: usize,
+  pub field: usize,
+  pub sourc: usize,
+  pub rcond: usize,
+  pub cmmnt: usize,
+  pub sampl: usize
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HeadrTp {         // Header lines
+  pub title: Vec<PattnTp>,   // Title, opens the header
+  pub authr: Vec<PattnTp>,   // Last update and author
+  pub custm: Vec<PattnTp>,   // Version and customer
+  pub frmts: Vec<PattnTp>    // Heading preceding target and source formats
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DetctTp {         // Body lines
+  pub cntrl: Vec<PattnTp>,   // Control record
+  pub group: Vec<PattnTp>,   // Section or group
+  #[serde(default)]
+  pub level: Vec<PattnTp>,   // Hierarchical level (HL loop), in ASN specs only
+  pub frsgm: Vec<PattnTp>,   // First segment after header or control record
+  pub segmt: Vec<PattnTp>,   // Subsequent segments
+  pub endsp: Vec<PattnTp>    // End of mapping specs
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PattnTp {
+  pub colmn: usize,          // Role column (0-6) to check
+  #[serde(default)]
+  pub contains: String,      // Text contains
+  #[serde(default)]
+  pub starts: String,        // Text starts with
+  #[serde(default)]
+  pub nostart: String,       // Text does not start with
+  #[serde(default = "nocase_default")]
+  pub nocase: bool           // Compare ignoring case
+}
+
+fn nocase_default() -> bool {
+  return true;
+}
+
+// get_template.rs - Get definition of the template of a mapping specs: file
+// <templ>.toml in templates folder, or built-in definition of the common
+// templates (2021-07-01 bar8tl)
+pub fn get_template(tpldr: &String, templ: &String, mapid: &String) ->
+  Result<TemplTp> {
+  let tpath = format!("{}{}.toml", tpldr, templ);
+  let tdefn = if Path::new(&tpath).exists() {
+    read_to_string(&tpath).map_err(|e| ErrorTp::Config { file: tpath.clone(),
+      detail: e.to_string() })?
+  } else if let Some((_, tdefn)) = BUILTIN.iter().find(|(t, _)| t == templ) {
+    tdefn.to_string()
+  } else {
+    return Err(ErrorTp::Config { file: tpath, detail: format!(
+      "Template '{}' of mapping specs {} not supported, available templates: {}",
+      templ, mapid, list_templates(tpldr).join(", ")) });
+  };
+  let tp: TemplTp = toml::from_str(&tdefn).map_err(|e| ErrorTp::Config {
+    file: tpath, detail: e.to_string() })?;
+  return Ok(tp);
+}
+
+// list_templates.rs - IDs of the templates defined, built-in or by a file in
+// templates folder (2021-07-01 bar8tl)
+pub fn list_templates(tpldr: &String) -> Vec<String> {
+  let mut tlist: Vec<String> = BUILTIN.iter().map(|(t, _)| t.to_string())
+    .collect();
+  if let Ok(dir) = read_dir(tpldr) {
+    for p in dir.filter_map(|e| e.ok()).map(|e| e.path()) {
+      if p.extension().map_or(false, |x| x == "toml") {
+        if let Some(t) = p.file_stem().and_then(|t| t.to_str()) {
+          tlist.push(t.to_string());
+        }
+      }
+    }
+  }
+  tlist.sort();
+  tlist.dedup();
+  return tlist;
+}
+
+// get_roles.rs - Arrange spreadsheet columns of a line in role order (descr,
+// chang, field, sourc, rcond, cmmnt, sampl) as used to process the specs
+// (2021-07-01 bar8tl)
+pub fn get_roles(tp: &TemplTp, row: &Vec<String>) -> [String; 7] {
+  let c = &tp.colmn;
+  let cell = |i: usize| if i < row.len() { row[i].clone() } else { String::new() };
+  return [cell(c.descr), cell(c.chang), cell(c.field), cell(c.sourc),
+          cell(c.rcond), cell(c.cmmnt), cell(c.sampl)];
+}
+
+// is_match.rs - Check whether a line (in role order) matches any of the patterns
+// (2021-07-01 bar8tl)
+pub fn is_match(pt: &Vec<PattnTp>, cl: &[String; 7]) -> bool {
+  return match_text(pt, cl).is_some();
+}
+
+// match_text.rs - Get text of the column where a line (in role order) matches
+// first of the patterns (2021-07-01 bar8tl)
+pub fn match_text(pt: &Vec<PattnTp>, cl: &[String; 7]) -> Option<String> {
+  for p in pt.iter().filter(|p| p.colmn < cl.len()) {
+    let fold = |s: &str| if p.nocase { s.to_lowercase() } else { s.to_string() };
+    let text = fold(&cl[p.colmn]);
+    if (p.contains.len() == 0 || text.contains(&fold(&p.contains))) &&
+       (p.starts  .len() == 0 || text.starts_with(&fold(&p.starts))) &&
+       (p.nostart .len() == 0 || !text.starts_with(&fold(&p.nostart))) {
+      return Some(cl[p.colmn].clone());
+    }
+  }
+  return None;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readidoc::samples::work_folder;
+  use std::fs::write;
+
+  #[test]
+  fn get_template_reads_builtin_definitions() {
+    let tpldr = work_folder("templ-builtin");
+    for templ in [INBCM, OUTCM] {
+      let tp = get_template(&tpldr, &templ.to_string(), &"tst810".to_string())
+        .unwrap();
+      assert_eq!(tp.sheet, "Mapping");
+      assert_eq!(tp.detect.segmt.len(), 1);
+    }
+  }
+
+  #[test]
+  fn get_template_prefers_templates_folder() {
+    let tpldr = work_folder("templ-folder");
+    let tdefn = BUILTIN[1].1.replace("sheet = \"Mapping\"", "sheet = \"Specs\"");
+    write(format!("{}{}.toml", tpldr, OUTCM), &tdefn).unwrap();
+    write(format!("{}cst810.toml", tpldr), &tdefn).unwrap();
+    let get = |templ: &str| get_template(&tpldr, &templ.to_string(),
+      &"tst810".to_string()).unwrap().sheet;
+    assert_eq!(get(OUTCM), "Specs");
+    assert_eq!(get(INBCM), "Mapping");
+    assert_eq!(get("cst810"), "Specs");
+  }
+
+  #[test]
+  fn get_template_reports_specs_and_available_templates() {
+    let tpldr = work_folder("templ-missing");
+    write(format!("{}cst810.toml", tpldr), BUILTIN[0].1).unwrap();
+    let e = get_template(&tpldr, &"xyz".to_string(), &"tst810".to_string())
+      .unwrap_err().to_string();
+    assert!(e.contains("Template 'xyz' of mapping specs tst810 not supported, \
+      available templates: cst810, inbcm, outcm"), "{}", e);
+  }
+}
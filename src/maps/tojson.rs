@@ -1,19 +1,22 @@
 // tojson.rs - Function modules being used to convert MS Excel mapping specification
 // files to json text mode format (2021-07-01 bar8tl)
+use crate::error::{io_error, json_error, sheet_error, Result};
 use crate::maps::proc_maps::{CrTp, IdxdatTp};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::Write;
 
 // SpecsTp - Json file structure
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SpecsTp {
   pub header: HeaderTp,
   pub groups: Vec<GroupTp>
 }
 
 // HeaderTp - Header fields
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct HeaderTp {
   pub title        : String,   // Title
   pub last_update  : String,   // Last Update
@@ -21,13 +24,14 @@ pub struct HeaderTp {
   pub version      : String,   // Version / Change number
   pub customer     : String,   // Customer
   pub target_format: String,   // Target Format
-  pub source_format: String    // Source Format
+  pub source_format: String,   // Source Format
+  pub rowno        : String    // Row in specs file
 }
 
 // GroupTp - Group fields
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GroupTp {
-  #[serde(default)]
   pub group   : String,        // Group name
   #[serde(skip_serializing_if = "String::is_empty")]
   pub text    : String,        // Text
@@ -39,11 +43,14 @@ pub struct GroupTp {
   pub descr   : String,        // Description
   #[serde(skip_serializing_if = "String::is_empty")]
   pub change  : String,        // Change
+  #[serde(skip_serializing_if = "String::is_empty")]
+  pub rowno   : String,        // Row in specs file
   pub segments: Vec<SegmentTp> // Child segments
 }
 
 // SegmentTp - Segment fields
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SegmentTp {
   pub segment : String,        // Segment id
   #[serde(skip_serializing_if = "String::is_empty")]
@@ -56,11 +63,14 @@ pub struct SegmentTp {
   pub descr   : String,        // Description
   #[serde(skip_serializing_if = "String::is_empty")]
   pub change  : String,        // Change
+  #[serde(skip_serializing_if = "String::is_empty")]
+  pub rowno   : String,        // Row in specs file
   pub fields  : Vec<FieldTp>   // Child fields
 }
 
 // FieldTp - Field fields
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FieldTp {
   pub field  : String,         // Field name (Target)
   #[serde(skip_serializing_if = "String::is_empty")]
@@ -74,7 +84,9 @@ pub struct FieldTp {
   #[serde(skip_serializing_if = "String::is_empty")]
   pub text   : String,         // Text
   #[serde(skip_serializing_if = "String::is_empty")]
-  pub change : String          // Change
+  pub change : String,         // Change
+  #[serde(skip_serializing_if = "String::is_empty")]
+  pub rowno  : String          // Row in specs file
 }
 
 pub fn init_cr_json(sp: &mut SpecsTp) {
@@ -89,7 +101,8 @@ pub fn isrt_crhdr_json(cr: &CrTp, lstup: &String, sp: &mut SpecsTp) {
     version      : cr.hdr.bvers.clone(),
     customer     : cr.hdr.custm.clone(),
     target_format: cr.hdr.tform.clone(),
-    source_format: cr.hdr.sform.clone()
+    source_format: cr.hdr.sform.clone(),
+    rowno        : cr.rowno.clone()
   };
 }
 
@@ -101,6 +114,7 @@ pub fn isrt_cregrp_json(cr: &CrTp, sp: &mut SpecsTp) {
     status  : String::new(),
     descr   : String::new(),
     change  : String::new(),
+    rowno   : cr.rowno.clone(),
     segments: Vec::new()
   } );
 }
@@ -113,12 +127,17 @@ pub fn isrt_crgrps_json(cl: &[String; 7], cr: &CrTp, sp: &mut SpecsTp) {
     status  : cl[5].clone(),
     descr   : cl[0].clone(),
     change  : cl[1].clone(),
+    rowno   : cr.rowno.clone(),
     segments: Vec::new()
   } );
 }
 
 pub fn isrt_crsgms_json(cl: &[String; 7], cr: &CrTp, sgmtp: &String,
-  sp: &mut SpecsTp) {
+  sp: &mut SpecsTp) -> Result<()> {
+  if cr.ixgrp == 0 {
+    return Err(sheet_error(format!("Segment {} found before any group", cr.insgm),
+      "", &cr.tpdef.sheet));
+  }
   sp.groups[cr.ixgrp as usize-1].segments.push( SegmentTp {
     segment : cr.insgm.clone(),
     name    : sgmtp.to_string(),
@@ -126,12 +145,19 @@ pub fn isrt_crsgms_json(cl: &[String; 7], cr: &CrTp, sgmtp: &String,
     status  : cl[5].clone(),
     descr   : cl[0].clone(),
     change  : cl[1].clone(),
+    rowno   : cr.rowno.clone(),
     fields  : Vec::new()
   } );
+  return Ok(());
 }
 
-pub fn isrt_crflds_json(cl: &[String; 7], cr: &CrTp, sp: &mut SpecsTp) {
+pub fn isrt_crflds_json(cl: &[String; 7], cr: &CrTp, sp: &mut SpecsTp) ->
+  Result<()> {
   //println!("|{}|{}|", cr.ixgrp, cr.ixsgm);
+  if cr.ixgrp == 0 || cr.ixsgm == 0 {
+    return Err(sheet_error(format!("Field {} found before any segment", cl[2]), "",
+      &cr.tpdef.sheet));
+  }
   let wfld = if cl[2] == "" { "<empty>".to_string() } else { cl[2].clone() };
   sp.groups[cr.ixgrp as usize-1].segments[cr.ixsgm as usize-1].fields.push(FieldTp {
     field  : wfld,
@@ -140,15 +166,20 @@ pub fn isrt_crflds_json(cl: &[String; 7], cr: &CrTp, sp: &mut SpecsTp) {
     comment: cl[5].clone(),
     sample : cl[6].clone(),
     text   : cl[0].clone(),
-    change : cl[1].clone()
+    change : cl[1].clone(),
+    rowno  : cr.rowno.clone()
   } );
+  return Ok(());
 }
 
-pub fn write_cr_json(bkpdr: &String, d: &IdxdatTp, sp: &SpecsTp) {
+pub fn write_cr_json(bkpdr: &String, d: &IdxdatTp, sp: &SpecsTp) -> Result<()> {
   let ofnam = d.fname.replace(".xlsx", ".json");
 //  println!("out json = {} {}", bkpdr, ofnam);
-  let mut file = File::create(format!("{}{}", bkpdr, ofnam)).expect("error");
-  let fdata = serde_json::to_string_pretty(sp).unwrap();
+  let opath = format!("{}{}", bkpdr, ofnam);
+  let mut file = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+  let fdata = serde_json::to_string_pretty(sp)
+    .map_err(|e| json_error(e, &opath))?;
   let bdata: &[u8] = fdata.as_bytes();
-  file.write_all(&bdata).unwrap();
+  file.write_all(&bdata).map_err(|e| io_error(e, &opath))?;
+  return Ok(());
 }
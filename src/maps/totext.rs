@@ -1,6 +1,7 @@
 // totxt.rs - Function modules being used to convert MS Excel mapping specification
 // files to flat text mode format (2021-07-01 bar8tl)
 use crate::config::MapsTp;
+use crate::error::{io_error, Result};
 use crate::maps::proc_maps::{CrTp, IdxdatTp};
 use std::fs::File;
 use std::io::Write;
@@ -15,6 +16,7 @@ pub fn isrt_crhdr_text(cr: &CrTp, lstup: &String, s: &mut String, map: &MapsTp)
   sprintf(s, "    CUSTOMER            ", cr.hdr.custm.as_str(), map);
   sprintf(s, "    TARGET_FORMAT       ", cr.hdr.tform.as_str(), map);
   sprintf(s, "    SOURCE_FORMAT       ", cr.hdr.sform.as_str(), map);
+  sprintf(s, "    ROWNO               ", cr.rowno.as_str(), map);
   sprint (s, "  END_HEADER_RECORD");
 }
 
@@ -25,18 +27,22 @@ pub fn isrt_cregrp_text(cr: &CrTp, s: &mut String, map: &MapsTp) {
     sprint (s, "  END_GROUP");
   }
   sprintf(s, "  BEGIN_GROUP           ", cr.ingrp.as_str(), map);
+  sprintf(s, "    ROWNO               ", cr.rowno.as_str(), map);
 }
 
 pub fn isrt_crgrps_text(cl: &[String; 7], cr: &CrTp, s: &mut String, map: &MapsTp) {
-  sprint (s, "      END_FIELDS");
-  sprint (s, "    END_SEGMENT");
-  sprint (s, "  END_GROUP");
+  if cr.ixgrp > 1 {
+    sprint (s, "      END_FIELDS");
+    sprint (s, "    END_SEGMENT");
+    sprint (s, "  END_GROUP");
+  }
   sprintf(s, "  BEGIN_GROUP           ", cr.ingrp.as_str(), map);
   sprintf(s, "    TEXT                ", cl[3].as_str(), map);
   sprintf(s, "    LOOP_MAX            ", cl[4].as_str(), map);
   sprintf(s, "    STATUS              ", cl[5].as_str(), map);
   sprintf(s, "    DESCR               ", cl[0].as_str(), map);
   sprintf(s, "    CHANGE              ", cl[1].as_str(), map);
+  sprintf(s, "    ROWNO               ", cr.rowno.as_str(), map);
 }
 
 pub fn isrt_crsgms_text(cl: &[String; 7], cr: &CrTp, sgmtp: &String, s: &mut String,
@@ -45,12 +51,17 @@ pub fn isrt_crsgms_text(cl: &[String; 7], cr: &CrTp, sgmtp: &String, s: &mut Str
     sprint (s, "      END_FIELDS");
     sprint (s, "    END_SEGMENT");
   }
-  sprintf(s, "    BEGIN_SEGMENT       ", cr.insgm.as_str(), map);
+  if cr.insgm.len() == 0 && map.nodat != "yes" { // keep block of control record
+    sprint (s, "    BEGIN_SEGMENT");
+  } else {
+    sprintf(s, "    BEGIN_SEGMENT       ", cr.insgm.as_str(), map);
+  }
   sprintf(s, "      NAME              ", sgmtp.as_str(), map);
   sprintf(s, "      LOOP_MAX          ", cl[4].as_str(), map);
   sprintf(s, "      STATUS            ", cl[5].as_str(), map);
   sprintf(s, "      DESCR             ", cl[0].as_str(), map);
   sprintf(s, "      CHANGE            ", cl[1].as_str(), map);
+  sprintf(s, "      ROWNO             ", cr.rowno.as_str(), map);
   sprint (s, "      BEGIN_FIELDS");
 }
 
@@ -66,16 +77,19 @@ pub fn isrt_crflds_text(cl: &[String; 7], cr: &CrTp, s: &mut String, map: &MapsT
   sprintf(s, "        SAMPLE          ", cl[6].as_str(), map);
   sprintf(s, "        TEXT            ", cl[0].as_str(), map);
   sprintf(s, "        CHANGE          ", cl[1].as_str(), map);
+  sprintf(s, "        ROWNO           ", cr.rowno.as_str(), map);
 }
 
-pub fn write_cr_text(bkpdr: &String, d: &IdxdatTp, s: &mut String) {
+pub fn write_cr_text(bkpdr: &String, d: &IdxdatTp, s: &mut String) -> Result<()> {
   sprint (s, "      END_FIELDS");
   sprint (s, "    END_SEGMENT");
   sprint (s, "  END_GROUP");
   sprint (s, "END_MAPPING_SPECS");
   let ofnam = d.fname.replace(".xlsx", ".txt");
-  let mut file = File::create(format!("{}{}", bkpdr, ofnam)).expect("error");
-  write!(file, "{}", s).unwrap();
+  let opath = format!("{}{}", bkpdr, ofnam);
+  let mut file = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+  write!(file, "{}", s).map_err(|e| io_error(e, &opath))?;
+  return Ok(());
 }
 
 pub fn sprint (line: &mut String, text: &str) {
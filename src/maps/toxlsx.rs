@@ -0,0 +1,137 @@
+// toxlsx.rs - Function modules being used to render EDI mapping specification as
+// MS Excel workbook in the layout of the RBNA common template (2021-07-01 bar8tl)
+use crate::config::MapsTp;
+use crate::error::{sheet_error, Result};
+use crate::maps::proc_maps::{spec_template, CrrowTp, IdxdatTp, ASN};
+use crate::maps::templates::TemplTp;
+use crate::maps::tojson::{SegmentTp, SpecsTp};
+use chrono::NaiveDate;
+use rust_xlsxwriter::{Format, Workbook, Worksheet};
+
+// symbols.rs - Constants used to render mapping specs workbook (2021-07-01 bar8tl)
+const CTRL : &str = "CTRL";            // Group of control record
+const MAIN : &str = "MAIN";            // Group of segments out of any section
+const EMPTY: &str = "<empty>";         // Field name placeholder in json backup
+const LUPDT: u32 = 1;                  // Line of last update in header block
+const WIDTH: [f64; 7] = [30.0, 8.0, 22.0, 30.0, 30.0, 40.0, 16.0];
+
+// write_cr_xlsx.rs - Write mapping specs workbook, named as its MS Excel file, to
+// backup folder. Rows are placed in the worksheet and role columns of the template
+// the specs are loaded with, at the row number their records had in the specs
+// file, so the workbook can be loaded again (2021-07-01 bar8tl)
+pub fn write_cr_xlsx(map: &MapsTp, d: &IdxdatTp, sp: &SpecsTp) -> Result<()> {
+  let tp = spec_template(&map.tpldr, d)?;
+  let opath = format!("{}{}", map.bkpdr, d.fname);
+  let mut wb = Workbook::new();
+  let ws = wb.add_worksheet();
+  ws.set_name(&tp.sheet).map_err(|e| sheet_error(e, &opath, &tp.sheet))?;
+  let bold = Format::new().set_bold();
+  let mut r: u32 = 0;
+  for (cl, isbld, rowno) in specs_rows(sp, &d.msgtp).iter() {
+    r = rowno.parse::<u32>().map_or(r, |n| n.max(r));
+    let fmt = if *isbld { bold.clone() } else { Format::new() };
+    write_xlsx_row(ws, &tp, r, cl, &fmt).map_err(|e| e.at(&opath, 0))?;
+    r += 1;
+  }
+  let c = &tp.colmn;
+  for (i, ci) in [c.descr, c.chang, c.field, c.sourc, c.rcond, c.cmmnt, c.sampl]
+    .iter().enumerate() {
+    ws.set_column_width(*ci as u16, WIDTH[i])
+      .map_err(|e| sheet_error(e, &opath, &tp.sheet))?;
+  }
+  wb.save(&opath).map_err(|e| sheet_error(e, &opath, &tp.sheet))?;
+  return Ok(());
+}
+
+// specs_rows.rs - Lay out mapping specs as lines of the common template, in role
+// order, flagging group and segment lines to be written in bold: header block,
+// control record, section, segment and field lines and end of mapping. Groups of
+// ASN specs go as hierarchical level lines. Last update goes as the day number
+// MS Excel keeps for dates. Each line goes with the row number its record had in
+// the specs file, empty for lines not giving a record (2021-07-01 bar8tl)
+pub fn specs_rows(sp: &SpecsTp, msgtp: &String) -> Vec<(CrrowTp, bool, String)> {
+  let grmrk = if msgtp == ASN { "Level" } else { "Section" };
+  let h = &sp.header;
+  let none = String::new();
+  let mut rows: Vec<(CrrowTp, bool, String)> = vec![
+    (row_of(["", &h.title, "", "", "", "", ""]), true, none.clone()),
+    (row_of(["", "", &day_number(&h.last_update), "Author:", &h.author, "", ""]),
+      false, none.clone()),
+    (row_of(["", "", &h.version, "Customer:", &h.customer, "", ""]), false,
+      none.clone()),
+    (row_of(["Field", "", "Target", "Source", "", "", ""]), true, none.clone()),
+    (row_of(["", "", &h.target_format, &h.source_format, "", "", ""]), false,
+      h.rowno.clone())];
+  for g in &sp.groups {
+    if g.group == CTRL {
+      for s in &g.segments {
+        rows.push((row_of([&s.descr, &s.change, "Control Record", &name_of(&s.name),
+          &s.loop_max, &s.status, ""]), true, s.rowno.clone()));
+        push_fields(&mut rows, s);
+      }
+      continue;
+    }
+    if g.group != MAIN || g.text.len() > 0 || g.loop_max.len() > 0 ||
+       g.status.len() > 0 || g.descr.len() > 0 || g.change.len() > 0 {
+      rows.push((row_of([&g.descr, &g.change, &format!("{}: {}", grmrk, g.group),
+        &g.text, &g.loop_max, &g.status, ""]), true, g.rowno.clone()));
+    }
+    for s in &g.segments {
+      rows.push((row_of([&s.descr, &s.change, &format!("Segment: {}", s.segment),
+        &name_of(&s.name), &s.loop_max, &s.status, ""]), true, s.rowno.clone()));
+      push_fields(&mut rows, s);
+    }
+  }
+  rows.push((row_of(["", "", "", "", "", "End of mapping", ""]), true, none));
+  return rows;
+}
+
+// push_fields.rs - Add field rows of a segment (2021-07-01 bar8tl)
+fn push_fields(rows: &mut Vec<(CrrowTp, bool, String)>, s: &SegmentTp) {
+  for f in &s.fields {
+    let field = if f.field == EMPTY { "" } else { f.field.as_str() };
+    rows.push((row_of([&f.text, &f.change, field, &f.source, &f.r_cond, &f.comment,
+      &f.sample]), false, f.rowno.clone()));
+  }
+}
+
+// write_xlsx_row.rs - Write a line given in role order into the template columns.
+// Day numbers (last update) are written as numbers (2021-07-01 bar8tl)
+fn write_xlsx_row(ws: &mut Worksheet, tp: &TemplTp, r: u32, cl: &CrrowTp,
+  fmt: &Format) -> Result<()> {
+  let c = &tp.colmn;
+  let colmn = [c.descr, c.chang, c.field, c.sourc, c.rcond, c.cmmnt, c.sampl];
+  for i in 0..cl.len() {
+    if cl[i].len() == 0 {
+      continue;
+    }
+    match (r, cl[i].parse::<f64>()) {
+      (LUPDT, Ok(n)) if i == 2 => ws.write_number_with_format(r, colmn[i] as u16,
+        n, fmt),
+      _ => ws.write_string_with_format(r, colmn[i] as u16, &cl[i], fmt)
+    }.map_err(|e| sheet_error(e, "", &tp.sheet))?;
+  }
+  return Ok(());
+}
+
+// day_number.rs - Convert a date (yyyy-mm-dd) to the day number MS Excel keeps for
+// dates, as it is read when the specs are loaded (2021-07-01 bar8tl)
+pub fn day_number(lupdt: &String) -> String {
+  let strdt = NaiveDate::from_ymd_opt(1900, 1, 1).expect("Error in start date");
+  match NaiveDate::parse_from_str(lupdt, "%Y-%m-%d") {
+    Ok(dt) => return ((dt - strdt).num_days() + 2).to_string(),
+    Err(_) => return lupdt.clone()
+  }
+}
+
+// name_of.rs - Format segment name as read from the specs (2021-07-01 bar8tl)
+fn name_of(name: &String) -> String {
+  if name.len() == 0 {
+    return String::new();
+  }
+  return format!("Name: {}", name);
+}
+
+fn row_of(c: [&str; 7]) -> CrrowTp {
+  return c.map(|s| s.to_string());
+}
@@ -0,0 +1,296 @@
+// queries.rs - Run named, parameterised queries on the edimaps repository, taken
+// from the registry of queries: built-in definitions plus those of the registry
+// file, and print the result as table, CSV or JSON (2021-07-01 bar8tl)
+use crate::error::{io_error, json_error, repo_error, ErrorTp, Result};
+use crate::maps::coverage::csv_line;
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::{read_to_string, File};
+use std::io::Write;
+use std::path::Path;
+
+// symbols.rs - Constants used to run registry queries (2021-07-01 bar8tl)
+const QUERIES_DEF: &str = include_str!("queries.toml"); // Built-in registry
+pub const PARAMS: [&str; 5] = ["messg", "mvers", "field", "mapid", "idoc"];
+
+// types.rs - Data types of the registry of queries (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct QregsTp {
+  #[serde(default)]
+  pub query: Vec<QdefnTp>
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct QdefnTp {
+  pub name : String,
+  #[serde(default)]
+  pub descr: String,
+  pub sql  : String,
+  #[serde(default)]
+  pub reqrd: Vec<String>,                 // Parameters to be given
+  #[serde(default)]
+  pub defaults: HashMap<String, String>   // Values of parameters not given
+}
+
+pub struct RecrdTp<'a> {      // Result row, serialized in column order
+  pub colms: &'a Vec<String>,
+  pub value: &'a Vec<String>
+}
+
+impl Serialize for RecrdTp<'_> {
+  fn serialize<S: Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+    let mut m = s.serialize_map(Some(self.colms.len()))?;
+    for (c, v) in self.colms.iter().zip(self.value.iter()) {
+      m.serialize_entry(c, v)?;
+    }
+    return m.end();
+  }
+}
+
+// run_query.rs - Run a query of the registry with the parameters given, or list
+// the queries of the registry when no query name is given (2021-07-01 bar8tl)
+// Command line: edimaps query [<name>] [--messg <m1,m2,..>] [--mvers <v>]
+//   [--field <f>] [--mapid <id>] [--idoc <type>] [-f table|csv|json] [-o <file>]
+pub fn run_query(dbopt: &String, qrypt: &String, qname: &Option<String>,
+  parms: &HashMap<String, String>, outfm: &String, opath: &Option<String>) ->
+  Result<()> {
+  let qrys = get_queries(qrypt)?;
+  let qname = match qname {
+    Some(q) => q,
+    None    => {
+      for q in &qrys {
+        println!("{:12}|{}", q.name, q.descr);
+      }
+      return Ok(());
+    }
+  };
+  let q = qrys.iter().find(|q| q.name == *qname).ok_or(ErrorTp::Config {
+    file: qrypt.clone(), detail: format!("Query '{}' not defined", qname) })?;
+  let cnn = Connection::open(dbopt)?;
+  let (colms, lines) = exec_query(&cnn, qrypt, q, parms)?;
+  let s = match outfm.as_str() {
+    "table" => table_text(&colms, &lines),
+    "csv"   => {
+      let mut s = csv_line(&colms);
+      lines.iter().for_each(|l| s.push_str(&csv_line(l)));
+      s
+    },
+    "json"  => {
+      let recds: Vec<RecrdTp> = lines.iter().map(|l| RecrdTp { colms: &colms,
+        value: l }).collect();
+      serde_json::to_string_pretty(&recds).map_err(|e| json_error(e, ""))? + "\n"
+    },
+    _       => return Err(ErrorTp::Config { file: String::new(), detail: format!(
+      "Output format '{}' not supported, use table, csv or json", outfm) })
+  };
+  match opath {
+    Some(p) => {
+      let mut file = File::create(p).map_err(|e| io_error(e, p))?;
+      file.write_all(s.as_bytes()).map_err(|e| io_error(e, p))?;
+      println!("Query {} |{:6} rows|", qname, lines.len());
+    },
+    None => print!("{}", s)
+  }
+  return Ok(());
+}
+
+// exec_query.rs - Execute a query of the registry, binding the parameters it uses
+// by name: those given, else their default, else empty. Parameters required by
+// the query must be given. Values are returned as text, in column order
+// (2021-07-01 bar8tl)
+pub fn exec_query(cnn: &Connection, qrypt: &String, q: &QdefnTp,
+  parms: &HashMap<String, String>) -> Result<(Vec<String>, Vec<Vec<String>>)> {
+  for p in &q.reqrd {
+    if parms.get(p).map(|v| v.len() == 0).unwrap_or(true) {
+      return Err(ErrorTp::Config { file: qrypt.clone(), detail: format!(
+        "Query '{}' requires parameter --{}", q.name, p) });
+    }
+  }
+  let rerr = |e: rusqlite::Error| repo_error(e, &format!("query {}", q.name));
+  let mut stmt = cnn.prepare(&q.sql).map_err(rerr)?;
+  for p in PARAMS {
+    if let Some(i) = stmt.parameter_index(&format!(":{}", p)).map_err(rerr)? {
+      let value = parms.get(p).filter(|v| v.len() > 0).or(q.defaults.get(p))
+        .cloned().unwrap_or_default();
+      stmt.raw_bind_parameter(i, value).map_err(rerr)?;
+    }
+  }
+  let colms: Vec<String> = stmt.column_names().iter().map(|c| c.to_string())
+    .collect();
+  let mut lines: Vec<Vec<String>> = Vec::new();
+  let mut rows = stmt.raw_query();
+  while let Some(row) = rows.next().map_err(rerr)? {
+    let mut line: Vec<String> = Vec::with_capacity(colms.len());
+    for i in 0..colms.len() {
+      line.push(match row.get_ref(i).map_err(rerr)? {
+        ValueRef::Null       => String::new(),
+        ValueRef::Integer(n) => n.to_string(),
+        ValueRef::Real(n)    => n.to_string(),
+        ValueRef::Text(t)    => String::from_utf8_lossy(t).to_string(),
+        ValueRef::Blob(b)    => format!("<{} bytes>", b.len())
+      });
+    }
+    lines.push(line);
+  }
+  return Ok((colms, lines));
+}
+
+// get_queries.rs - Get registry of queries: built-in definitions, replaced or
+// extended by those of the registry file, if it exists (2021-07-01 bar8tl)
+fn get_queries(qrypt: &String) -> Result<Vec<QdefnTp>> {
+  let mut qrys = toml::from_str::<QregsTp>(QUERIES_DEF).map_err(|e|
+    ErrorTp::Config { file: "queries.toml".to_string(), detail: e.to_string() })?
+    .query;
+  if Path::new(qrypt).exists() {
+    let qdefn = read_to_string(qrypt).map_err(|e| ErrorTp::Config {
+      file: qrypt.clone(), detail: e.to_string() })?;
+    let owned: QregsTp = toml::from_str(&qdefn).map_err(|e| ErrorTp::Config {
+      file: qrypt.clone(), detail: e.to_string() })?;
+    for q in owned.query {
+      match qrys.iter().position(|b| b.name == q.name) {
+        Some(i) => qrys[i] = q,
+        None    => qrys.push(q)
+      }
+    }
+  }
+  return Ok(qrys);
+}
+
+// table_text.rs - Format result as a table of columns aligned to their widest
+// value (2021-07-01 bar8tl)
+pub fn table_text(colms: &Vec<String>, lines: &Vec<Vec<String>>) -> String {
+  let mut width: Vec<usize> = colms.iter().map(|c| c.chars().count()).collect();
+  for l in lines {
+    for (i, v) in l.iter().enumerate() {
+      width[i] = width[i].max(v.chars().count());
+    }
+  }
+  let fmt_line = |l: &Vec<String>| -> String {
+    let cells: Vec<String> = l.iter().enumerate().map(|(i, v)|
+      format!("{:w$}", v, w = width[i])).collect();
+    format!("{}\n", cells.join("|").trim_end())
+  };
+  let mut s = fmt_line(colms);
+  s.push_str(&format!("{}\n", width.iter().map(|w| "-".repeat(*w))
+    .collect::<Vec<String>>().join("+")));
+  for l in lines {
+    s.push_str(&fmt_line(l));
+  }
+  s.push_str(&format!("({} rows)\n", lines.len()));
+  return s;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::maps::samples::specs_repo;
+  use crate::readidoc::samples::work_folder;
+  use crate::reposit::ini_repo;
+  use std::fs::write;
+
+  const TABLES: [&str; 7] =
+    ["items", "struc", "cdcodes", "cddata", "cdfield", "indix", "pipsts"];
+
+  // query_repo.rs - Repository with the tables the built-in queries read and
+  // field mappings of an 856 and an 830 specs
+  fn query_repo(wrkdr: &String) -> Connection {
+    let dbopt = specs_repo(wrkdr);
+    for table in TABLES {
+      ini_repo(&dbopt, &table.to_string()).unwrap();
+    }
+    let cnn = Connection::open(&dbopt).unwrap();
+    for (mapid, messg) in [("tst856", "856"), ("tst830", "830")] {
+      cnn.execute("INSERT INTO indix (mapid, chgnr, messg, mvers) VALUES
+        (?1, '', ?2, '4010');", [mapid, messg]).unwrap();
+    }
+    for (mapid, targt, rowno) in [("tst856", "VBELN", "0001"),
+      ("tst856", "MATNR", "0002"), ("tst830", "MATNR", "0001")] {
+      cnn.execute("INSERT INTO fields (mapid, chgnr, targt, rowno) VALUES
+        (?1, '', ?2, ?3);", [mapid, targt, rowno]).unwrap();
+    }
+    return cnn;
+  }
+
+  fn query_of(qrys: &Vec<QdefnTp>, qname: &str) -> QdefnTp {
+    return qrys.iter().find(|q| q.name == qname).unwrap().clone();
+  }
+
+  fn parms_of(parms: &[(&str, &str)]) -> HashMap<String, String> {
+    return parms.iter().map(|(p, v)| (p.to_string(), v.to_string())).collect();
+  }
+
+  #[test]
+  fn get_queries_parses_builtin_registry() {
+    let qrys = get_queries(&format!("{}queries.toml", work_folder("query-builtin")))
+      .unwrap();
+    let names: Vec<&str> = qrys.iter().map(|q| q.name.as_str()).collect();
+    assert_eq!(names, vec!["fields", "inbsd", "inbmsc", "outsd", "fieldmap",
+      "items", "struc", "codes", "pipruns"]);
+    assert!(qrys.iter().all(|q| q.descr.len() > 0 && q.sql.len() > 0));
+    assert_eq!(query_of(&qrys, "fieldmap").reqrd, vec!["field"]);
+    assert!(query_of(&qrys, "inbsd").defaults["messg"].contains("830"));
+  }
+
+  #[test]
+  fn get_queries_lets_registry_file_replace_builtin_queries() {
+    let qrypt = format!("{}queries.toml", work_folder("query-file"));
+    write(&qrypt, "[[query]]\nname = \"fields\"\ndescr = \"Own fields\"\n\
+      sql = \"SELECT targt FROM fields;\"\n\n[[query]]\nname = \"mine\"\n\
+      sql = \"SELECT mapid FROM indix;\"\n").unwrap();
+    let qrys = get_queries(&qrypt).unwrap();
+    assert_eq!(qrys.len(), 10);
+    assert_eq!((qrys[0].name.as_str(), qrys[0].descr.as_str()),
+      ("fields", "Own fields"));
+    assert_eq!(qrys[9].name, "mine");
+    write(&qrypt, "[[query]]\nname = \"nosql\"\n").unwrap();
+    assert!(get_queries(&qrypt).is_err());
+  }
+
+  #[test]
+  fn exec_query_binds_parameters_given_or_default() {
+    let wrkdr = work_folder("query-params");
+    let cnn = query_repo(&wrkdr);
+    let qrys = get_queries(&format!("{}queries.toml", wrkdr)).unwrap();
+    let mapped = |qname: &str, parms: &[(&str, &str)]| -> Vec<(String, String)> {
+      let (colms, lines) = exec_query(&cnn, &String::new(), &query_of(&qrys, qname),
+        &parms_of(parms)).unwrap();
+      let t = colms.iter().position(|c| c == "targt").unwrap();
+      return lines.iter().map(|l| (l[0].clone(), l[t].clone())).collect();
+    };
+    let pair = |m: &str, f: &str| (m.to_string(), f.to_string());
+    assert_eq!(mapped("fields", &[]), vec![pair("tst830", "MATNR"),
+      pair("tst856", "VBELN"), pair("tst856", "MATNR")]);
+    assert_eq!(mapped("fields", &[("messg", ""), ("mvers", "")]),
+      mapped("fields", &[]));
+    assert_eq!(mapped("fields", &[("messg", "810,856")]), vec![
+      pair("tst856", "VBELN"), pair("tst856", "MATNR")]);
+    assert_eq!(mapped("fields", &[("field", "MAT%"), ("mvers", "4010")]), vec![
+      pair("tst830", "MATNR"), pair("tst856", "MATNR")]);
+    assert!(mapped("fields", &[("mvers", "5010")]).is_empty());
+    assert_eq!(mapped("inbsd", &[]), vec![pair("tst830", "MATNR")]);
+    assert_eq!(mapped("inbsd", &[("messg", "856")]).len(), 2);
+    assert_eq!(mapped("outsd", &[]).len(), 2);
+    assert_eq!(mapped("fieldmap", &[("field", "VBELN")]), vec![
+      pair("tst856", "VBELN")]);
+    for parms in [vec![], vec![("field", "")]] {
+      let e = exec_query(&cnn, &String::new(), &query_of(&qrys, "fieldmap"),
+        &parms_of(&parms)).unwrap_err();
+      assert!(e.to_string().contains("requires parameter --field"), "{}", e);
+    }
+  }
+
+  #[test]
+  fn builtin_queries_run_on_repository_tables() {
+    let wrkdr = work_folder("query-all");
+    let cnn = query_repo(&wrkdr);
+    for q in get_queries(&format!("{}queries.toml", wrkdr)).unwrap() {
+      let (colms, _) = exec_query(&cnn, &String::new(), &q,
+        &parms_of(&[("field", "%")]))
+        .unwrap_or_else(|e| panic!("{}: {}", q.name, e));
+      assert!(colms.len() > 0, "{}", q.name);
+    }
+  }
+}
@@ -0,0 +1,7 @@
+// mod.rs - Function modules used for EDI X12 and EDIFACT content processing
+// (2021-07-01 bar8tl)
+#[cfg(test)]
+pub mod samples;
+pub mod segments;
+pub mod toidoc;
+pub mod tojson;
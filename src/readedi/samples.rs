@@ -0,0 +1,95 @@
+// samples.rs - Test fixtures: small EDI X12 and EDIFACT interchanges with loops,
+// hierarchical levels and escaped delimiters (2021-07-01 bar8tl)
+
+// symbols.rs - X12 830 planning schedule: N1 loops holding N3, LIN loops holding
+// a composite UIT and repeated FST forecasts, closed by the CTT summary
+// (2021-07-01 bar8tl)
+pub const X12830: &str = "\
+ISA*00*          *00*          *ZZ*SENDER         *ZZ*RECEIVER       \
+*230101*1200*U*00401*000000001*0*P*>~
+GS*PS*SENDER*RECEIVER*20230101*1200*1*X*004010~
+ST*830*0001~
+BFR*05**1*DL*A*20230101*20231231*20230101~
+N1*ST*PLANT 1~
+N3*1 MAIN ST~
+N1*SU*SUPPLIER~
+N3*2 SIDE ST~
+LIN**BP*P-100~
+UIT*EA>1~
+FST*100*C*D*20230109~
+FST*200*C*D*20230116~
+LIN**BP*P-200~
+UIT*EA>1~
+FST*300*C*D*20230109~
+CTT*2~
+SE*15*0001~
+GE*1*1~
+IEA*1*000000001~
+";
+
+// symbols.rs - X12 856 ship notice: shipment, orders and items as hierarchical
+// levels, each HL referring to its parent level (2021-07-01 bar8tl)
+pub const X12856: &str = "\
+ISA*00*          *00*          *ZZ*SENDER         *ZZ*RECEIVER       \
+*230101*1200*U*00401*000000002*0*P*>~
+GS*SH*SENDER*RECEIVER*20230101*1200*2*X*004010~
+ST*856*0001~
+BSN*00*SH001*20230101*1200~
+HL*1**S~
+TD1*CTN*2~
+HL*2*1*O~
+PRF*PO42~
+HL*3*2*I~
+LIN**BP*P-100~
+SN1**10*EA~
+HL*4*2*I~
+LIN**BP*P-200~
+SN1**5*EA~
+HL*5*1*O~
+PRF*PO43~
+HL*6*5*I~
+LIN**BP*P-300~
+SN1**7*EA~
+CTT*6~
+SE*19*0001~
+GE*1*2~
+IEA*1*000000002~
+";
+
+// symbols.rs - EDIFACT DELFOR delivery schedule with UNA service string advice
+// overriding the delimiters, release character '?'. A DTM in the header and again
+// in the QTY loops nested in the LIN loops (2021-07-01 bar8tl)
+pub const DELFOR: &str = "\
+UNA>*.? ~
+UNB*UNOC>3*SENDER*RECEIVER*230101>1200*1~
+UNH*1*DELFOR>D>97A>UN~
+BGM*241*DF1~
+DTM*137>20230101>102~
+NAD*BY*BUYER~
+NAD*SE*SELLER?*S ?>AG~
+LIN***P-100>IN~
+PIA*1*A1>SA~
+QTY*1>100~
+SCC*1~
+DTM*2>20230109>102~
+QTY*1>200~
+SCC*4~
+DTM*2>20230116>102~
+LIN***P-200>IN~
+PIA*1*A2>SA~
+QTY*1>300~
+SCC*1~
+DTM*2>20230109>102~
+UNT*19*1~
+UNZ*1*1~
+";
+
+// symbols.rs - EDIFACT interchange without UNA, default delimiters and release
+// character escaping them (2021-07-01 bar8tl)
+pub const EDFDEF: &str = "\
+UNB+UNOC:3+SENDER+RECEIVER+230101:1200+2'
+UNH+1+DELFOR:D:97A:UN'
+FTX+AAI+++WHAT?'S UP?? 50?+?:1'
+UNT+3+1'
+UNZ+1+2'
+";
@@ -0,0 +1,208 @@
+// segments.rs - Split EDI X12 and EDIFACT interchanges into segments, elements
+// and components. Delimiters are taken from the ISA segment (X12) or from the UNA
+// service string advice (EDIFACT, defaults when absent), unless the workflow
+// settings override them (2021-07-01 bar8tl)
+use crate::config::WkflowTp;
+use crate::error::{edi_error, io_error, Result};
+use serde::Serialize;
+use std::fs::read_to_string;
+
+// symbols.rs - Constants used to split EDI interchanges (2021-07-01 bar8tl)
+pub const X12    : &str = "X12";
+pub const EDIFACT: &str = "EDIFACT";
+const ISALN: usize = 106;              // Length of X12 ISA segment
+const UNALN: usize = 9;                // Length of EDIFACT UNA service string
+
+// types.rs - Data types of EDI segments (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DelimTp {         // Delimiters of an interchange
+  pub stndr: String,         // X12 or EDIFACT
+  pub elmsp: char,           // Element separator
+  pub cmpsp: char,           // Component separator
+  pub segtr: char,           // Segment terminator
+  pub rlsch: Option<char>    // Release character (EDIFACT)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EdisgTp {         // EDI segment
+  pub segno: usize,          // Position in the interchange, from 1
+  pub tag  : String,
+  pub elems: Vec<Vec<String>> // Elements (01, 02, ...), each with its components
+}
+
+impl EdisgTp {
+  // value - Value of element (from 1) and component (from 1; 0 = whole element),
+  // empty when the segment has no such element
+  pub fn value(&self, elemn: usize, compn: usize) -> String {
+    let e = match self.elems.get(elemn.wrapping_sub(1)) {
+      Some(e) => e,
+      None    => return String::new()
+    };
+    if compn == 0 {
+      return e.first().cloned().unwrap_or_default();
+    }
+    return e.get(compn - 1).cloned().unwrap_or_default();
+  }
+}
+
+// read_edi_file.rs - Read EDI file and split it into segments (2021-07-01 bar8tl)
+pub fn read_edi_file(ipath: &String, wkf: &WkflowTp) ->
+  Result<(DelimTp, Vec<EdisgTp>)> {
+  let text = read_to_string(ipath).map_err(|e| io_error(e, ipath))?;
+  return split_segments(&text, wkf).map_err(|e| e.at(ipath, 0));
+}
+
+// split_segments.rs - Detect standard and delimiters of an interchange and split it
+// into segments. Line breaks after segment terminators are ignored
+// (2021-07-01 bar8tl)
+pub fn split_segments(text: &str, wkf: &WkflowTp) ->
+  Result<(DelimTp, Vec<EdisgTp>)> {
+  let chars: Vec<char> = text.trim_start().chars().collect();
+  let head: String = chars.iter().take(3).collect();
+  let (mut dl, start) = match head.as_str() {
+    "ISA" => {
+      if chars.len() < ISALN {
+        return Err(edi_error(1, "ISA", "Interchange header too short".to_string()));
+      }
+      (DelimTp { stndr: X12.to_string(), elmsp: chars[3], cmpsp: chars[104],
+        segtr: chars[105], rlsch: None }, 0)
+    },
+    "UNA" => {
+      if chars.len() < UNALN {
+        return Err(edi_error(1, "UNA", "Service string advice too short"
+          .to_string()));
+      }
+      (DelimTp { stndr: EDIFACT.to_string(), cmpsp: chars[3], elmsp: chars[4],
+        rlsch: if chars[6] == ' ' { None } else { Some(chars[6]) },
+        segtr: chars[8] }, UNALN)
+    },
+    "UNB" => (DelimTp { stndr: EDIFACT.to_string(), cmpsp: ':', elmsp: '+',
+      rlsch: Some('?'), segtr: '\'' }, 0),
+    _     => return Err(edi_error(1, &head,
+      "Not an X12 (ISA) or EDIFACT (UNA/UNB) interchange".to_string()))
+  };
+  if let Some(c) = wkf.elmsp.chars().next() { dl.elmsp = c; }
+  if let Some(c) = wkf.cmpsp.chars().next() { dl.cmpsp = c; }
+  if let Some(c) = wkf.segtr.chars().next() { dl.segtr = c; }
+  if let Some(c) = wkf.rlsch.chars().next() { dl.rlsch = Some(c); }
+  let mut sgmts: Vec<EdisgTp> = Vec::new();
+  let mut elems: Vec<Vec<String>> = vec![vec![String::new()]];
+  let mut i = start;
+  while i < chars.len() {
+    let c = chars[i];
+    if Some(c) == dl.rlsch && i + 1 < chars.len() {
+      elems.last_mut().unwrap().last_mut().unwrap().push(chars[i+1]);
+      i += 2;
+      continue;
+    }
+    if c == dl.segtr {
+      push_segment(&mut sgmts, elems);
+      elems = vec![vec![String::new()]];
+    } else if c == dl.elmsp {
+      elems.push(vec![String::new()]);
+    } else if c == dl.cmpsp && !(dl.stndr == X12 && sgmts.len() == 0) {
+      elems.last_mut().unwrap().push(String::new()); // ISA16 is the separator
+    } else if c == '\r' || c == '\n' {
+      // Line breaks only separate segments, never belong to a value
+    } else {
+      elems.last_mut().unwrap().last_mut().unwrap().push(c);
+    }
+    i += 1;
+  }
+  push_segment(&mut sgmts, elems);
+  return Ok((dl, sgmts));
+}
+
+// push_segment.rs - Add segment split so far, skipping empty ones
+// (2021-07-01 bar8tl)
+fn push_segment(sgmts: &mut Vec<EdisgTp>, mut elems: Vec<Vec<String>>) {
+  let tag = elems[0].join("").trim().to_string();
+  if tag.len() == 0 {
+    return;
+  }
+  elems.remove(0);
+  sgmts.push(EdisgTp { segno: sgmts.len() + 1, tag: tag, elems: elems });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readedi::samples::{DELFOR, EDFDEF, X12830};
+
+  fn wkflow() -> WkflowTp {
+    return WkflowTp { ..Default::default() };
+  }
+
+  #[test]
+  fn split_segments_takes_x12_delimiters_from_isa() {
+    let (dl, sgmts) = split_segments(X12830, &wkflow()).unwrap();
+    assert_eq!(dl.stndr, X12);
+    assert_eq!((dl.elmsp, dl.cmpsp, dl.segtr, dl.rlsch), ('*', '>', '~', None));
+    assert_eq!(sgmts.len(), 19);
+    assert_eq!(sgmts[0].tag, "ISA");
+    assert_eq!(sgmts[0].elems.len(), 16);
+    assert_eq!(sgmts[0].value(16, 0), ">");
+    assert_eq!(sgmts[0].value(6, 0), "SENDER         ");
+    let uit = &sgmts[9];
+    assert_eq!((uit.segno, uit.tag.as_str()), (10, "UIT"));
+    assert_eq!(uit.elems[0], vec!["EA", "1"]);
+    assert_eq!((uit.value(1, 1), uit.value(1, 2), uit.value(2, 0)),
+      ("EA".to_string(), "1".to_string(), String::new()));
+    assert_eq!(sgmts[18].tag, "IEA");
+  }
+
+  #[test]
+  fn split_segments_ignores_line_breaks() {
+    let crlf = X12830.replace("\n", "\r\n");
+    let oneln = X12830.replace("\n", "");
+    let (_, sgcrl) = split_segments(&crlf, &wkflow()).unwrap();
+    let (_, sgone) = split_segments(&oneln, &wkflow()).unwrap();
+    assert_eq!(sgcrl.len(), sgone.len());
+    for (a, b) in sgcrl.iter().zip(sgone.iter()) {
+      assert_eq!((&a.tag, &a.elems), (&b.tag, &b.elems));
+    }
+    assert_eq!(sgcrl[4].value(2, 0), "PLANT 1");
+  }
+
+  #[test]
+  fn split_segments_takes_una_delimiters_and_release_character() {
+    let (dl, sgmts) = split_segments(DELFOR, &wkflow()).unwrap();
+    assert_eq!(dl.stndr, EDIFACT);
+    assert_eq!((dl.elmsp, dl.cmpsp, dl.segtr, dl.rlsch), ('*', '>', '~', Some('?')));
+    assert_eq!(sgmts[0].tag, "UNB");
+    assert_eq!(sgmts[0].elems[0], vec!["UNOC", "3"]);
+    assert_eq!(sgmts[1].elems[1], vec!["DELFOR", "D", "97A", "UN"]);
+    let nad = &sgmts[5];
+    assert_eq!(nad.tag, "NAD");
+    assert_eq!(nad.elems[1], vec!["SELLER*S >AG"]);
+    assert_eq!(sgmts.last().unwrap().tag, "UNZ");
+    assert_eq!(sgmts.len(), 21);
+  }
+
+  #[test]
+  fn split_segments_defaults_edifact_delimiters_without_una() {
+    let (dl, sgmts) = split_segments(EDFDEF, &wkflow()).unwrap();
+    assert_eq!((dl.elmsp, dl.cmpsp, dl.segtr, dl.rlsch), ('+', ':', '\'', Some('?')));
+    assert_eq!(sgmts.len(), 5);
+    assert_eq!(sgmts[2].tag, "FTX");
+    assert_eq!(sgmts[2].elems.len(), 4);
+    assert_eq!(sgmts[2].elems[3], vec!["WHAT'S UP? 50+:1"]);
+  }
+
+  #[test]
+  fn split_segments_applies_workflow_delimiters() {
+    let text = EDFDEF.replace("'", "!").replace("?!", "?'");
+    let wkf = WkflowTp { segtr: "!".to_string(), ..Default::default() };
+    let (dl, sgmts) = split_segments(&text, &wkf).unwrap();
+    assert_eq!(dl.segtr, '!');
+    assert_eq!(sgmts.len(), 5);
+    assert_eq!(sgmts[2].value(4, 0), "WHAT'S UP? 50+:1");
+  }
+
+  #[test]
+  fn split_segments_rejects_other_content() {
+    assert!(split_segments("<?xml version=\"1.0\"?>", &wkflow()).is_err());
+    assert!(split_segments("ISA*00*~", &wkflow()).is_err());
+    assert!(split_segments("UNA:+", &wkflow()).is_err());
+  }
+}
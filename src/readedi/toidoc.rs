@@ -0,0 +1,356 @@
+// toidoc.rs - Apply a mapping specification of the repository to an inbound EDI
+// X12 or EDIFACT file, generating the IDoc in fixed-size format. Only sources
+// given as simple element paths (LIN03, N104, SDP01:1) with no rule/condition
+// are evaluated; rows with any other source or with rule text are reported as not
+// machine-evaluable (2021-07-01 bar8tl)
+use crate::config::{line_end, MapsTp, RefersTp, WkflowTp};
+use crate::error::{io_error, Result};
+use crate::maps::fromrepo::read_cr_repo;
+use crate::maps::proc_maps::get_mapdetail;
+use crate::maps::tojson::SegmentTp;
+use crate::readedi::segments::{read_edi_file, EdisgTp, X12};
+use crate::readidoc::validate::{load_definition, FldefTp, ValidTp,
+  UNKNOWN_FIELD, UNKNOWN_SEGMENT};
+use rusqlite::Connection;
+use std::fs::File;
+use std::io::Write;
+
+// symbols.rs - Constants used to apply mapping specs to EDI files
+// (2021-07-01 bar8tl)
+pub const NOT_EVALUABLE  : &str = "NOT_EVALUABLE";
+pub const VALUE_TRUNCATED: &str = "VALUE_TRUNCATED";
+const CTRL   : &str = "CTRL";          // Group of control record
+const CONTROL: &str = "CONTROL";       // Control record definition
+const DATA   : &str = "DATA";          // Data record definition
+const EMPTY  : &str = "<empty>";
+const INBOUND: &str = "2";             // Direction of the IDoc
+const MSGHDR : [&str; 2] = ["ST", "UNH"]; // Message headers
+const MSGTRL : [&str; 2] = ["SE", "UNT"]; // Message trailers
+
+// types.rs - Data types used to apply mapping specs to EDI files
+// (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default)]
+pub struct EpathTp {         // Element path of a source
+  pub tag  : String,
+  pub elemn: usize,
+  pub compn: usize           // 0 = whole element
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SgrulTp {         // Evaluable rules of a spec segment
+  pub sgdef: usize,          // Segment definition
+  pub rules: Vec<(String, EpathTp)> // Target field, source path
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AfindTp {         // Finding while applying the specs
+  pub rulid: String,
+  pub group: String,
+  pub segmn: String,
+  pub field: String,
+  pub dtext: String
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OsegmTp {         // IDoc segment instance to write
+  pub segpo: usize,          // Position of its driving EDI segment
+  pub order: usize,          // Order of its rules in the specs
+  pub sgdef: usize,
+  pub value: Vec<(String, String)>
+}
+
+// apply_maps.rs - Generate the IDoc of each message (ST/UNH) of an EDI file as
+// the mapping specs describe it, into output file (default <edi-file>_idoc.txt).
+// Each segment of the specs is driven by the EDI segment of its first evaluable
+// source: one IDoc segment per occurrence of it; other sources are taken from
+// that occurrence up to the next one or, if absent there, from their last
+// occurrence before it (2021-07-01 bar8tl)
+// Command line: edimaps map apply <mapid.chgnr> <edi-file> [-i <idoc-type>]
+//   [-o <file>]
+pub fn apply_maps(dbopt: &String, rfr: &RefersTp, map: &MapsTp, wkf: &WkflowTp,
+  ipath: &String, idocn: &Option<String>, opath: &Option<String>) -> Result<()> {
+  let cnn = Connection::open(dbopt)?;
+  let idocn = match idocn {
+    Some(i) => i.clone(),
+    None    => get_mapdetail(rfr, map)?.idoct
+  };
+  let mut v = ValidTp { ..Default::default() };
+  load_definition(&cnn, &mut v, &idocn)?;
+  let idocb: String = cnn.query_row("SELECT dname FROM items WHERE idocn=?1 and
+    rname=\"IDOC\";", [&idocn], |row| row.get(0)).unwrap_or_default();
+  let sp = read_cr_repo(&cnn, &map.mapid, &map.chgnr)?;
+  let (dl, sgmts) = read_edi_file(ipath, wkf)?;
+  let mut findg: Vec<AfindTp> = Vec::new();
+  let mut crule: Vec<(String, EpathTp)> = Vec::new();
+  let mut srule: Vec<SgrulTp> = Vec::new();
+  for g in &sp.groups {
+    for s in &g.segments {
+      let segmn = if s.segment.len() > 0 { &s.segment } else { &s.name };
+      let find = |rulid: &str, field: &str, dtext: String| AfindTp {
+        rulid: rulid.to_string(), group: g.group.clone(), segmn: segmn.clone(),
+        field: field.to_string(), dtext: dtext };
+      let sgdef = if g.group == CTRL { None } else {
+        match v.sgidx.get(&s.segment).or(v.sgtyp.get(&s.name)) {
+          Some(i) => Some(*i),
+          None    => {
+            findg.push(find(UNKNOWN_SEGMENT, "", "Segment not in IDoc definition"
+              .to_string()));
+            continue;
+          }
+        }
+      };
+      let (rules, nevls) = field_rules(s);
+      for (field, dtext) in nevls {
+        findg.push(find(NOT_EVALUABLE, &field, dtext));
+      }
+      match sgdef {
+        Some(i) if rules.len() > 0 => srule.push(SgrulTp { sgdef: i, rules }),
+        Some(_) => {},
+        None    => crule.extend(rules)
+      }
+    }
+  }
+  let dctrl = v.rcdef.get(CONTROL).cloned().unwrap_or_default();
+  let ddata = v.rcdef.get(DATA).cloned().unwrap_or_default();
+  let lctrl = dctrl.iter().map(|f| f.endps).max().unwrap_or(0);
+  let ldata = ddata.iter().map(|f| f.endps).max().unwrap_or(0);
+  let lnend = line_end(&wkf.lnend);
+  let mut odata = String::new();
+  let mut nidoc = 0;
+  let mut nsegm = 0;
+  for (envlp, msg) in split_messages(&sgmts) {
+    let scope: Vec<&EdisgTp> = envlp.iter().chain(msg.iter()).copied().collect();
+    nidoc += 1;
+    let docnm = format!("{:0>16}", msg[0].value(if dl.stndr == X12 { 2 } else { 1 },
+      0));
+    let mut line = vec![' '; lctrl];
+    let idoct = if idocb.len() > 0 { &idocb } else { &idocn };
+    let mut cvals: Vec<(String, String)> = vec![("TABNAM".to_string(),
+      wkf.cntrl.clone()), ("MANDT".to_string(), wkf.clien.clone()),
+      ("DOCNUM".to_string(), docnm.clone()), ("DIRECT".to_string(),
+      INBOUND.to_string()), ("IDOCTYP".to_string(), idoct.clone())];
+    if idocn != *idoct {
+      cvals.push(("CIMTYP".to_string(), idocn.clone()));
+    }
+    for (field, p) in &crule {
+      if let Some(s) = scope.iter().find(|s| s.tag == p.tag) {
+        cvals.push((field.clone(), s.value(p.elemn, p.compn)));
+      }
+    }
+    for (field, value) in &cvals {
+      put_value(&mut line, &dctrl, field, value).map_err(|rulid| findg.push(
+        AfindTp { rulid, group: CTRL.to_string(), segmn: CONTROL.to_string(),
+        field: field.clone(), dtext: value.clone() })).ok();
+    }
+    odata.push_str(&format!("{}{}", line.iter().collect::<String>(), lnend));
+    let osegm = eval_segments(&srule, &scope);
+    let mut stack: Vec<(usize, usize)> = Vec::new(); // Level, segment number
+    for (n, o) in osegm.iter().enumerate() {
+      let d = &v.sgdef[o.sgdef];
+      let level = if d.level > 0 { d.level } else { 1 };
+      while stack.last().map(|(l, _)| *l >= level).unwrap_or(false) {
+        stack.pop();
+      }
+      let psgnm = stack.last().map(|(_, s)| *s).unwrap_or(0);
+      stack.push((level, n + 1));
+      let mut line = vec![' '; ldata];
+      let dsegm = v.rcdef.get(&d.dname).cloned().unwrap_or_default();
+      for (field, value) in [("SEGNAM", d.dname.clone()), ("MANDT", wkf.clien
+        .clone()), ("DOCNUM", docnm.clone()), ("SEGNUM", format!("{:06}", n + 1)),
+        ("PSGNUM", format!("{:06}", psgnm)), ("HLEVEL", format!("{:02}", level))] {
+        put_value(&mut line, &ddata, field, &value).ok();
+      }
+      for (field, value) in &o.value {
+        if let Err(rulid) = put_value(&mut line, &dsegm, field, value) {
+          let exist = findg.iter().any(|f| f.rulid == rulid && f.segmn == d.dname
+            && f.field == *field);
+          if !exist {
+            findg.push(AfindTp { rulid, segmn: d.dname.clone(), field:
+              field.clone(), dtext: value.clone(), ..Default::default() });
+          }
+        }
+      }
+      odata.push_str(&format!("{}{}", line.iter().collect::<String>(), lnend));
+    }
+    nsegm += osegm.len();
+  }
+  let opath = opath.clone().unwrap_or(format!("{}_idoc.txt", ipath.rsplitn(2, '.')
+    .last().unwrap_or(ipath)));
+  let mut file = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+  file.write_all(odata.as_bytes()).map_err(|e| io_error(e, &opath))?;
+  for f in &findg {
+    println!("{:16}|{:8}|{:12}|{:10}|{}", f.rulid, f.group, f.segmn, f.field,
+      f.dtext.replace('\n', " "));
+  }
+  println!("EDI {} file {}: {} IDocs, {} segments, {} findings", dl.stndr, ipath,
+    nidoc, nsegm, findg.len());
+  return Ok(());
+}
+
+// field_rules.rs - Get evaluable rules of the fields of a spec segment: target
+// field and source path. Fields with a source other than a path or with a rule or
+// condition, which would be applied unconditionally, are given apart as not
+// evaluable, with the text causing it (2021-07-01 bar8tl)
+fn field_rules(s: &SegmentTp) -> (Vec<(String, EpathTp)>, Vec<(String, String)>) {
+  let mut rules: Vec<(String, EpathTp)> = Vec::new();
+  let mut nevls: Vec<(String, String)> = Vec::new();
+  for f in s.fields.iter().filter(|f| f.field.len() > 0 && f.field != EMPTY) {
+    if f.r_cond.len() > 0 {
+      nevls.push((f.field.clone(), f.r_cond.clone()));
+      continue;
+    }
+    match parse_epath(&f.source) {
+      Some(p) => rules.push((f.field.clone(), p)),
+      None if f.source.len() > 0 => nevls.push((f.field.clone(), f.source.clone())),
+      None => {}
+    }
+  }
+  return (rules, nevls);
+}
+
+// parse_epath.rs - Parse a source given as element path: segment tag (2-3 chars),
+// element position (2 digits) and optionally component position after '-', ':'
+// or '.'. Anything else is not an element path; composite element IDs (C040,
+// S009) name no segment and are not taken as paths (2021-07-01 bar8tl)
+pub fn parse_epath(sourc: &String) -> Option<EpathTp> {
+  let s = sourc.trim().to_uppercase();
+  let (main, compn) = match s.split_once(|c| c == '-' || c == ':' || c == '.') {
+    Some((m, c)) => (m.to_string(), c.parse::<usize>().ok().filter(|c| *c > 0)?),
+    None         => (s.clone(), 0)
+  };
+  if main.len() < 4 || main.len() > 5 || !main.is_ascii() {
+    return None;
+  }
+  let (tag, elemn) = main.split_at(main.len() - 2);
+  if !tag.starts_with(|c: char| c.is_ascii_uppercase()) ||
+     !tag.chars().all(|c| c.is_ascii_alphanumeric()) || is_composite(&main) {
+    return None;
+  }
+  let elemn = elemn.parse::<usize>().ok().filter(|e| *e > 0)?;
+  return Some(EpathTp { tag: tag.to_string(), elemn, compn });
+}
+
+// is_composite.rs - Check whether an ID is a composite element ID: C, E or S
+// followed by 3 digits (2021-07-01 bar8tl)
+fn is_composite(id: &String) -> bool {
+  return id.len() == 4 && id.starts_with(['C', 'E', 'S']) &&
+    id[1..].chars().all(|c| c.is_ascii_digit());
+}
+
+// split_messages.rs - Split interchange into messages (ST..SE, UNH..UNT), each
+// with the envelope segments preceding it (2021-07-01 bar8tl)
+fn split_messages(sgmts: &Vec<EdisgTp>) -> Vec<(Vec<&EdisgTp>, Vec<&EdisgTp>)> {
+  let mut msgs: Vec<(Vec<&EdisgTp>, Vec<&EdisgTp>)> = Vec::new();
+  let mut envlp: Vec<&EdisgTp> = Vec::new();
+  let mut msg: Option<Vec<&EdisgTp>> = None;
+  for s in sgmts {
+    match msg.as_mut() {
+      Some(m) => {
+        m.push(s);
+        if MSGTRL.contains(&s.tag.as_str()) {
+          msgs.push((envlp.clone(), msg.take().unwrap()));
+        }
+      },
+      None if MSGHDR.contains(&s.tag.as_str()) => msg = Some(vec![s]),
+      None => envlp.push(s)
+    }
+  }
+  if let Some(m) = msg {
+    msgs.push((envlp, m));
+  }
+  return msgs;
+}
+
+// eval_segments.rs - Evaluate rules of spec segments over the segments of a
+// message, giving IDoc segment instances in EDI order (2021-07-01 bar8tl)
+fn eval_segments(srule: &Vec<SgrulTp>, scope: &Vec<&EdisgTp>) -> Vec<OsegmTp> {
+  let mut osegm: Vec<OsegmTp> = Vec::new();
+  for (order, r) in srule.iter().enumerate() {
+    let drivr = &r.rules[0].1.tag;
+    let occrs: Vec<usize> = (0..scope.len()).filter(|i| scope[*i].tag == *drivr)
+      .collect();
+    for (k, p) in occrs.iter().enumerate() {
+      let q = occrs.get(k + 1).copied().unwrap_or(scope.len());
+      let mut o = OsegmTp { segpo: *p, order, sgdef: r.sgdef, ..Default::default() };
+      for (field, path) in &r.rules {
+        let s = scope[*p..q].iter().find(|s| s.tag == path.tag)
+          .or(scope[..*p].iter().rev().find(|s| s.tag == path.tag));
+        if let Some(s) = s {
+          let value = s.value(path.elemn, path.compn);
+          if value.len() > 0 {
+            o.value.push((field.clone(), value));
+          }
+        }
+      }
+      if o.value.len() > 0 {
+        osegm.push(o);
+      }
+    }
+  }
+  osegm.sort_by_key(|o| (o.segpo, o.order));
+  return osegm;
+}
+
+// put_value.rs - Place field value in its positions of a fixed-size record. Gives
+// the finding when the field is not defined or the value does not fit
+// (2021-07-01 bar8tl)
+fn put_value(line: &mut Vec<char>, fdefs: &Vec<FldefTp>, field: &str, value: &String)
+  -> std::result::Result<(), String> {
+  let d = fdefs.iter().find(|d| d.dname == field)
+    .ok_or(UNKNOWN_FIELD.to_string())?;
+  let width = d.endps + 1 - d.strps;
+  for (k, c) in value.chars().take(width).enumerate() {
+    if d.strps + k <= line.len() {
+      line[d.strps - 1 + k] = c;
+    }
+  }
+  if value.chars().count() > width {
+    return Err(VALUE_TRUNCATED.to_string());
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::maps::tojson::FieldTp;
+
+  #[test]
+  fn field_rules_leaves_fields_with_rules_out() {
+    let fld = |field: &str, source: &str, r_cond: &str| FieldTp {
+      field: field.to_string(), source: source.to_string(),
+      r_cond: r_cond.to_string(), ..Default::default() };
+    let s = SegmentTp { fields: vec![
+      fld("MATNR", "LIN03", ""),
+      fld("KDMAT", "LIN05", "Only if LIN04 = 'BP'"),
+      fld("MENGE", "", "Sum of SDP quantities"),
+      fld("WERKS", "Constant 1000", ""),
+      fld("POSEX", "", ""),
+      fld(EMPTY, "LIN01", "")], ..Default::default() };
+    let (rules, nevls) = field_rules(&s);
+    let rules: Vec<(&str, &str, usize, usize)> = rules.iter().map(|(f, p)| (
+      f.as_str(), p.tag.as_str(), p.elemn, p.compn)).collect();
+    assert_eq!(rules, vec![("MATNR", "LIN", 3, 0)]);
+    assert_eq!(nevls, vec![
+      ("KDMAT".to_string(), "Only if LIN04 = 'BP'".to_string()),
+      ("MENGE".to_string(), "Sum of SDP quantities".to_string()),
+      ("WERKS".to_string(), "Constant 1000".to_string())]);
+  }
+
+  #[test]
+  fn parse_epath_takes_segment_element_and_component() {
+    let path = |s: &str| parse_epath(&s.to_string()).map(|p| (p.tag, p.elemn,
+      p.compn));
+    let some = |t: &str, e: usize, c: usize| Some((t.to_string(), e, c));
+    assert_eq!(path("LIN03"), some("LIN", 3, 0));
+    assert_eq!(path(" dtm02 "), some("DTM", 2, 0));
+    assert_eq!(path("N104"), some("N1", 4, 0));
+    assert_eq!(path("SDP01:1"), some("SDP", 1, 1));
+    assert_eq!(path("QTY01-2"), some("QTY", 1, 2));
+    assert_eq!(path("TD503.1"), some("TD5", 3, 1));
+    for s in ["C040-2", "C50701-2", "S009", "LIN3", "LIN00", "LIN03-0", "LIN03:X",
+      "1IN03", "LIN_03", "LINE003", "LIN03 or LIN05", "Constant 1000", ""] {
+      assert_eq!(path(s), None, "{}", s);
+    }
+  }
+}
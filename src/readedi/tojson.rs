@@ -0,0 +1,420 @@
+// tojson.rs - Starts proper function to convert EDI X12 or EDIFACT content to a
+// JSON tree of segments: envelopes (interchange, functional group, message) hold
+// their segments, and loops detected within each message hold theirs. Either from
+// a set of files contained within a folder or from an specific single file
+// (2021-07-01 bar8tl)
+use crate::error::{edi_error, io_error, json_error, Result, RunsmTp};
+use crate::readedi::segments::{read_edi_file, DelimTp, EdisgTp, EDIFACT, X12};
+use crate::readidoc::read_idocs::{run_inbatch, StageTp};
+use crate::readidoc::tojson::{FieldTp, OKAY};
+use rblib::files_infolder::FilelistTp;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+
+// symbols.rs - Symbolic constants for EDI file conversion to JSON format
+// (2021-07-01 bar8tl)
+pub const HL    : &str = "HL";         // X12 hierarchical level
+const JSON      : &str = "json";
+const X12ENV    : [(&str, &str); 3] = [("ISA", "IEA"), ("GS", "GE"), ("ST", "SE")];
+const EDFENV    : [(&str, &str); 3] = [("UNB", "UNZ"), ("UNG", "UNE"),
+  ("UNH", "UNT")];
+
+// types.rs - Data structures used in EDI file conversion to JSON format
+// (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EsegmTp {
+  pub segmn: String,         // Segment tag
+  pub segno: usize,          // Position in the interchange
+  pub level: usize,
+  pub loopn: String,         // Loop headed by the segment, if any
+  pub field: Vec<FieldTp>,   // Elements (TAG01) or components (TAG01-1)
+  pub child: Vec<EsegmTp>
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EinchTp {         // EDI file
+  pub flide: String,
+  pub stndr: String,
+  pub delim: DelimTp,
+  pub nsegm: usize,
+  pub sgmts: Vec<EsegmTp>
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LoopsTp {         // Loops detected in a message body
+  pub trigr: HashSet<String>,              // Tags opening a loop
+  pub prnt : HashMap<String, String>,      // Loop tag -> enclosing loop tag
+  pub insid: HashMap<String, HashSet<String>> // Tag -> loops holding it
+}
+
+// x12json_content_inbatch.rs - Start batch process to convert EDI X12 files to
+// JSON format (2021-07-01 bar8tl)
+// Command line: edimaps step x12json <EDI-file>
+pub fn x12json_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) ->
+  Result<RunsmTp> {
+  return run_inbatch(dbopt, &st, idoct, x12json_content_onefile);
+}
+
+pub fn x12json_content_onefile(_cnn: &Connection, st: &StageTp, fl: &FilelistTp)
+  -> Result<String> {
+  return edijson_content_onefile(st, fl, X12);
+}
+
+// edfjson_content_inbatch.rs - Start batch process to convert EDIFACT files to
+// JSON format (2021-07-01 bar8tl)
+// Command line: edimaps step edfjson <EDI-file>
+pub fn edfjson_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) ->
+  Result<RunsmTp> {
+  return run_inbatch(dbopt, &st, idoct, edfjson_content_onefile);
+}
+
+pub fn edfjson_content_onefile(_cnn: &Connection, st: &StageTp, fl: &FilelistTp)
+  -> Result<String> {
+  return edijson_content_onefile(st, fl, EDIFACT);
+}
+
+// edijson_content_onefile.rs - Convert individual EDI file of the standard
+// expected into JSON tree, written into file <outdr><edi-file>.<outtp>
+// (2021-07-01 bar8tl)
+pub fn edijson_content_onefile(st: &StageTp, fl: &FilelistTp, stndr: &str) ->
+  Result<String> {
+  let (dl, sgmts) = read_edi_file(&fl.flpth, &st.wfhdr)?;
+  if dl.stndr != stndr {
+    return Err(edi_error(1, "", format!("{} interchange found, {} expected",
+      dl.stndr, stndr)).at(&fl.flpth, 0));
+  }
+  let ei = EinchTp { flide: fl.flide.clone(), stndr: dl.stndr.clone(),
+    delim: dl, nsegm: sgmts.len(), sgmts: edi_tree(&sgmts, stndr) };
+  let outtp = if st.wfstp.outtp.len() > 0 { st.wfstp.outtp.as_str() } else { JSON };
+  let opath = format!("{}{}.{}", st.wfstp.outdr, fl.flnam, outtp);
+  let mut file = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+  let fdata = serde_json::to_string_pretty(&ei).map_err(|e| json_error(e, &opath))?;
+  file.write_all(fdata.as_bytes()).map_err(|e| io_error(e, &opath))?;
+  return Ok(OKAY.to_string());
+}
+
+// edi_tree.rs - Build tree of segments: envelope headers open a node closed by
+// their trailer; the body of each message is arranged in loops
+// (2021-07-01 bar8tl)
+pub fn edi_tree(sgmts: &Vec<EdisgTp>, stndr: &str) -> Vec<EsegmTp> {
+  let envlp = if stndr == X12 { X12ENV } else { EDFENV };
+  let (msghd, msgtr) = envlp[2];
+  let mut stack: Vec<(String, EsegmTp)> = vec![(String::new(),
+    EsegmTp { ..Default::default() })];
+  let mut i = 0;
+  while i < sgmts.len() {
+    let s = &sgmts[i];
+    if s.tag == msghd {
+      let j = (i+1..sgmts.len()).find(|j| sgmts[*j].tag == msgtr)
+        .unwrap_or(sgmts.len());
+      let body: Vec<&EdisgTp> = sgmts[i+1..j].iter().collect();
+      let mut m = edi_segment(s);
+      m.child = loop_tree(&body, &find_loops(&body, stndr), stndr);
+      if j < sgmts.len() {
+        m.child.push(edi_segment(&sgmts[j]));
+      }
+      stack.last_mut().unwrap().1.child.push(m);
+      i = j + 1;
+      continue;
+    }
+    if envlp.iter().any(|(h, _)| *h == s.tag) {
+      stack.push((s.tag.clone(), edi_segment(s)));
+    } else if let Some((h, _)) = envlp.iter().find(|(_, t)| *t == s.tag) {
+      stack.last_mut().unwrap().1.child.push(edi_segment(s));
+      if stack.len() > 1 && stack.last().unwrap().0 == *h {
+        let (_, n) = stack.pop().unwrap();
+        stack.last_mut().unwrap().1.child.push(n);
+      }
+    } else {
+      stack.last_mut().unwrap().1.child.push(edi_segment(s));
+    }
+    i += 1;
+  }
+  while stack.len() > 1 {
+    let (_, n) = stack.pop().unwrap();
+    stack.last_mut().unwrap().1.child.push(n);
+  }
+  let mut sgmts = stack.pop().unwrap().1.child;
+  sgmts.iter_mut().for_each(|n| set_levels(n, 1));
+  return sgmts;
+}
+
+// find_loops.rs - Detect loops of a message body. A tag repeating with other
+// segments between its occurrences opens a loop (HL always does), unless it
+// occurs once per instance of a loop already opened, as its member; each
+// occurrence up to the next one is an instance. A loop is nested into the latest
+// opened loop whose instances its occurrences spread over. Other tags belong to
+// the loops having them in some instance but the last one, which may run into
+// the summary segments.
+// A tag found in the message header and again within the loops (as DTM in
+// EDIFACT messages) would open the first loop: its first occurrence is taken as
+// header segment when, without it, the tag is no loop and repeats within an
+// instance of the first loop left (2021-07-01 bar8tl)
+pub fn find_loops(body: &Vec<&EdisgTp>, stndr: &str) -> LoopsTp {
+  let mut hdrps: HashSet<usize> = HashSet::new();
+  let mut lp = detect_loops(body, stndr, &hdrps);
+  loop {
+    let first = |lp: &LoopsTp, hdrps: &HashSet<usize>| body.iter().enumerate()
+      .find(|(i, s)| !hdrps.contains(i) && lp.trigr.contains(&s.tag))
+      .map(|(i, s)| (i, s.tag.clone()));
+    let (p, t) = match first(&lp, &hdrps) {
+      Some((p, t)) if !(stndr == X12 && t == HL) => (p, t),
+      _ => break
+    };
+    let mut hdrnw = hdrps.clone();
+    hdrnw.insert(p);
+    let lpnw = detect_loops(body, stndr, &hdrnw);
+    let (q, u) = match first(&lpnw, &hdrnw) {
+      Some(f) if !lpnw.trigr.contains(&t) => f,
+      _ => break
+    };
+    let insts: Vec<usize> = body.iter().enumerate().skip(q)
+      .filter(|(i, s)| !hdrnw.contains(i) && s.tag == u).map(|(i, _)| i).collect();
+    let inrep = body.iter().enumerate().skip(q)
+      .filter(|(i, s)| !hdrnw.contains(i) && s.tag == t)
+      .map(|(i, _)| insts.iter().rposition(|o| *o <= i))
+      .collect::<Vec<_>>().windows(2).any(|w| w[0] == w[1]);
+    if !inrep {
+      break;
+    }
+    hdrps = hdrnw;
+    lp = lpnw;
+  }
+  return lp;
+}
+
+// detect_loops.rs - Detect loops of a message body, leaving the given header
+// occurrences out (2021-07-01 bar8tl)
+fn detect_loops(body: &Vec<&EdisgTp>, stndr: &str, hdrps: &HashSet<usize>) ->
+  LoopsTp {
+  let mut lp = LoopsTp { ..Default::default() };
+  let mut occrs: HashMap<&str, Vec<usize>> = HashMap::new();
+  let mut order: Vec<&str> = Vec::new();
+  for (i, s) in body.iter().enumerate().filter(|(i, _)| !hdrps.contains(i)) {
+    let o = occrs.entry(s.tag.as_str()).or_insert(Vec::new());
+    if o.len() == 0 {
+      order.push(s.tag.as_str());
+    }
+    o.push(i);
+  }
+  let instance = |t: &str, i: usize| -> Option<usize> {
+    occrs[t].iter().rposition(|p| *p <= i)
+  };
+  for t in &order {
+    let o = &occrs[t];
+    if !(stndr == X12 && *t == HL) && !o.windows(2).any(|w| w[1] > w[0] + 1) {
+      continue;
+    }
+    let membr = order.iter().take_while(|u| *u != t).any(|u| lp.trigr.contains(*u)
+      && {
+        let insts: Vec<Option<usize>> = o.iter().map(|i| instance(u, *i)).collect();
+        !insts.contains(&None) && insts.windows(2).all(|w| w[0] < w[1])
+      });
+    if (stndr == X12 && *t == HL) || !membr {
+      lp.trigr.insert(t.to_string());
+    }
+  }
+  for u in order.iter().filter(|u| lp.trigr.contains(**u) && **u != HL) {
+    let prnt = order.iter().take_while(|t| *t != u).filter(|t| lp.trigr
+      .contains(**t) && occrs[**t].len() > 1).filter(|t| {
+        let insts: HashSet<Option<usize>> = occrs[u].iter().map(|i| instance(t, *i))
+          .collect();
+        !insts.contains(&None) && insts.len() > 1
+      }).last();
+    if let Some(t) = prnt {
+      lp.prnt.insert(u.to_string(), t.to_string());
+    }
+  }
+  for t in lp.trigr.iter() {
+    let o = &occrs[t.as_str()];
+    for w in o.windows(2) {
+      for s in &body[w[0]+1..w[1]] {
+        lp.insid.entry(s.tag.clone()).or_insert(HashSet::new()).insert(t.clone());
+      }
+    }
+  }
+  return lp;
+}
+
+// loop_tree.rs - Arrange segments of a message body in the loops detected. HL
+// loops nest under the HL their parent ID (HL02) refers to (2021-07-01 bar8tl)
+fn loop_tree(body: &Vec<&EdisgTp>, lp: &LoopsTp, stndr: &str) -> Vec<EsegmTp> {
+  let mut tree: Vec<EsegmTp> = Vec::new();
+  let mut stack: Vec<(String, String, EsegmTp)> = Vec::new(); // Tag, HL ID, node
+  let close = |stack: &mut Vec<(String, String, EsegmTp)>, tree: &mut Vec<EsegmTp>|
+  {
+    let (_, _, n) = stack.pop().unwrap();
+    match stack.last_mut() {
+      Some(p) => p.2.child.push(n),
+      None    => tree.push(n)
+    }
+  };
+  for s in body {
+    if stndr == X12 && s.tag == HL && lp.trigr.contains(HL) {
+      let prnid = s.value(2, 0);
+      let found = stack.iter().any(|(t, h, _)| t == HL && *h == prnid);
+      while stack.len() > 0 && !(found && stack.last().unwrap().1 == prnid &&
+        stack.last().unwrap().0 == HL) {
+        close(&mut stack, &mut tree);
+      }
+      let mut n = edi_segment(s);
+      n.loopn = HL.to_string();
+      stack.push((HL.to_string(), s.value(1, 0), n));
+    } else if lp.trigr.contains(&s.tag) {
+      let prnt = lp.prnt.get(&s.tag);
+      while stack.len() > 0 && Some(&stack.last().unwrap().0) != prnt {
+        close(&mut stack, &mut tree);
+      }
+      let mut n = edi_segment(s);
+      n.loopn = s.tag.clone();
+      stack.push((s.tag.clone(), String::new(), n));
+    } else {
+      let insid = lp.insid.get(&s.tag);
+      while stack.len() > 0 && !insid.map(|t| t.contains(&stack.last().unwrap().0))
+        .unwrap_or(false) {
+        close(&mut stack, &mut tree);
+      }
+      match stack.last_mut() {
+        Some(p) => p.2.child.push(edi_segment(s)),
+        None    => tree.push(edi_segment(s))
+      }
+    }
+  }
+  while stack.len() > 0 {
+    close(&mut stack, &mut tree);
+  }
+  return tree;
+}
+
+// edi_segment.rs - Build tree node of a segment, with its elements, or its
+// components when an element is composite. Empty values are omitted
+// (2021-07-01 bar8tl)
+fn edi_segment(s: &EdisgTp) -> EsegmTp {
+  let mut n = EsegmTp { segmn: s.tag.clone(), segno: s.segno, ..Default::default() };
+  for (i, e) in s.elems.iter().enumerate() {
+    if e.len() == 1 {
+      if e[0].len() > 0 {
+        n.field.push(FieldTp { key: format!("{}{:02}", s.tag, i + 1),
+          val: e[0].clone(), ..Default::default() });
+      }
+      continue;
+    }
+    for (k, c) in e.iter().enumerate().filter(|(_, c)| c.len() > 0) {
+      n.field.push(FieldTp { key: format!("{}{:02}-{}", s.tag, i + 1, k + 1),
+        val: c.clone(), ..Default::default() });
+    }
+  }
+  return n;
+}
+
+fn set_levels(n: &mut EsegmTp, level: usize) {
+  n.level = level;
+  n.child.iter_mut().for_each(|c| set_levels(c, level + 1));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::WkflowTp;
+  use crate::readedi::samples::{DELFOR, X12830, X12856};
+  use crate::readedi::segments::split_segments;
+
+  // Segments of the message body, between message header and trailer
+  fn message_body(text: &str) -> (String, Vec<EdisgTp>) {
+    let wkf = WkflowTp { ..Default::default() };
+    let (dl, sgmts) = split_segments(text, &wkf).unwrap();
+    let (msghd, msgtr) = if dl.stndr == X12 { ("ST", "SE") } else { ("UNH", "UNT") };
+    let i = sgmts.iter().position(|s| s.tag == msghd).unwrap();
+    let j = sgmts.iter().position(|s| s.tag == msgtr).unwrap();
+    return (dl.stndr, sgmts[i+1..j].to_vec());
+  }
+
+  // Tags of a tree of segments, indented by nesting, loop heads marked with '*'
+  fn outline(nodes: &Vec<EsegmTp>, depth: usize, lines: &mut Vec<String>) {
+    for n in nodes {
+      let mark = if n.loopn.len() > 0 { "*" } else { "" };
+      lines.push(format!("{}{}{}", "  ".repeat(depth), n.segmn, mark));
+      outline(&n.child, depth + 1, lines);
+    }
+  }
+
+  fn body_outline(text: &str) -> Vec<String> {
+    let (stndr, sgmts) = message_body(text);
+    let body: Vec<&EdisgTp> = sgmts.iter().collect();
+    let mut lines: Vec<String> = Vec::new();
+    outline(&loop_tree(&body, &find_loops(&body, &stndr), &stndr), 0, &mut lines);
+    return lines;
+  }
+
+  fn tags(v: &[&str]) -> HashSet<String> {
+    return v.iter().map(|t| t.to_string()).collect();
+  }
+
+  #[test]
+  fn find_loops_takes_repeated_segments_as_loops() {
+    let (stndr, sgmts) = message_body(X12830);
+    let lp = find_loops(&sgmts.iter().collect(), &stndr);
+    assert_eq!(lp.trigr, tags(&["N1", "LIN", "FST"]));
+    assert_eq!(lp.prnt.get("FST"), Some(&"LIN".to_string()));
+    assert_eq!(lp.prnt.get("LIN"), None);
+    assert_eq!(lp.insid.get("N3"), Some(&tags(&["N1"])));
+    assert_eq!(lp.insid.get("BFR"), None);
+    assert_eq!(lp.insid.get("CTT"), None);
+  }
+
+  #[test]
+  fn loop_tree_nests_repeated_segment_loops() {
+    assert_eq!(body_outline(X12830), vec!["BFR", "N1*", "  N3", "N1*", "  N3",
+      "LIN*", "  UIT", "  FST*", "  FST*", "LIN*", "  UIT", "  FST*", "CTT"]);
+  }
+
+  #[test]
+  fn loop_tree_nests_hierarchical_levels_by_parent() {
+    assert_eq!(body_outline(X12856), vec!["BSN",
+      "HL*", "  TD1",
+      "  HL*", "    PRF",
+      "    HL*", "      LIN", "      SN1",
+      "    HL*", "      LIN", "      SN1",
+      "  HL*", "    PRF",
+      "    HL*", "      LIN", "      SN1",
+      "CTT"]);
+  }
+
+  #[test]
+  fn find_loops_keeps_header_segment_repeated_in_loops() {
+    let (stndr, sgmts) = message_body(DELFOR);
+    let lp = find_loops(&sgmts.iter().collect(), &stndr);
+    assert_eq!(lp.trigr, tags(&["LIN", "QTY"]));
+    assert_eq!(lp.prnt.get("QTY"), Some(&"LIN".to_string()));
+    assert_eq!(body_outline(DELFOR), vec!["BGM", "DTM", "NAD", "NAD",
+      "LIN*", "  PIA", "  QTY*", "    SCC", "    DTM", "  QTY*", "    SCC",
+      "    DTM",
+      "LIN*", "  PIA", "  QTY*", "    SCC", "    DTM"]);
+  }
+
+  #[test]
+  fn edi_tree_wraps_messages_in_envelopes() {
+    let wkf = WkflowTp { ..Default::default() };
+    let (dl, sgmts) = split_segments(DELFOR, &wkf).unwrap();
+    let tree = edi_tree(&sgmts, &dl.stndr);
+    assert_eq!(tree.len(), 1);
+    let unb = &tree[0];
+    assert_eq!((unb.segmn.as_str(), unb.level), ("UNB", 1));
+    let inner: Vec<&str> = unb.child.iter().map(|n| n.segmn.as_str()).collect();
+    assert_eq!(inner, vec!["UNH", "UNZ"]);
+    let unh = &unb.child[0];
+    assert_eq!(unh.level, 2);
+    assert_eq!(unh.child.first().unwrap().segmn, "BGM");
+    assert_eq!(unh.child.last().unwrap().segmn, "UNT");
+    let nad = &unh.child[3];
+    assert_eq!(nad.field[1].key, "NAD02");
+    assert_eq!(nad.field[1].val, "SELLER*S >AG");
+    let lin = &unh.child[4];
+    assert_eq!((lin.level, lin.child[1].level), (3, 4));
+    assert_eq!(lin.field[0].key, "LIN03-1");
+    assert_eq!(lin.field[0].val, "P-100");
+  }
+}
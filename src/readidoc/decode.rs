@@ -0,0 +1,83 @@
+// decode.rs - Annotate coded IDoc field values with their descriptions. Fields to
+// be decoded and the code table of each are taken from the repository table
+// cdfield; code descriptions from the SAP code tables (cdcodes) and from the EDI
+// code equivalences (cddata) (2021-07-01 bar8tl)
+use crate::error::{repo_error, Result};
+use crate::readidoc::tojson::{FieldTp, RsegmTp, CONTROL};
+use rusqlite::Connection;
+use std::collections::HashMap;
+
+// types.rs - Data structures used to decode IDoc field values (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default)]
+pub struct CdfldTp {         // Coded field
+  pub segmn: String,         // Segment type (prefix), empty for any segment
+  pub dname: String,         // Field name
+  pub tabid: String          // Code table
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DecodTp {
+  pub cdfld: Vec<CdfldTp>,
+  pub texts: HashMap<(String, String), String> // (code table, code) -> text
+}
+
+impl DecodTp {
+  // describe - Description of the value of a field in a segment, if the field is
+  // coded and the code is known. The most specific segment entry applies
+  pub fn describe(&self, segmn: &str, dname: &str, val: &str) -> Option<String> {
+    let c = self.cdfld.iter().filter(|c| c.dname == dname &&
+      segmn.starts_with(&c.segmn)).max_by_key(|c| c.segmn.len())?;
+    return self.texts.get(&(c.tabid.clone(), val.to_string())).cloned();
+  }
+
+  // annotate - Set description of the coded fields of a segment
+  pub fn annotate(&self, segmn: &str, field: &mut Vec<FieldTp>) {
+    for f in field.iter_mut() {
+      if let Some(t) = self.describe(segmn, &f.key, &f.val) {
+        f.dtext = t;
+      }
+    }
+  }
+
+  // annotate_tree - Set description of the coded fields of a segment and of all
+  // its descendants
+  pub fn annotate_tree(&self, rsegm: &mut RsegmTp) {
+    self.annotate(&rsegm.segmn, &mut rsegm.field);
+    for c in rsegm.child.iter_mut() {
+      self.annotate_tree(c);
+    }
+  }
+
+  // annotate_cntrl - Set description of the coded fields of a control record
+  pub fn annotate_cntrl(&self, field: &mut Vec<FieldTp>) {
+    self.annotate(CONTROL, field);
+  }
+}
+
+// get_decoder.rs - Load coded fields and the texts of their code tables. With no
+// coded fields in the repository (or a repository created before table cdfield)
+// nothing is decoded (2021-07-01 bar8tl)
+pub fn get_decoder(cnn: &Connection) -> Result<DecodTp> {
+  let mut dc: DecodTp = Default::default();
+  let mut stmt = match cnn.prepare("SELECT segmn, dname, tabid FROM cdfield;") {
+    Ok(s)  => s,
+    Err(e) if e.to_string().contains("no such table") => return Ok(dc),
+    Err(e) => return Err(repo_error(e, "cdfield"))
+  };
+  let mut rows = stmt.query([]).map_err(|e| repo_error(e, "cdfield"))?;
+  while let Some(row) = rows.next().map_err(|e| repo_error(e, "cdfield"))? {
+    dc.cdfld.push(CdfldTp { segmn: row.get(0)?, dname: row.get(1)?,
+      tabid: row.get(2)? });
+  }
+  if dc.cdfld.len() == 0 {
+    return Ok(dc);
+  }
+  let mut stmt = cnn.prepare("SELECT tabid, cdkey, cdtext FROM cdcodes UNION ALL
+    SELECT tabid, cdkey, CASE WHEN relshp2 <> '' THEN relshp2 ELSE relshp1 END
+    FROM cddata;").map_err(|e| repo_error(e, "cdcodes"))?;
+  let mut rows = stmt.query([]).map_err(|e| repo_error(e, "cdcodes"))?;
+  while let Some(row) = rows.next().map_err(|e| repo_error(e, "cdcodes"))? {
+    dc.texts.entry((row.get(0)?, row.get(1)?)).or_insert(row.get(2)?);
+  }
+  return Ok(dc);
+}
@@ -1,5 +1,15 @@
 // mod.rs - Function modules used for SAP IDoc content processing (2021-07-01 bar8tl)
+pub mod decode;
+pub mod pipeline;
 pub mod read_idocs;
 mod runquery;
+#[cfg(test)]
+pub mod samples;
+mod strtojson;
 mod tofixdsz;
-mod tojson;
+pub mod tojson;
+mod toxml;
+mod unjson;
+mod unxml;
+pub mod watch;
+pub mod validate;
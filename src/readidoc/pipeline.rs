@@ -0,0 +1,319 @@
+// pipeline.rs - Run workflow pipelines: the steps of a pipeline run in chain for
+// each file of the input folder of its first step, the output files of a step
+// being the input of the next one. The chain of a file stops on its first failure
+// and the status of each stage is kept in the repository table pipsts
+// (2021-07-01 bar8tl)
+use crate::config::WkflowTp;
+use crate::error::{repo_error, ErrorTp, Result, RunsmTp};
+use crate::readidoc::read_idocs::{get_onefile, get_stage, next_stage,
+  single_filelist, tally_file, OnefileFn, StageTp, OKAY};
+use crate::reposit::add_table;
+use chrono::Local;
+use rblib::files_infolder::{files_infolder, FilelistTp};
+use rblib::pass_filter::pass_filter;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs::metadata;
+use std::time::SystemTime;
+
+// symbols.rs - Constants used to run pipelines (2021-07-01 bar8tl)
+pub const PIPELN : &str = "pipeln";
+pub const PIPSTS : &str = "pipsts";
+pub const SOKAY  : &str = "okay";      // Stage status
+pub const SRJCT  : &str = "rejected";
+pub const SFAIL  : &str = "failed";
+pub const SSKIP  : &str = "skipped";
+
+// types.rs - Data types of pipelines (2021-07-01 bar8tl)
+#[derive(Clone)]
+pub struct PipstTp {         // Pipeline step
+  pub seqno: usize,
+  pub ifilt: String,         // File name filter, empty for all files
+  pub stage: StageTp,
+  pub onefl: OnefileFn
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PiprnTp {         // Pipeline run
+  pub runid: String,
+  pub pipnm: String,
+  pub srcfl: String,         // File the chain started from
+  pub done : Vec<(usize, FilelistTp)> // Stages concluded okay: step, input file
+}
+
+// run_pipeline.rs - Run a pipeline over all files of the input folder of its first
+// step, or over a single file of it; list the pipelines when no name is given.
+// Workflow file handling of each step (wkflw = yes) is done once the chain of the
+// file has concluded okay (2021-07-01 bar8tl)
+// Command line: edimaps run [<pipeline>] [<file>]
+pub fn run_pipeline(dbopt: &String, wkflow: &WkflowTp, pipnm: &Option<String>,
+  file: &Option<String>) -> Result<RunsmTp> {
+  let cnn = Connection::open(dbopt)?;
+  let pipnm = match pipnm {
+    Some(p) => p,
+    None    => {
+      list_pipelines(&cnn)?;
+      return Ok(Default::default());
+    }
+  };
+  let steps = get_pipeline(&cnn, wkflow, pipnm)?;
+  add_table(&cnn, PIPSTS)?;
+  let first = &steps[0].stage.wfstp;
+  let flist: Vec<FilelistTp> = match file {
+    Some(f) => vec![single_filelist(&first.inpdr, f)?],
+    None    => files_infolder(&first.inpdr, &first.inptp, &String::new())
+  };
+  let mut rs: RunsmTp = Default::default();
+  let runid = Local::now().format("%Y%m%d-%H%M%S%.3f").to_string();
+  for fl in &flist {
+    let mut pr = PiprnTp { runid: runid.clone(), pipnm: pipnm.clone(),
+      srcfl: fl.flide.clone(), done: Vec::new() };
+    let rslt = run_source(&cnn, &steps, fl, &mut pr);
+    tally_file(&mut rs, fl, rslt);
+  }
+  println!("Pipeline {} run {}", pipnm, runid);
+  return Ok(rs);
+}
+
+// run_source.rs - Run the chain of a file of the input folder of the first step.
+// Once the chain has concluded okay, do the workflow file handling of the steps
+// it went through (2021-07-01 bar8tl)
+pub fn run_source(cnn: &Connection, steps: &Vec<PipstTp>, fl: &FilelistTp,
+  pr: &mut PiprnTp) -> Result<String> {
+  let rtncd = run_chain(cnn, steps, 0, fl, pr)?;
+  if rtncd == OKAY {
+    for (i, f) in &pr.done {
+      if steps[*i].stage.wfstp.wkflw == "yes" {
+        next_stage(&rtncd, &steps[*i].stage, f);
+      }
+    }
+  }
+  return Ok(rtncd);
+}
+
+// run_chain.rs - Run a step of the pipeline on a file, then the rest of the
+// chain on each of its output files. A file not passing the filter of the step
+// ends its chain (2021-07-01 bar8tl)
+pub fn run_chain(cnn: &Connection, steps: &Vec<PipstTp>, i: usize,
+  fl: &FilelistTp, pr: &mut PiprnTp) -> Result<String> {
+  let p = &steps[i];
+  if p.ifilt.len() > 0 && !pass_filter(&p.ifilt, &fl.flnam) {
+    write_status(cnn, pr, p, fl, SSKIP, &format!("Filter {}", p.ifilt))?;
+    return Ok(OKAY.to_string());
+  }
+  let befor = if i + 1 < steps.len() { stage_files(&steps[i+1].stage, fl) } else {
+    HashMap::new() };
+  let rslt = (p.onefl)(cnn, &p.stage, fl);
+  match &rslt {
+    Ok(rtncd) if rtncd == OKAY => write_status(cnn, pr, p, fl, SOKAY, "")?,
+    Ok(rtncd) => write_status(cnn, pr, p, fl, SRJCT, &format!("Return code {}",
+      rtncd))?,
+    Err(e)    => write_status(cnn, pr, p, fl, SFAIL, &e.to_string())?
+  }
+  let rtncd = rslt?;
+  if rtncd != OKAY {
+    return Ok(rtncd);
+  }
+  pr.done.push((i, fl.clone()));
+  if i + 1 == steps.len() {
+    return Ok(rtncd);
+  }
+  let outs = stage_outputs(&steps[i+1].stage, fl, &befor);
+  if outs.len() == 0 {
+    return Err(ErrorTp::Config { file: fl.flide.clone(), detail: format!(
+      "No output of step {} found as input of step {} ({}*.{})",
+      p.stage.wfstp.step, steps[i+1].stage.wfstp.step, steps[i+1].stage.wfstp
+      .inpdr, steps[i+1].stage.wfstp.inptp) });
+  }
+  for o in &outs {
+    let rtncd = run_chain(cnn, steps, i + 1, o, pr)?;
+    if rtncd != OKAY {
+      return Ok(rtncd);
+    }
+  }
+  return Ok(OKAY.to_string());
+}
+
+// stage_outputs.rs - Files written by a step for an input file, found in the
+// input folder of the next step: those of its input type, named after the input
+// file, either new or changed since before the step (2021-07-01 bar8tl)
+pub fn stage_outputs(next: &StageTp, fl: &FilelistTp,
+  befor: &HashMap<String, SystemTime>) -> Vec<FilelistTp> {
+  let after = stage_files(next, fl);
+  let mut outs: Vec<FilelistTp> = files_infolder(&next.wfstp.inpdr,
+    &next.wfstp.inptp, &fl.flnam);
+  outs.retain(|o| o.flpth != fl.flpth &&
+    after.get(&o.flide) != befor.get(&o.flide));
+  return outs;
+}
+
+// stage_files.rs - Files of the input folder of a step named after a file, with
+// their modification time (2021-07-01 bar8tl)
+pub fn stage_files(next: &StageTp, fl: &FilelistTp) ->
+  HashMap<String, SystemTime> {
+  return files_infolder(&next.wfstp.inpdr, &next.wfstp.inptp, &fl.flnam).iter()
+    .filter_map(|f| metadata(&f.flpth).and_then(|m| m.modified()).ok()
+    .map(|t| (f.flide.clone(), t))).collect();
+}
+
+// get_pipeline.rs - Get steps of a pipeline, in sequence, with the settings of
+// each step (2021-07-01 bar8tl)
+pub fn get_pipeline(cnn: &Connection, wkflow: &WkflowTp, pipnm: &String) ->
+  Result<Vec<PipstTp>> {
+  let mut stmt = cnn.prepare("SELECT seqno, step, ifilt FROM pipeln WHERE pipnm=?1
+    ORDER BY seqno;").map_err(|e| repo_error(e, PIPELN))?;
+  let rows: Vec<(usize, String, String)> = stmt.query_map([pipnm], |row|
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?))).map_err(|e| repo_error(e, PIPELN))?
+    .collect::<rusqlite::Result<_>>().map_err(|e| repo_error(e, PIPELN))?;
+  if rows.len() == 0 {
+    return Err(ErrorTp::Repository { table: PIPELN.to_string(), detail: format!(
+      "Pipeline {} not found in repository", pipnm) });
+  }
+  let mut steps: Vec<PipstTp> = Vec::with_capacity(rows.len());
+  for (seqno, step, ifilt) in rows {
+    steps.push(PipstTp { seqno, ifilt, stage: get_stage(cnn, &step, wkflow)?,
+      onefl: get_onefile(&step)? });
+  }
+  return Ok(steps);
+}
+
+// list_pipelines.rs - List pipelines with their steps (2021-07-01 bar8tl)
+pub fn list_pipelines(cnn: &Connection) -> Result<()> {
+  let mut stmt = cnn.prepare("SELECT pipnm, group_concat(step, ' > ') FROM (SELECT
+    * FROM pipeln ORDER BY pipnm, seqno) GROUP BY pipnm;")
+    .map_err(|e| repo_error(e, PIPELN))?;
+  let mut rows = stmt.query([]).map_err(|e| repo_error(e, PIPELN))?;
+  while let Some(row) = rows.next().map_err(|e| repo_error(e, PIPELN))? {
+    let (pipnm, steps): (String, String) = (row.get(0)?, row.get(1)?);
+    println!("{:12}|{}", pipnm, steps);
+  }
+  return Ok(());
+}
+
+// write_status.rs - Keep status of a stage of a file in a pipeline run
+// (2021-07-01 bar8tl)
+pub fn write_status(cnn: &Connection, pr: &PiprnTp, p: &PipstTp, fl: &FilelistTp,
+  stats: &str, detail: &str) -> Result<()> {
+  cnn.execute("INSERT OR REPLACE INTO pipsts VALUES (?1,?2,?3,?4,?5,?6,?7,?8);",
+    (&pr.runid, &pr.pipnm, &pr.srcfl, p.seqno, &p.stage.wfstp.step, &fl.flide,
+    stats, detail)).map_err(|e| repo_error(e, PIPSTS))?;
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readidoc::samples::{sample_file, sample_repo, sample_stage,
+    work_folder, STRUC};
+  use crate::readidoc::tojson::RC01;
+  use std::fs::{create_dir_all, write};
+  use std::path::Path;
+
+  const SRCFL: &str = "i2xr4_IDOC0000002382843157.txt";
+
+  // sample_pipeline.rs - Repository with pipeline 'tojson' of steps fixed and
+  // json over work folders, the first step concluding its files into 'pcd', and
+  // the structured sample file in the input folder of the first step
+  fn sample_pipeline(wrkdr: &String) -> (Connection, Vec<PipstTp>, FilelistTp) {
+    let cnn = Connection::open(sample_repo(wrkdr)).unwrap();
+    for table in ["wkflow", PIPELN, PIPSTS] {
+      add_table(&cnn, table).unwrap();
+    }
+    for dir in ["inp", "fix", "jsn", "pcd"] {
+      create_dir_all(format!("{}{}", wrkdr, dir)).unwrap();
+    }
+    for (step, inpdr, outdr, outtp, wkflw) in [("fixed", "inp", "fix", "txt", "yes"),
+      ("json", "fix", "jsn", "json", "no")] {
+      cnn.execute("INSERT INTO wkflow VALUES (?1,?2,'txt',?3,?4,'','',?5,?6,'');",
+        [step, &format!("{}{}/", wrkdr, inpdr), &format!("{}{}/", wrkdr, outdr),
+        outtp, wkflw, &format!("{}pcd/", wrkdr)]).unwrap();
+    }
+    cnn.execute("INSERT INTO pipeln VALUES ('tojson',1,'fixed',''),
+      ('tojson',2,'json','');", ()).unwrap();
+    let wfhdr = sample_stage(wrkdr, wrkdr).wfhdr;
+    let steps = get_pipeline(&cnn, &wfhdr, &"tojson".to_string()).unwrap();
+    let fl = sample_file(STRUC, &format!("{}inp/", wrkdr));
+    return (cnn, steps, fl);
+  }
+
+  // run.rs - Pipeline run of the sample file
+  fn run(runid: &str) -> PiprnTp {
+    return PiprnTp { runid: runid.to_string(), pipnm: "tojson".to_string(),
+      srcfl: SRCFL.to_string(), done: Vec::new() };
+  }
+
+  // statuses.rs - Status rows of a pipeline run: step, file and status
+  fn statuses(cnn: &Connection, runid: &str) -> Vec<(String, String, String)> {
+    let mut stmt = cnn.prepare("SELECT step, flide, stats FROM pipsts
+      WHERE runid=?1 ORDER BY seqno;").unwrap();
+    return stmt.query_map([runid], |row| Ok((row.get(0)?, row.get(1)?,
+      row.get(2)?))).unwrap().collect::<rusqlite::Result<_>>().unwrap();
+  }
+
+  // row.rs - Status row of a step on the sample file
+  fn row(step: &str, stats: &str) -> (String, String, String) {
+    return (step.to_string(), SRCFL.to_string(), stats.to_string());
+  }
+
+  // rejects.rs - Step rejecting its file, as a validation finding errors
+  fn rejects(_cnn: &Connection, _st: &StageTp, _fl: &FilelistTp) ->
+    Result<String> {
+    return Ok(RC01.to_string());
+  }
+
+  // writes_nothing.rs - Step concluding okay with no output file
+  fn writes_nothing(_cnn: &Connection, _st: &StageTp, _fl: &FilelistTp) ->
+    Result<String> {
+    return Ok(OKAY.to_string());
+  }
+
+  #[test]
+  fn run_source_chains_steps_and_concludes_them_after_the_chain() {
+    let wrkdr = work_folder("pipeline-chain");
+    let (cnn, steps, fl) = sample_pipeline(&wrkdr);
+    let mut pr = run("r1");
+    assert_eq!(run_source(&cnn, &steps, &fl, &mut pr).unwrap(), OKAY);
+    assert_eq!(statuses(&cnn, "r1"), vec![row("fixed", SOKAY), row("json", SOKAY)]);
+    let done: Vec<(usize, String)> = pr.done.iter().map(|(i, f)| (*i,
+      f.flpth.clone())).collect();
+    assert_eq!(done, vec![(0, fl.flpth.clone()), (1, format!("{}fix/{}", wrkdr,
+      SRCFL))]);
+    assert!(Path::new(&format!("{}jsn/i2xr4_IDOC0000002382843157-0-segment.json",
+      wrkdr)).exists());
+    assert!(!Path::new(&fl.flpth).exists());
+    assert!(Path::new(&format!("{}pcd/{}", wrkdr, SRCFL)).exists());
+    assert!(Path::new(&format!("{}fix/{}", wrkdr, SRCFL)).exists());
+  }
+
+  #[test]
+  fn run_source_keeps_status_of_rejected_and_filtered_out_files() {
+    let wrkdr = work_folder("pipeline-status");
+    let (cnn, mut steps, fl) = sample_pipeline(&wrkdr);
+    steps[1].onefl = rejects;
+    assert_eq!(run_source(&cnn, &steps, &fl, &mut run("r1")).unwrap(), RC01);
+    assert_eq!(statuses(&cnn, "r1"), vec![row("fixed", SOKAY),
+      row("json", SRJCT)]);
+    assert!(Path::new(&fl.flpth).exists());
+    steps[1].ifilt = "zz*".to_string();
+    assert_eq!(run_source(&cnn, &steps, &fl, &mut run("r2")).unwrap(), OKAY);
+    assert_eq!(statuses(&cnn, "r2"), vec![row("fixed", SOKAY),
+      row("json", SSKIP)]);
+    assert!(!Path::new(&fl.flpth).exists());
+  }
+
+  #[test]
+  fn run_chain_stops_when_no_output_of_a_step_is_found() {
+    let wrkdr = work_folder("pipeline-output");
+    let (cnn, mut steps, fl) = sample_pipeline(&wrkdr);
+    write(format!("{}fix/{}", wrkdr, SRCFL), "EDI_DC40\n").unwrap();
+    steps[0].onefl = writes_nothing;
+    let rslt = run_source(&cnn, &steps, &fl, &mut run("r1"));
+    match rslt {
+      Err(ErrorTp::Config { detail, .. }) => assert!(detail.starts_with(
+        "No output of step fixed found as input of step json"), "{}", detail),
+      _ => panic!("chain should stop without output of step fixed")
+    }
+    assert_eq!(statuses(&cnn, "r1"), vec![row("fixed", SOKAY)]);
+    assert!(Path::new(&fl.flpth).exists());
+  }
+}
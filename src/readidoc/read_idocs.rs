@@ -2,14 +2,26 @@
 // Starting by getting workflow settings (2021-07-01 bar8tl)
 use crate::assets::{IdoctpTp, StepTp};
 use crate::config::WkflowTp;
-use crate::readidoc::tofixdsz::{flat_content_onefile, flat_content_inbatch};
-use crate::readidoc::tojson::{json_content_onefile, json_content_inbatch};
+use crate::error::{io_error, repo_error, ErrorTp, FailrTp, Result, RunsmTp};
+use crate::readedi::tojson::{x12json_content_onefile, x12json_content_inbatch,
+  edfjson_content_onefile, edfjson_content_inbatch};
+use crate::readidoc::tofixdsz::{flat_content_onefile, flat_content_inbatch,
+  CIMTYP, IDOCTYP, SEGNAM};
+use crate::readidoc::tojson::{json_content_onefile, json_content_inbatch,
+  LsegmTp};
 use crate::readidoc::runquery::{query_content_onefile, query_content_inbatch};
-use rblib::files_infolder::FilelistTp;
+use crate::readidoc::strtojson::{strjson_content_onefile, strjson_content_inbatch};
+use crate::readidoc::unjson::{unjson_content_onefile, unjson_content_inbatch};
+use crate::readidoc::toxml::{xml_content_onefile, xml_content_inbatch};
+use crate::readidoc::unxml::{unxml_content_onefile, unxml_content_inbatch};
+use crate::readidoc::validate::{valid_content_onefile, valid_content_inbatch,
+  EDI_DC};
+use rblib::files_infolder::{FilelistTp, files_infolder};
 use rblib::pass_filter::pass_filter;
 use rblib::move_file_wf::move_file_wf;
 use rblib::rename_file_wf::rename_file_wf;
 use rusqlite::Connection;
+use std::fs::read_to_string;
 
 pub const OKAY: &str = "00";
 pub const INP : &str = "inp";
@@ -21,72 +33,270 @@ pub struct StageTp {
   pub wfstp: StepTp
 }
 
+pub type OnefileFn = fn(&Connection, &StageTp, &FilelistTp) -> Result<String>;
+
 pub fn read_idocs(dbopt: &String, step: &str, wkflow: &WkflowTp, file: &String,
-  single: bool) {
+  single: bool) -> Result<RunsmTp> {
+  let cnn = Connection::open(dbopt)?;
+  let st = get_stage(&cnn, step, wkflow)?;
+  if single {
+    let fl = single_filelist(&st.wfstp.inpdr, file)?;
+    let rslt = get_onefile(step)?(&cnn, &st, &fl);
+    return Ok(run_onefile(&fl, rslt));
+  }
+  if step == "fixed" {
+    return flat_content_inbatch(dbopt, st, file);
+  } else if step == "json" {
+    return json_content_inbatch(dbopt, st, file);
+  } else if step == "query" {
+    return query_content_inbatch(dbopt, st, file);
+  } else if step == "unjson" {
+    return unjson_content_inbatch(dbopt, st, file);
+  } else if step == "strjson" {
+    return strjson_content_inbatch(dbopt, st, file);
+  } else if step == "xml" {
+    return xml_content_inbatch(dbopt, st, file);
+  } else if step == "unxml" {
+    return unxml_content_inbatch(dbopt, st, file);
+  } else if step == "validate" {
+    return valid_content_inbatch(dbopt, st, file);
+  } else if step == "x12json" {
+    return x12json_content_inbatch(dbopt, st, file);
+  } else if step == "edfjson" {
+    return edfjson_content_inbatch(dbopt, st, file);
+  }
+  return Err(step_error(step));
+}
+
+// get_stage.rs - Get settings of a workflow step from the repository
+// (2021-07-01 bar8tl)
+pub fn get_stage(cnn: &Connection, step: &str, wkflow: &WkflowTp) ->
+  Result<StageTp> {
   let mut st: StageTp = Default::default();
-  st.wfhdr.cntrl = wkflow.cntrl.clone();
-  st.wfhdr.clien = wkflow.clien.clone();
-  st.wfhdr.rcvpf = wkflow.rcvpf.clone();
-  let cnn = Connection::open(dbopt).expect("DB Error");
+  st.wfhdr = wkflow.clone();
   cnn.query_row("SELECT * FROM wkflow WHERE step=?1;", [step,], |row| { Ok({
-    st.wfstp.step  = row.get(0).unwrap();
-    st.wfstp.inpdr = row.get(1).unwrap();
-    st.wfstp.inptp = row.get(2).unwrap();
-    st.wfstp.outdr = row.get(3).unwrap();
-    st.wfstp.outtp = row.get(4).unwrap();
-    st.wfstp.refdr = row.get(5).unwrap();
-    st.wfstp.reftp = row.get(6).unwrap();
-    st.wfstp.wkflw = row.get(7).unwrap();
-    st.wfstp.pcddr = row.get(8).unwrap();
-    st.wfstp.ifilt = row.get(9).unwrap();
-  }) }).expect("Error: Step type not found in repository");
-  if single {
-    let atokn: Vec<&str> = file.rsplitn(2, ".").collect();
-    let fl = FilelistTp {
-      flpth: format!("{}{}", st.wfstp.inpdr, file),
-      fldir: st.wfstp.inpdr.clone(),
-      flide: file.clone(),
-      flnam: atokn[1].to_string(),
-      flext: atokn[0].to_string()
-    };
-    if step == "fixed" {
-      flat_content_onefile (&cnn, &st, &fl);
-    } else if step == "json" {
-      json_content_onefile (&cnn, &st, &fl);
-    } else if step == "query" {
-      query_content_onefile(&cnn, &st, &fl);
-    }
-  } else {
-    if step == "fixed" {
-      flat_content_inbatch (dbopt, st, file);
-    } else if step == "json" {
-      json_content_inbatch (dbopt, st, file);
-    } else if step == "query" {
-      query_content_inbatch(dbopt, st, file);
+    st.wfstp.step  = row.get(0)?;
+    st.wfstp.inpdr = row.get(1)?;
+    st.wfstp.inptp = row.get(2)?;
+    st.wfstp.outdr = row.get(3)?;
+    st.wfstp.outtp = row.get(4)?;
+    st.wfstp.refdr = row.get(5)?;
+    st.wfstp.reftp = row.get(6)?;
+    st.wfstp.wkflw = row.get(7)?;
+    st.wfstp.pcddr = row.get(8)?;
+    st.wfstp.ifilt = row.get(9)?;
+  }) }).map_err(|e| match e {
+    rusqlite::Error::QueryReturnedNoRows => ErrorTp::Repository {
+      table: "wkflow".to_string(),
+      detail: format!("Step type {} not found in repository", step) },
+    _ => repo_error(e, "wkflow")
+  })?;
+  return Ok(st);
+}
+
+// single_filelist.rs - File entry of a single file in the input folder of a step
+// (2021-07-01 bar8tl)
+pub fn single_filelist(inpdr: &String, file: &String) -> Result<FilelistTp> {
+  let atokn: Vec<&str> = file.rsplitn(2, ".").collect();
+  if atokn.len() < 2 {
+    return Err(ErrorTp::Config { file: file.clone(),
+      detail: "File name has no extension".to_string() });
+  }
+  return Ok(FilelistTp {
+    flpth: format!("{}{}", inpdr, file),
+    fldir: inpdr.clone(),
+    flide: file.clone(),
+    flnam: atokn[1].to_string(),
+    flext: atokn[0].to_string()
+  });
+}
+
+// get_onefile.rs - Conversion function of a step type for an individual file
+// (2021-07-01 bar8tl)
+pub fn get_onefile(step: &str) -> Result<OnefileFn> {
+  return Ok(match step {
+    "fixed"    => flat_content_onefile,
+    "json"     => json_content_onefile,
+    "query"    => query_content_onefile,
+    "unjson"   => unjson_content_onefile,
+    "strjson"  => strjson_content_onefile,
+    "xml"      => xml_content_onefile,
+    "unxml"    => unxml_content_onefile,
+    "validate" => valid_content_onefile,
+    "x12json"  => x12json_content_onefile,
+    "edfjson"  => edfjson_content_onefile,
+    _          => return Err(step_error(step))
+  });
+}
+
+// step_error.rs - Error for a step type with no conversion function
+// (2021-07-01 bar8tl)
+pub fn step_error(step: &str) -> ErrorTp {
+  return ErrorTp::Config { file: String::new(),
+    detail: format!("Step type {} not supported", step) };
+}
+
+// run_onefile.rs - Summarize the run over an specific single IDOC file
+// (2021-07-01 bar8tl)
+pub fn run_onefile(fl: &FilelistTp, rslt: Result<String>) -> RunsmTp {
+  let mut rs: RunsmTp = Default::default();
+  tally_file(&mut rs, fl, rslt);
+  return rs;
+}
+
+// run_inbatch.rs - Run a step over the set of IDOC files contained within the
+// input folder. A file failing with an error is reported at the end of the run
+// and does not stop the rest of the batch (2021-07-01 bar8tl)
+pub fn run_inbatch(dbopt: &String, st: &StageTp, idoct: &String,
+  onefile: OnefileFn) -> Result<RunsmTp> {
+  let cnn = Connection::open(dbopt)?;
+  let mut rs: RunsmTp = Default::default();
+  let flist: Vec<FilelistTp> = files_infolder(&st.wfstp.inpdr, &st.wfstp.inptp,
+    idoct);
+  for fl in &flist {
+    let rslt = onefile(&cnn, st, fl);
+    if let Ok(rtncd) = &rslt {
+      if st.wfstp.wkflw == "yes" {
+        next_stage(rtncd, st, fl);
+      }
     }
+    tally_file(&mut rs, fl, rslt);
+  }
+  return Ok(rs);
+}
+
+// tally_file.rs - Count outcome of an individual file in the run summary
+// (2021-07-01 bar8tl)
+pub fn tally_file(rs: &mut RunsmTp, fl: &FilelistTp, rslt: Result<String>) {
+  rs.nfile += 1;
+  match rslt {
+    Ok(rtncd) => if rtncd == OKAY { rs.nokay += 1 } else { rs.nrjct += 1 },
+    Err(e)    => rs.fails.push(FailrTp { flide: fl.flide.clone(),
+      error: e.at(&fl.flpth, 0) })
   }
 }
 
-// get_idoctp.rs - Upload internal table of IDOC types (2021-07-01 bar8tl)
-pub fn get_idoctp(cnn: &Connection, wkflow: &WkflowTp, flide: &String) -> IdoctpTp {
-  let mut it: IdoctpTp = Default::default();
-  let atokn: Vec<&str> = flide.splitn(2, "_").collect();
+// get_idoctp.rs - Get IDOC type of a file: by the prefix of the file name (up to
+// '_') if it is a known short name, otherwise by the IDoc type found in the file
+// content, as with files named by SAP (IDOC000...). Control record name and client
+// of the IDoc type override those of the workflow settings (2021-07-01 bar8tl)
+pub fn get_idoctp(cnn: &Connection, wkflow: &WkflowTp, fl: &FilelistTp) ->
+  Result<IdoctpTp> {
+  let atokn: Vec<&str> = fl.flnam.splitn(2, "_").collect();
   if atokn.len() == 2 {
-    let short = atokn[0].to_string();
-    cnn.query_row("SELECT * FROM idoctp WHERE short=?1;", [short,], |row| { Ok({
-      it.itype = row.get(0).unwrap();
-      it.itype = it.itype.to_uppercase();
-      it.idefn = it.itype.to_uppercase().replace("/", "_-");
-      it.short = row.get(1).unwrap();
-      it.cntrl = wkflow.cntrl.clone();
-      it.clien = wkflow.clien.clone();
-      it.rcvpf = row.get(2).unwrap();
-      if it.rcvpf.len() == 0 {
-        it.rcvpf = wkflow.rcvpf.clone();
+    if let Some(it) = read_idoctp(cnn, wkflow, "short=?1", atokn[0])? {
+      return Ok(it);
+    }
+  }
+  for itype in content_idoctypes(cnn, &fl.flpth)? {
+    if let Some(it) = read_idoctp(cnn, wkflow, "upper(itype)=upper(?1)", &itype)? {
+      return Ok(it);
+    }
+  }
+  return Err(ErrorTp::Repository { table: "idoctp".to_string(), detail: format!(
+    "IDoc type of file {} not found in repository, neither by file name prefix \
+    nor by content", fl.flide) });
+}
+
+// read_idoctp.rs - Read IDOC type entry matching the condition given, completed
+// with the workflow settings where the entry leaves them empty
+// (2021-07-01 bar8tl)
+pub fn read_idoctp(cnn: &Connection, wkflow: &WkflowTp, cond: &str, value: &str) ->
+  Result<Option<IdoctpTp>> {
+  let rslt = cnn.query_row(&format!("SELECT itype, idefn, short, cntrl, clien, rcvpf
+    FROM idoctp WHERE {};", cond), [value,], |row| Ok(IdoctpTp {
+    itype: row.get(0)?, idefn: row.get(1)?, short: row.get(2)?, cntrl: row.get(3)?,
+    clien: row.get(4)?, rcvpf: row.get(5)? }));
+  let mut it = match rslt {
+    Ok(it) => it,
+    Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+    Err(e) => return Err(repo_error(e, "idoctp"))
+  };
+  it.itype = it.itype.to_uppercase();
+  if it.idefn.len() == 0 {
+    it.idefn = it.itype.replace("/", "_-");
+  }
+  if it.cntrl.len() == 0 {
+    it.cntrl = wkflow.cntrl.clone();
+  }
+  if it.clien.len() == 0 {
+    it.clien = wkflow.clien.clone();
+  }
+  if it.rcvpf.len() == 0 {
+    it.rcvpf = wkflow.rcvpf.clone();
+  }
+  return Ok(Some(it));
+}
+
+// content_idoctypes.rs - Get candidate IDOC types from the content of a file,
+// extension (CIMTYP) before basic type (IDOCTYP): from the control record line of
+// fixed size files (positions as in the IDoc definitions), the EDIDC section of
+// structured listings, the control record of JSON and XML files. Structured
+// listings giving codes instead of type names resolve by their first segment, if
+// it belongs to only one IDoc type in the repository (2021-07-01 bar8tl)
+pub fn content_idoctypes(cnn: &Connection, flpth: &String) -> Result<Vec<String>> {
+  let text = read_to_string(flpth).map_err(|e| io_error(e, flpth))?;
+  let mut types: Vec<String> = Vec::new();
+  let first = text.lines().next().unwrap_or_default();
+  if first.starts_with(EDI_DC) {
+    let chars: Vec<char> = first.chars().collect();
+    let mut stmt = cnn.prepare("SELECT DISTINCT dname, strps, endps FROM items
+      WHERE rname='CONTROL' and dname IN ('CIMTYP', 'IDOCTYP') ORDER BY dname;")
+      .map_err(|e| repo_error(e, "items"))?;
+    let mut rows = stmt.query([]).map_err(|e| repo_error(e, "items"))?;
+    while let Some(row) = rows.next().map_err(|e| repo_error(e, "items"))? {
+      let (strps, endps): (usize, usize) = (row.get(1)?, row.get(2)?);
+      if strps > 0 && strps <= endps && endps <= chars.len() {
+        types.push(chars[strps-1..endps].iter().collect::<String>().trim()
+          .to_string());
+      }
+    }
+  } else if text.trim_start().starts_with('{') {
+    if let Ok(lsegm) = serde_json::from_str::<LsegmTp>(&text) {
+      for ssegm in lsegm.ssegm.iter().take(1) {
+        for key in [CIMTYP, IDOCTYP] {
+          ssegm.cntrl.iter().filter(|f| f.key == key)
+            .for_each(|f| types.push(f.val.clone()));
+        }
+      }
+    }
+  } else if text.trim_start().starts_with('<') {
+    for key in [CIMTYP, IDOCTYP] {
+      if let Some((_, rest)) = text.split_once(&format!("<{}>", key)) {
+        types.push(rest.split('<').next().unwrap_or_default().trim().to_string());
+      }
+    }
+  } else {
+    let mut segmn = String::new();
+    let mut ctype: [String; 2] = Default::default(); // CIMTYP, IDOCTYP
+    for line in text.lines() {
+      let tokn: Vec<&str> = line.trim().split('\t').collect();
+      if tokn.len() >= 3 && (tokn[0] == CIMTYP || tokn[0] == IDOCTYP) {
+        let val = tokn[2].split(" :").next().unwrap_or_default().trim();
+        if !val.chars().all(|c| c.is_ascii_digit()) { // Code, not a type name
+          ctype[if tokn[0] == CIMTYP { 0 } else { 1 }] = val.to_string();
+        }
+      }
+      if tokn.len() == 3 && tokn[0] == SEGNAM {
+        segmn = tokn[2].trim().to_string();
+        break;
       }
-    }) }).expect("Error: Idoctp not found in repository");
+    }
+    types.extend(ctype);
+    if segmn.len() > 0 {
+      let mut stmt = cnn.prepare("SELECT DISTINCT a.itype FROM idoctp AS a JOIN
+        items AS b ON upper(a.itype)=b.idocn WHERE b.rname='SEGMENT' and
+        (b.dname=?1 or b.dtype=?1);").map_err(|e| repo_error(e, "idoctp"))?;
+      let found: Vec<String> = stmt.query_map([&segmn], |row| row.get(0))
+        .map_err(|e| repo_error(e, "idoctp"))?.collect::<rusqlite::Result<_>>()
+        .map_err(|e| repo_error(e, "idoctp"))?;
+      if found.len() == 1 {
+        types.push(found[0].clone());
+      }
+    }
   }
-  return it;
+  types.retain(|t| t.len() > 0);
+  return Ok(types);
 }
 
 // next_stage.rs - Conclude workflow steps process (2021-07-01 bar8tl)
@@ -100,3 +310,68 @@ pub fn next_stage(rtncd: &String, st: &StageTp, fl: &FilelistTp) {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readidoc::samples::{sample_file, sample_path, sample_repo,
+    sample_stage, work_folder, FIXED, ITYPE, JSONF, STRUC};
+  use std::fs::{copy, write};
+
+  const XMLF: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<INVOIC02>\
+    <IDOC BEGIN=\"1\"><EDI_DC40 SEGMENT=\"1\"><TABNAM>EDI_DC40</TABNAM>\
+    <IDOCTYP>INVOIC02</IDOCTYP><CIMTYP>/RB04/YP3_INVOIC02_XML</CIMTYP>\
+    </EDI_DC40></IDOC></INVOIC02>\n";
+
+  // unknown_file.rs - Copy a sample file into a work folder under a name whose
+  // prefix is no IDoc type short name
+  fn unknown_file(sampl: &String, flext: &str, wrkdr: &String) -> FilelistTp {
+    let flnam = format!("zz9_{}", flext);
+    let flide = format!("{}.{}", flnam, flext);
+    let flpth = format!("{}{}", wrkdr, flide);
+    copy(sampl, &flpth).unwrap();
+    return FilelistTp { flpth, fldir: wrkdr.clone(), flide, flnam,
+      flext: flext.to_string() };
+  }
+
+  #[test]
+  fn get_idoctp_resolves_type_from_content_of_each_format() {
+    let wrkdr = work_folder("idoctp-content");
+    let cnn = Connection::open(sample_repo(&wrkdr)).unwrap();
+    let st = sample_stage(&wrkdr, &wrkdr);
+    let xpath = format!("{}sample.xml", wrkdr);
+    write(&xpath, XMLF).unwrap();
+    let named = [ITYPE.to_string(), "INVOIC02".to_string()];
+    for (sampl, flext, types) in [
+      (sample_path(FIXED), "fxs", named.to_vec()),
+      (sample_path(JSONF), "json", named.to_vec()),
+      (xpath, "xml", named.to_vec()),
+      (sample_path(STRUC), "txt", vec![ITYPE.to_string()])] {
+      let fl = unknown_file(&sampl, flext, &wrkdr);
+      assert_eq!(content_idoctypes(&cnn, &fl.flpth).unwrap(), types, "{}", flext);
+      let it = get_idoctp(&cnn, &st.wfhdr, &fl).unwrap();
+      assert_eq!(it.itype, ITYPE, "{}", flext);
+    }
+    let fl = unknown_file(&format!("{}sample.xml", wrkdr), "dat", &wrkdr);
+    write(&fl.flpth, "unknown content\n").unwrap();
+    assert!(get_idoctp(&cnn, &st.wfhdr, &fl).is_err());
+  }
+
+  #[test]
+  fn get_idoctp_takes_idoc_type_settings_over_workflow_defaults() {
+    let wrkdr = work_folder("idoctp-settings");
+    let cnn = Connection::open(sample_repo(&wrkdr)).unwrap();
+    let st = sample_stage(&wrkdr, &wrkdr);
+    let fl = sample_file(FIXED, &wrkdr);
+    let it = get_idoctp(&cnn, &st.wfhdr, &fl).unwrap();
+    assert_eq!([it.cntrl, it.clien, it.rcvpf], ["EDI_DC40", "011", "RE"]);
+    assert_eq!(it.idefn, "_-RB04_-YP3_INVOIC02_XML");
+    cnn.execute("UPDATE idoctp SET cntrl='EDI_DC', clien='200', rcvpf='LS';", ())
+      .unwrap();
+    let unknw = unknown_file(&sample_path(STRUC), "txt", &wrkdr);
+    for fl in [fl, unknw] {
+      let it = get_idoctp(&cnn, &st.wfhdr, &fl).unwrap();
+      assert_eq!([it.cntrl, it.clien, it.rcvpf], ["EDI_DC", "200", "LS"]);
+    }
+  }
+}
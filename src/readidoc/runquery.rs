@@ -1,32 +1,41 @@
 // query_content.rs - Starts proper function to perform IDOC content inquiry from
 // JSON format. Either from a set of files contained within a folder or from an
 // specific single file (2021-07-01 bar8tl)
-#![allow(unused_variables, unused_imports)]
-#![allow(dead_code)]
-
 use crate::assets::IdoctpTp;
-use crate::readidoc::read_idocs::next_stage;
-use crate::readidoc::read_idocs::{StageTp, get_idoctp};
-use crate::readidoc::tojson::{FieldTp, OKAY};
-use rblib::files_infolder::{FilelistTp, files_infolder};
+use crate::error::{io_error, json_error, Result, RunsmTp};
+use crate::readidoc::decode::{get_decoder, DecodTp};
+use crate::readidoc::read_idocs::{run_inbatch, StageTp, get_idoctp};
+use crate::readidoc::tojson::{FieldTp, LsegmTp, RsegmTp, SsegmTp, CONTROL, OKAY};
+use rblib::files_infolder::FilelistTp;
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use serde_json::from_reader;
 use std::fs::File;
 use std::io::Write;
 
+// symbols.rs - Symbolic constants for IDOC query from files stored in JSON format
+// (2021-07-01 bar8tl)
+pub const STD: &str = "std"; // Default query definition suffix
+
 // types.rs - Data structures used in IDOC query from files stored in JSON format
 // (2021-07-01 bar8tl)
 #[derive(Debug, Clone, Default, Deserialize)]
 pub struct RquryTp {
+  #[serde(alias = "Fields")]
   pub fields: Vec<String>
 }
 
 #[derive(Debug, Clone, Default, Serialize)]
 pub struct SquryTp {
+  pub instn : usize,
   pub fields: Vec<FieldTp>
 }
 
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LquryTp {
+  pub squry: Vec<SquryTp>
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct QtoknTp {
   pub segmn: String,
@@ -37,167 +46,235 @@ pub struct QtoknTp {
 
 // query_content_inbatch.rs - Start batch process to perform queries into IDOC files
 // stored in JSON format (2021-07-01 bar8tl)
-pub fn query_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) {}
-/*  let f = File::open(&objtp).expect("Query JSON file not found.");
-  let reqqy: RquryTp = from_reader(f).expect("JSON not well-formed");
-  let flist: Vec<FilelistTp> = files_infolder(inpdr, inptp, objtp);
-  for fle in &flist {
-    let rtncd = query_content_onefile(inpdr, outdr, idt, objtp, refdr, fle, &reqqy);
-    if wkflw == "yes" {
-      next_stage(&rtncd, inpdr, outdr, pcddr, fle, ifilt);
-    }
-  }
-} */
+// Command line: edimaps step query <IDOC-json-file>
+pub fn query_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) ->
+  Result<RunsmTp> {
+  return run_inbatch(dbopt, &st, idoct, query_content_onefile);
+}
 
-// query_content_onefile.rs - Perform query on individual IDOC files in JSON format
-// (2021-07-01 bar8tl)
+// query_content_onefile.rs - Perform query on individual IDOC files in JSON format.
+// Query definition is taken from file <refdr><idoc-short>-<reftp>.json, and the
+// result is written into file <outdr><idoc-file>_resp.json (2021-07-01 bar8tl)
 pub fn query_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
-  String { return OKAY.to_string(); }
-/*
-  let mut resqy: SquryTp = Default::default(return OKAY.to_string(););
+  Result<String> {
+  let it: IdoctpTp = get_idoctp(cnn, &st.wfhdr, fl)?;
+  let reqqy: RquryTp = read_query_file(st, &it)?;
+  let ifile = File::open(&fl.flpth).map_err(|e| io_error(e, &fl.flpth))?;
+  let lsegm: LsegmTp = from_reader(ifile).map_err(|e| json_error(e, &fl.flpth))?;
+  let decod: DecodTp = get_decoder(cnn)?;
+  let mut resqy: LquryTp = Default::default();
+  for ssegm in &lsegm.ssegm {
+    resqy.squry.push(query_idoc_set(&reqqy, ssegm, &decod));
+  }
+  let opath = format!("{}{}_resp.json", st.wfstp.outdr, fl.flnam);
+  let mut file = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+  let fdata = serde_json::to_string_pretty(&resqy)
+    .map_err(|e| json_error(e, &opath))?;
+  let bdata: &[u8] = fdata.as_bytes();
+  file.write_all(&bdata).map_err(|e| io_error(e, &opath))?;
+  return Ok(OKAY.to_string());
+}
 
-//  let mut token: Vec<QtoknTp> = Default::default();
-//  let field: String = Default::default();
+// read_query_file.rs - Get list of query keys to be resolved for the IDOC type
+// being inquired (2021-07-01 bar8tl)
+pub fn read_query_file(st: &StageTp, it: &IdoctpTp) -> Result<RquryTp> {
+  let reftp = if st.wfstp.reftp.len() == 0 { STD } else { st.wfstp.reftp.as_str() };
+  let qpath = format!("{}{}-{}.json", st.wfstp.refdr, it.short, reftp);
+  let f = File::open(&qpath).map_err(|e| io_error(e, &qpath))?;
+  let reqqy: RquryTp = from_reader(f).map_err(|e| json_error(e, &qpath))?;
+  return Ok(reqqy);
+}
+
+// query_idoc_set.rs - Resolve each query key against one IDOC data set. Keys are
+// paths of segments separated by '\' and ending in a field name; each segment
+// token can carry an instance index and a qualifier filter, as in
+// E2EDK01005\E2EDP01011[1]\E2EDPA1003.PARVW:WE\PARTN or CONTROL\DOCNUM. Coded
+// values get the description of their code (2021-07-01 bar8tl)
+pub fn query_idoc_set(reqqy: &RquryTp, ssegm: &SsegmTp, decod: &DecodTp) ->
+  SquryTp {
+  let mut squry = SquryTp { instn: ssegm.instn, fields: Vec::new() };
   for fld in &reqqy.fields {
     let tokn: Vec<&str> = fld.split('\\').collect();
-    if tokn.len() == 1 {
-      resqy.fields.push(FieldTp{key: fld.to_string(), val: String::new()});
-      continue;
-    }
-
-    if tokn.len() == 2 && tokn[0] == "CONTROL" {
-      resqy.fields.push(FieldTp{key: fld, val: query_control(tokn[1]});
-      continue;
-    }
-    for (i, t) in tokn.iter().enumerate() {
-      if i < tokn.len()-1 {
-        let c = split_querykey(t.to_string());
-        println!("{:?}|", c);
-        token.push(c);
-      } else {
-        field = tokn[tokn.len()-1].to_string();
-        println!("{}", field);
-        if token.len() == 1 {
-          resqy.fields.push(FieldTp{key: fld, val: query_segment(token[0], field)});
-          continue;
+    let mut val = String::new();
+    let mut segmn = String::new();
+    if tokn.len() == 2 && tokn[0] == CONTROL {
+      val = query_control(&ssegm.cntrl, tokn[1]);
+      segmn = CONTROL.to_string();
+    } else if tokn.len() > 1 {
+      let field = tokn[tokn.len()-1];
+      let qtokn: Vec<QtoknTp> = tokn[..tokn.len()-1].iter()
+        .map(|t| split_querykey(t)).collect();
+      segmn = qtokn[qtokn.len()-1].segmn.clone();
+      for rsegm in &ssegm.rsegm {
+        if let Some(v) = query_segment(rsegm, &qtokn, field) {
+          val = v;
+          break;
         }
       }
     }
-
+    let dtext = decod.describe(&segmn, tokn[tokn.len()-1], &val)
+      .unwrap_or_default();
+    squry.fields.push(FieldTp { key: fld.to_string(), val: val, dtext: dtext });
   }
-  let mut file = File::create(format!("{}_resp.json", fle.flnam)).expect("error");
-  let fdata = serde_json::to_string_pretty(&resqy).unwrap();
-  let bdata: &[u8] = fdata.as_bytes();
-  file.write_all(&bdata).unwrap();
-  return OKAY.to_string();
+  return squry;
 }
 
-// split_querykey.rs -  Function to identify individual tokens in IDoc query key
+// split_querykey.rs -  Function to identify individual tokens in IDoc query key:
+// SEGMN[INSTN].QLKEY:QLVAL, with instance and qualifier being optional
 // (2021-07-01 bar8tl)
-pub fn split_querykey(key: String) -> QtoknTp {
+pub fn split_querykey(key: &str) -> QtoknTp {
   let mut q: QtoknTp = Default::default();
-  let atokn: Vec<&str> = key.splitn(2, "[").collect();
+  let atokn: Vec<&str> = key.splitn(2, ".").collect();
   if atokn.len() == 2 {
-    q.segmn = atokn[0].to_string();
-    let btokn: Vec<&str> = atokn[1].splitn(2, "]").collect();
+    let btokn: Vec<&str> = atokn[1].splitn(2, ":").collect();
+    q.qlkey = btokn[0].to_string();
     if btokn.len() == 2 {
-      q.instn = btokn[0].parse::<usize>().unwrap();
-      let ctokn: Vec<&str> = btokn[1].splitn(2, ".").collect();
-      if ctokn.len() == 2 {
-        q.segmn = ctokn[0].to_string();
-        let dtokn: Vec<&str> = ctokn[1].splitn(2, ":").collect();
-        if dtokn.len() == 2 {
-          q.qlkey = dtokn[0].to_string();
-          q.qlval = dtokn[1].to_string();
-        }
-      }
-    }
-  } else {
-    let btokn: Vec<&str> = key.splitn(2, ".").collect();
-    if btokn.len() == 2 {
-      q.segmn = btokn[0].to_string();
-      let ctokn: Vec<&str> = btokn[1].splitn(2, ":").collect();
-      if ctokn.len() == 2 {
-        q.qlkey = ctokn[0].to_string();
-        q.qlval = ctokn[1].to_string();
-      }
-    } else {
-      q.segmn = key;
+      q.qlval = btokn[1].to_string();
     }
   }
+  let ctokn: Vec<&str> = atokn[0].splitn(2, "[").collect();
+  q.segmn = ctokn[0].to_string();
+  if ctokn.len() == 2 {
+    q.instn = ctokn[1].trim_end_matches(']').parse::<usize>().unwrap_or(0);
+  }
   return q;
 }
 
-func (d *Query_tp) MatchSegmL0(l0 int, sgkey lib.Qtokn_tp) (bool) {
-  if d.Segm.Child[l0].Segmn == sgkey.Segmn {
-    if sgkey.Instn != 0 && d.Segm.Child[l0].Instn == sgkey.Instn {
-      if sgkey.Qlkey != "" && d.Segm.Child[l0].Qlkey == sgkey.Qlkey {
-        if sgkey.Qlval != "" && d.Segm.Child[l0].Qlval == sgkey.Qlval {
-          return true
-        }
-      }
-    } else {
-      if sgkey.Qlkey != "" && d.Segm.Child[l0].Qlkey == sgkey.Qlkey {
-        if sgkey.Qlval != "" && d.Segm.Child[l0].Qlval == sgkey.Qlval {
-          return true
-        }
-      } else {
-        return true
-      }
+// query_control.rs - Read specific field into Control Record (2021-07-01 bar8tl)
+pub fn query_control(cntrl: &Vec<FieldTp>, key: &str) -> String {
+  for c in cntrl {
+    if c.key == key {
+      return c.val.clone();
     }
-  } else {
-    return true
   }
-  return false
+  return String::new();
 }
 
-func (d *Query_tp) MatchSegmL1(l0 int, sgkey lib.Qtokn_tp) (bool) {
-  MatchSegmL0(0, sgkey)
-
-  if d.Segm.Child[l0].Segmn == sgkey.Segmn {
-    if sgkey.Instn != 0 && d.Segm.Child[l0].Child[l1].Instn == sgkey.Instn {
-      if sgkey.Qlkey != "" && d.Segm.Child[l0].Child[l1].Qlkey == sgkey.Qlkey {
-        if sgkey.Qlval != "" && d.Segm.Child[l0].Child[l1].Qlval == sgkey.Qlval {
-          return true
-        }
+// query_segment.rs - Walk down the segment tree following the query tokens, at any
+// nesting depth. When a token matches several sibling segments, each candidate is
+// tried in turn until the full path resolves (2021-07-01 bar8tl)
+pub fn query_segment(rsegm: &RsegmTp, qtokn: &[QtoknTp], field: &str) ->
+  Option<String> {
+  if qtokn.len() == 0 {
+    for f in &rsegm.field {
+      if f.key == field {
+        return Some(f.val.clone());
       }
-    } else {
-      if sgkey.Qlkey != "" && d.Segm.Child[l0].Child[l1].Qlkey == sgkey.Qlkey {
-        if sgkey.Qlval != "" && d.Segm.Child[l0].Child[l1].Qlval == sgkey.Qlval {
-          return true
-        }
-      } else {
-        return true
+    }
+    return None;
+  }
+  for c in &rsegm.child {
+    if match_segment(c, &qtokn[0]) {
+      if let Some(val) = query_segment(c, &qtokn[1..], field) {
+        return Some(val);
       }
     }
-  } else {
-    return true
   }
-  return false
+  return None;
 }
 
-// query_control.rs - Read specific field into Control Record (2021-07-01 bar8tl)
-fn query_control(key: String) -> String {
-  fc, _ := ioutil.ReadFile("control.json")
-  json.Unmarshal(fc, &d.Cntrl)
-  for c := range d.Cntrl.Field {
-    if c.key == key {
-      return c.val
+// match_segment.rs - Check a segment node against a query token: segment name,
+// instance number (if given) and qualifier (if given) (2021-07-01 bar8tl)
+pub fn match_segment(rsegm: &RsegmTp, sgkey: &QtoknTp) -> bool {
+  if rsegm.segmn != sgkey.segmn {
+    return false;
+  }
+  if sgkey.instn != 0 && rsegm.instn != sgkey.instn {
+    return false;
+  }
+  if sgkey.qlkey.len() > 0 {
+    if rsegm.qlkey == sgkey.qlkey && rsegm.qlval == sgkey.qlval {
+      return true;
     }
+    return rsegm.field.iter().any(|f| f.key == sgkey.qlkey && f.val == sgkey.qlval);
   }
-  return (String::new());
+  return true;
 }
 
-func (d *Query_tp) QuerySegment(sgkey lib.Qtokn_tp, key string) (string) {
-  fs, _ := ioutil.ReadFile("segment.json")
-  json.Unmarshal(fs, &d.Segm)
-  if d.MatchSegmL0(0, sgkey) {
-    for _, f := range d.Segm.Child[0].Field {
-      if f.Key == key {
-        return f.Val
-      }
-    }
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readidoc::samples::{sample_file, sample_path, sample_repo,
+    sample_stage, work_folder, JSONF};
+  use std::fs::read_to_string;
+
+  fn sample_set() -> SsegmTp {
+    let f = File::open(sample_path(JSONF)).unwrap();
+    let lsegm: LsegmTp = from_reader(f).unwrap();
+    return lsegm.ssegm[0].clone();
+  }
+
+  fn query(fields: &[&str]) -> Vec<String> {
+    let reqqy = RquryTp { fields: fields.iter().map(|f| f.to_string()).collect() };
+    return query_idoc_set(&reqqy, &sample_set(), &Default::default()).fields
+      .into_iter().map(|f| f.val).collect();
+  }
+
+  #[test]
+  fn split_querykey_takes_instance_and_qualifier() {
+    let q = split_querykey("E2EDP01011[12].PARVW:WE");
+    assert_eq!((q.segmn.as_str(), q.instn, q.qlkey.as_str(), q.qlval.as_str()),
+      ("E2EDP01011", 12, "PARVW", "WE"));
+    let q = split_querykey("E2EDKA1003.PARVW");
+    assert_eq!((q.segmn.as_str(), q.instn, q.qlkey.as_str(), q.qlval.as_str()),
+      ("E2EDKA1003", 0, "PARVW", ""));
+    let q = split_querykey("/RB04/YP3_E1EDK01_XML001");
+    assert_eq!((q.segmn.as_str(), q.instn, q.qlkey.len()),
+      ("/RB04/YP3_E1EDK01_XML001", 0, 0));
+  }
+
+  #[test]
+  fn query_content_onefile_resolves_standard_query_of_sample() {
+    let wrkdr = work_folder("runquery");
+    let dbopt = sample_repo(&wrkdr);
+    let cnn = Connection::open(&dbopt).unwrap();
+    let fl = sample_file(JSONF, &wrkdr);
+    let mut st = sample_stage(&wrkdr, &wrkdr);
+    st.wfstp.refdr = sample_path("extras/_queries/");
+    assert_eq!(query_content_onefile(&cnn, &st, &fl).unwrap(), OKAY);
+    let opath = format!("{}{}_resp.json", wrkdr, fl.flnam);
+    let resp: serde_json::Value =
+      serde_json::from_str(&read_to_string(&opath).unwrap()).unwrap();
+    let fields = resp["squry"][0]["fields"].as_array().unwrap();
+    let value = |key: &str| fields.iter().find(|f| f["key"] == key).unwrap()["val"]
+      .as_str().unwrap().to_string();
+    assert_eq!(fields.len(), 8);
+    assert_eq!(value("CONTROL\\DOCNUM"), "0000002382843157");
+    assert_eq!(value("E2EDK01005\\BELNR"), "0237179334");
+    assert_eq!(value("E2EDK01005\\/RB04/YP3_E1EDK01_XML001\\ISSUING_PLACE"),
+      "Toluca México");
+    assert_eq!(value("E2EDK01005\\E2EDKA1003.PARVW:AG\\PARTN"), "1000001805");
+    assert_eq!(value(
+      "E2EDK01005\\E2EDKA1003.PARVW:AG\\/RB04/YP3_E1EDKA1_XML001\\REG_TAX_NUMBER"),
+      "FMO8304236C5");
+    assert_eq!(value("E2EDK01005\\E2EDP01011[1]\\WERKS"), "E622");
+    assert_eq!(value("E2EDK01005\\E2EDP01011[1]\\E2EDPA1003.PARVW:WE\\PARTN"),
+      "1000000985");
+    assert_eq!(value("E2EDK01005\\E2EDS01.SUMID:010\\SUMME"), "50.49");
+  }
+
+  #[test]
+  fn query_idoc_set_matches_instances_and_backtracks_over_siblings() {
+    assert_eq!(query(&[
+      "E2EDK01005\\E2EDK02[2]\\BELNR",
+      "E2EDK01005\\E2EDK02.QUALF:002\\BELNR",
+      "E2EDK01005\\E2EDKA1003\\/RB04/YP3_E1EDKA1_XML001\\REG_TAX_NUMBER",
+      "E2EDK01005\\E2EDKA1003[5]\\/RB04/YP3_E1EDKA1_XML001\\REG_TAX_NUMBER",
+      "E2EDK01005\\E2EDP01011\\E2EDPA1003.PARVW:RG\\PARTN",
+      "CONTROL\\MANDT"]),
+      vec!["SF9KB4", "0034786978", "FMO8304236C5", "RBO910102QJ9", "1000001805",
+        "011"]);
+  }
+
+  #[test]
+  fn query_idoc_set_leaves_unmatched_paths_empty() {
+    assert_eq!(query(&[
+      "E2EDK01005\\E2EDKA1003.PARVW:ZZ\\PARTN",
+      "E2EDK01005\\E2EDP01011[4]\\WERKS",
+      "E2EDK01005\\E2EDK02[2]\\NOFIELD",
+      "E2EDK01005\\NOSEGMENT\\BELNR",
+      "CONTROL\\NOFIELD",
+      "BELNR"]),
+      vec!["", "", "", "", "", ""]);
   }
-  return ""
 }
-*/
\ No newline at end of file
@@ -0,0 +1,107 @@
+// samples.rs - Test fixtures: a repository loaded with the IDoc definition of the
+// sample files in extras, and work folders where test runs write their output
+// (2021-07-01 bar8tl)
+use crate::assets::StepTp;
+use crate::config::WkflowTp;
+use crate::definitn::add_definitn;
+use crate::readidoc::read_idocs::StageTp;
+use crate::reposit::ini_repo;
+use rblib::files_infolder::FilelistTp;
+use rusqlite::Connection;
+use std::env::temp_dir;
+use std::fs::{copy, create_dir_all, read_to_string, remove_dir_all, write};
+
+// symbols.rs - Sample files of an /RB04/YP3_INVOIC02_XML invoice
+// (2021-07-01 bar8tl)
+pub const IDEFN: &str = "extras/00-definition/_-rb04_-yp3_invoic02_xml.txt";
+pub const STRUC: &str = "extras/01-structured/i2xr4_IDOC0000002382843157.txt";
+pub const FIXED: &str = "extras/02-fixedsize/i2xr4_IDOC0000002382843157.txt";
+pub const JSONF: &str =
+  "extras/03-json/i2xr4_idoc0000002382843157-0-segment.json";
+pub const ITYPE: &str = "/RB04/YP3_INVOIC02_XML";
+pub const SHORT: &str = "i2xr4";
+
+// sample_path.rs - Full path of a sample file of the crate (2021-07-01 bar8tl)
+pub fn sample_path(file: &str) -> String {
+  return format!("{}/{}", env!("CARGO_MANIFEST_DIR"), file);
+}
+
+// work_folder.rs - Empty folder for the output of a test, ending with separator
+// (2021-07-01 bar8tl)
+pub fn work_folder(name: &str) -> String {
+  let dir = temp_dir().join(format!("edimaps-{}", name));
+  let _ = remove_dir_all(&dir);
+  create_dir_all(&dir).unwrap();
+  return format!("{}/", dir.display());
+}
+
+// sample_repo.rs - Repository in a work folder with the sample IDoc definition
+// and its IDoc type (2021-07-01 bar8tl)
+pub fn sample_repo(wrkdr: &String) -> String {
+  let dbopt = format!("{}edimaps.db", wrkdr);
+  for table in ["items", "struc", "idoctp"] {
+    ini_repo(&dbopt, &table.to_string()).unwrap();
+  }
+  add_definitn(&dbopt, &sample_path(IDEFN)).unwrap();
+  let cnn = Connection::open(&dbopt).unwrap();
+  cnn.execute("INSERT INTO idoctp VALUES (?1, '', ?2, '', '', '');",
+    [ITYPE, SHORT]).unwrap();
+  return dbopt;
+}
+
+// strict_repo.rs - Repository with the sample IDoc definition made stricter: item
+// segment E2EDP01011 heads group 1, mandatory and at most 3 times, as in the
+// sample files, and summary segment E2EDS01 is mandatory (2021-07-01 bar8tl)
+pub fn strict_repo(wrkdr: &String) -> String {
+  let dbopt = sample_repo(wrkdr);
+  let text = read_to_string(sample_path(IDEFN)).unwrap();
+  let (head, tail) = text.split_once("    BEGIN_SEGMENT       E2EDS01").unwrap();
+  let text = format!("{}    END_GROUP\n\n    BEGIN_SEGMENT       E2EDS01{}", head,
+    tail.replacen("STATUS              OPTIONAL", "STATUS              MANDATORY",
+    1)).replacen("    BEGIN_SEGMENT       E2EDP01011", "    BEGIN_GROUP         1
+    LEVEL               02
+    STATUS              MANDATORY
+    LOOPMIN             0000000001
+    LOOPMAX             0000000003
+
+    BEGIN_SEGMENT       E2EDP01011", 1);
+  let dpath = format!("{}strict_definition.txt", wrkdr);
+  write(&dpath, text).unwrap();
+  add_definitn(&dbopt, &dpath).unwrap();
+  return dbopt;
+}
+
+// sample_stage.rs - Step settings reading from and writing to work folders
+// (2021-07-01 bar8tl)
+pub fn sample_stage(inpdr: &String, outdr: &String) -> StageTp {
+  return StageTp {
+    wfhdr: WkflowTp { cntrl: "EDI_DC40".to_string(), clien: "011".to_string(),
+      rcvpf: "RE".to_string(), lnend: "lf".to_string(), ..Default::default() },
+    wfstp: StepTp { inpdr: inpdr.clone(), outdr: outdr.clone(),
+      wkflw: "no".to_string(), ..Default::default() }
+  };
+}
+
+// sample_file.rs - Copy a sample file into a work folder, returning its file
+// entry (2021-07-01 bar8tl)
+pub fn sample_file(file: &str, wrkdr: &String) -> FilelistTp {
+  let flide = file.rsplit('/').next().unwrap().to_string();
+  let (flnam, flext) = flide.rsplit_once('.').unwrap();
+  let flpth = format!("{}{}", wrkdr, flide);
+  copy(sample_path(file), &flpth).unwrap();
+  return FilelistTp { flpth: flpth, fldir: wrkdr.clone(), flide: flide.clone(),
+    flnam: flnam.to_string(), flext: flext.to_string() };
+}
+
+// fixed_lines.rs - Lines of a fixed size IDoc file with the parent segment number
+// of data records (PSGNUM, positions 56-61) blanked. The sample file does not
+// number parents by the segment tree, as the IDoc writers do (2021-07-01 bar8tl)
+pub fn fixed_lines(flpth: &String) -> Vec<String> {
+  return read_to_string(flpth).unwrap().split('\n').map(|l| {
+    let mut chars: Vec<char> = l.chars().collect();
+    if !l.starts_with("EDI_DC") && chars.len() >= 61 {
+      chars[55..61].fill(' ');
+    }
+    chars.into_iter().collect()
+  }).collect();
+}
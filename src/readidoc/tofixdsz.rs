@@ -2,9 +2,10 @@
 // hierarchical format to fixed size format. Either from a set of files contained
 // within a folder or from an specific single file (2021-07-01 bar8tl)
 use crate::assets::IdoctpTp;
-use crate::readidoc::read_idocs::next_stage;
-use crate::readidoc::read_idocs::{StageTp, get_idoctp};
-use rblib::files_infolder::{FilelistTp, files_infolder};
+use crate::config::line_end;
+use crate::error::{defn_error, format_error, io_error, ErrorTp, Result, RunsmTp};
+use crate::readidoc::read_idocs::{run_inbatch, StageTp, get_idoctp};
+use rblib::files_infolder::FilelistTp;
 use rusqlite::Connection;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
@@ -31,6 +32,8 @@ pub const CREDAT : &str = "CREDAT";
 pub const CRETIM : &str = "CRETIM";
 pub const IDOCTYP: &str = "IDOCTYP";
 pub const CIMTYP : &str = "CIMTYP";
+pub const CONTROL: &str = "CONTROL";
+pub const STATUS : &str = "STATUS";
 
 // types.rs - Data structures used in IDOC file conversion to fixed size format
 // (2021-07-01 bar8tl)
@@ -63,30 +66,30 @@ pub struct ConvertTp {
   pub nsegm: usize,
   pub dirty: bool,
   pub parnt: Vec<HstrucTp>,
-  pub l    : usize
+  pub l    : usize,
+  pub lnctl: usize,          // Record lengths, from the IDoc definition
+  pub lndat: usize,
+  pub lnsts: usize,
+  pub lnend: String          // Line terminator
 }
 
 // flat_content_inbatch.rs - Start batch process to convert IDOC files from
 // structured hierarchical (parser file) format to fixed-size (flat) format
 // (2021-07-01 bar8tl)
 // Command line: emi step -s fixed <Idoc-hierachical-file>
-pub fn flat_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) {
-  let cnn = Connection::open(dbopt).expect("DB Error");
-  let flist: Vec<FilelistTp> = files_infolder(&st.wfstp.inpdr, &st.wfstp.inptp,
-    idoct);
-  for fl in &flist {
-    let rtncd = flat_content_onefile(&cnn, &st, &fl);
-    if st.wfstp.wkflw == "yes" {
-      next_stage(&rtncd, &st, &fl);
-    }
-  }
+pub fn flat_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) ->
+  Result<RunsmTp> {
+  return run_inbatch(dbopt, &st, idoct, flat_content_onefile);
 }
 
 // flat_idocs_onefile.rs - Convert individual IDOC file from classic hierarchical
-// format to flat text file format (2021-07-01 bar8tl)
+// format to flat text file format. Record lengths are those of the control, data
+// and status records in the IDoc definition, 40-series (EDI_DC40) or 30-series
+// (EDI_DC) as it was uploaded; the control record name is taken from the workflow
+// settings (2021-07-01 bar8tl)
 pub fn flat_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
-  String {
-  let it: IdoctpTp = get_idoctp(cnn, &st.wfhdr, &fl.flnam);
+  Result<String> {
+  let it: IdoctpTp = get_idoctp(cnn, &st.wfhdr, fl)?;
   let mut c = ConvertTp { ..Default::default() };
   c.cntrl = it.cntrl.clone();
   c.clien = it.clien.clone();
@@ -94,17 +97,21 @@ pub fn flat_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
   c.inpdr = st.wfstp.inpdr.clone();
   c.outdr = st.wfstp.outdr.clone();
   c.idocx = it.itype.to_uppercase();
-  c.idocb = get_idoc_basicid(cnn, &c.idocx);
+  c.idocb = get_idoc_basicid(cnn, &c.idocx)?;
+  c.lnctl = get_record_length(cnn, &c.idocx, CONTROL)?;
+  c.lndat = get_record_length(cnn, &c.idocx, DATA)?;
+  c.lnsts = get_record_length(cnn, &c.idocx, STATUS).unwrap_or(0);
+  c.lnend = line_end(&st.wfhdr.lnend).to_string();
   c.parnt.push(HstrucTp { .. Default::default() });
-  let mut lctrl = [' ';  524];
-  let mut lsegm = [' '; 1063];
-  let mut lstat = [' ';  562];
-  let mut of = File::create(format!("{}{}.{}", st.wfstp.outdr, fl.flnam,
-    st.wfstp.outtp)).expect("creation failed");
-  let ifile = File::open(&fl.flpth).unwrap();
+  let mut lctrl = vec![' '; c.lnctl];
+  let mut lsegm = vec![' '; c.lndat];
+  let mut lstat = vec![' '; c.lnsts];
+  let opath = format!("{}{}.{}", st.wfstp.outdr, fl.flnam, st.wfstp.outtp);
+  let mut of = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+  let ifile = File::open(&fl.flpth).map_err(|e| io_error(e, &fl.flpth))?;
   let rdr = BufReader::new(ifile);
-  for wlin in rdr.lines() {
-    let wlin = wlin.unwrap();
+  for (j, wlin) in rdr.lines().enumerate() {
+    let wlin = wlin.map_err(|e| io_error(e, &fl.flpth))?;
     let line = wlin.trim();
     let tokn: Vec<&str> = line.split('\t').collect();
     if line.len() == 0 { // ignores lines in blank
@@ -113,8 +120,12 @@ pub fn flat_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
 
     // Gets IDoc number
     if c.idocn.len() == 0 && tokn.len() == 1 &&
-       line[0..11] == "IDoc Number".to_string() {
+       line.starts_with("IDoc Number") {
       let idtkn: Vec<&str> = line.split(" : ").collect();
+      if idtkn.len() < 2 {
+        return Err(format_error("", "IDoc number not found".to_string())
+          .at(&fl.flpth, j+1));
+      }
       c.idocn = idtkn[1].trim().to_string();
       continue;
     }
@@ -127,7 +138,7 @@ pub fn flat_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
     // Determines data section to analyze
     if tokn[0] == EDIDC || tokn[0] == EDIDD || tokn[0] == EDIDS {
       prep_sectn_header(cnn, &mut c, &mut lctrl, &mut lsegm, &mut lstat, tokn,
-        &mut of);
+        &mut of).map_err(|e| e.at(&fl.flpth, j+1))?;
       continue;
     }
 
@@ -140,64 +151,67 @@ pub fn flat_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
 
     // Checks in segment name to analize
     if tokn[0] == SEGNAM && tokn.len() == 3 {
-      prep_segmt_header(cnn, &mut c, &mut lsegm, tokn, &mut of);
+      prep_segmt_header(cnn, &mut c, &mut lsegm, tokn, &mut of)
+        .map_err(|e| e.at(&fl.flpth, j+1))?;
       continue;
     }
 
     // Process fields of each data section
     if c.sectn == EDIDC {
-      build_edidc_line(cnn, &mut c, &mut lctrl, tokn);
+      build_edidc_line(cnn, &mut c, &mut lctrl, tokn)
+        .map_err(|e| e.at(&fl.flpth, j+1))?;
     } else if c.sectn == EDIDD {
-      build_edidd_line(cnn, &mut c, &mut lsegm, tokn);
+      build_edidd_line(cnn, &mut c, &mut lsegm, tokn)
+        .map_err(|e| e.at(&fl.flpth, j+1))?;
     } else if c.sectn == EDIDS {
       build_edids_line();
     }
   }
-  return OKAY.to_string();
+  return Ok(OKAY.to_string());
 }
 
 // prep_sectn_header.rs - Function to prepare measures to take for each data
 // section. Each new section causes dumping data from previous one
 // (2021-07-01 bar8tl)
 pub fn prep_sectn_header(cnn: &Connection, c: &mut ConvertTp,
-   lctrl: &mut [char;  524], lsegm: &mut [char; 1063], lstat: &mut [char;  562],
-   tokn: Vec<&str>, of: &mut File) {
+   lctrl: &mut Vec<char>, lsegm: &mut Vec<char>, lstat: &mut Vec<char>,
+   tokn: Vec<&str>, of: &mut File) -> Result<()> {
   c.sectn = tokn[0].to_string();
   if c.sectn == EDIDC {
-    *lctrl = [' '; 524];
+    *lctrl = vec![' '; c.lnctl];
   }
   if c.sectn == EDIDD {
-    write_cntrl_line(cnn, c, lctrl, of);
+    write_cntrl_line(cnn, c, lctrl, of)?;
   }
   if c.sectn == EDIDS {
     c.sgnbk = c.sgnum.clone();
-    write_segmt_line(cnn, c, lsegm, of);
-    *lstat = [' '; 562];
+    write_segmt_line(cnn, c, lsegm, of)?;
+    *lstat = vec![' '; c.lnsts];
     if tokn.len() == 3 {
       c.secnb = tokn[2].to_string();
     }
   }
+  return Ok(());
 }
 
 // prep_segmt_header.rs - Function to prepare measures to take for each data segment
 // in Data Idoc being converted (2021-07-01 bar8tl)
 pub fn prep_segmt_header(cnn: &Connection, c: &mut ConvertTp,
-  lsegm: &mut [char; 1063], tokn: Vec<&str>, of: &mut File) {
+  lsegm: &mut Vec<char>, tokn: Vec<&str>, of: &mut File) -> Result<()> {
   c.nsegm += 1;
   if c.nsegm > 1 {
-    write_segmt_line(cnn, c, lsegm, of);
+    write_segmt_line(cnn, c, lsegm, of)?;
   }
   c.sgnam = tokn[2].to_string();
-  *lsegm = [' '; 1063];
+  *lsegm = vec![' '; c.lndat];
   let mut level: usize = 0;
   cnn.query_row("SELECT dname, level FROM items WHERE idocn=?1 and rname=\"SEGMENT\"
     and dtype=?2;", [c.idocx.clone(), c.sgnam.clone()], |row| {
       Ok({
-        c.sgdsc = row.get(0).unwrap();
-        level   = row.get(1).unwrap();}
+        c.sgdsc = row.get(0)?;
+        level   = row.get(1)?;}
       )
-    }).expect("Error: Idoc type not found in definition DB"
-  );
+    }).map_err(|e| defn_error(e, &c.idocx, &c.sgnam, ""))?;
   c.sglvl = format!("{:02}", level);
 
   if c.nsegm == 1 {
@@ -217,12 +231,11 @@ pub fn prep_segmt_header(cnn: &Connection, c: &mut ConvertTp,
       c.parnt[c.l].sglvl = c.sglvl.clone();
       c.sghnb = c.parnt[c.l-1].sgnum.clone();
     } else {
-      let prvlv = c.parnt[c.l].sglvl.parse::<usize>().unwrap();
-      let curlv = c.sglvl.           parse::<usize>().unwrap();
-      let nstep = prvlv - curlv;
-      for _ in 1..nstep {
+      // Closes open segments by level, not by level difference: after segments
+      // skipping levels there are fewer open segments than levels to go up
+      while c.l > 1 && c.parnt[c.l-1].sglvl >= c.sglvl {
         c.l -= 1;
-        c.parnt = c.parnt[..c.l+1].to_vec();
+        c.parnt.truncate(c.l+1);
       }
       c.parnt[c.l].sgnum = c.sgnum.clone();
       c.parnt[c.l].sgnam = c.sgnam.clone();
@@ -230,12 +243,13 @@ pub fn prep_segmt_header(cnn: &Connection, c: &mut ConvertTp,
       c.sghnb = c.parnt[c.l-1].sgnum.clone();
     }
   }
+  return Ok(());
 }
 
 // build_edidc_line.rs - Build cumulatively the Control Record (EDIDC) output
 // line (2021-07-01 bar8tl)
 pub fn build_edidc_line(cnn: &Connection, c: &mut ConvertTp,
-  lctrl: &mut [char; 524], tokn: Vec<&str>) {
+  lctrl: &mut Vec<char>, tokn: Vec<&str>) -> Result<()> {
   let mut flkey = tokn[0];
   if flkey == RVCPRN {
     flkey = RCVPRN;
@@ -253,14 +267,15 @@ pub fn build_edidc_line(cnn: &Connection, c: &mut ConvertTp,
   }
   if flval.len() > 0 {
     c.dirty = true;
-    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, flkey, flval);
+    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, flkey, flval)?;
   }
+  return Ok(());
 }
 
 // build_edidd_line.rs - Build cumulatively the Data Segment (EDIDD) output
 // line (2021-07-01 bar8tl)
 pub fn build_edidd_line(cnn: &Connection, c: &mut ConvertTp,
-  lsegm: &mut [char; 1063], tokn: Vec<&str>) {
+  lsegm: &mut Vec<char>, tokn: Vec<&str>) -> Result<()> {
   let flkey = tokn[0];
   let mut flval = Default::default();
   if tokn.len() == 3 {
@@ -270,8 +285,9 @@ pub fn build_edidd_line(cnn: &Connection, c: &mut ConvertTp,
   if flval.len() > 0 {
     c.dirty = true;
     let sgdsc = c.sgdsc.clone();
-    append_field_tosegmt(cnn, &c.idocx, lsegm, sgdsc.as_str(), flkey, flval);
+    append_field_tosegmt(cnn, &c.idocx, lsegm, sgdsc.as_str(), flkey, flval)?;
   }
+  return Ok(());
 }
 
 // build_edids_line.rs - Build cumulatively the Status Record (EDIDC) output
@@ -283,82 +299,269 @@ pub fn build_edids_line() {
 // write_cntrl_line.rs - Complete output of control record line and address it to a
 // flat fixed size text file (2021-07-01 bar8tl)
 pub fn write_cntrl_line(cnn: &Connection, c: &mut ConvertTp,
-  lctrl: &mut [char; 524], of: &mut File) {
+  lctrl: &mut Vec<char>, of: &mut File) -> Result<()> {
   if c.dirty {
-    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, TABNAM, c.cntrl.clone());
-    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, MANDT , c.clien.clone());
-    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, DOCNUM, c.idocn.clone());
-    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, RCVPFC, c.rcvpf.clone());
-    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, SERIAL, c.serie.clone());
+    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, TABNAM, c.cntrl.clone())?;
+    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, MANDT , c.clien.clone())?;
+    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, DOCNUM, c.idocn.clone())?;
+    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, RCVPFC, c.rcvpf.clone())?;
+    append_field_tocntrl(cnn, &c.idocx, &c.idocb, lctrl, SERIAL, c.serie.clone())?;
     let oline: String = lctrl.iter().collect();
-    of.write_all(format!("{}\r\n", oline).as_bytes()).expect("write failed");
+    of.write_all(format!("{}{}", oline, c.lnend).as_bytes())?;
     c.dirty = false;
   }
+  return Ok(());
 }
 
 // write_segmt_line.rs - Complete output of data segment lines and address it to a
 // flat fixed size text file (2021-07-01 bar8tl)
 pub fn write_segmt_line(cnn: &Connection, c: &mut ConvertTp,
-  lsegm: &mut [char; 1063], of: &mut File) {
+  lsegm: &mut Vec<char>, of: &mut File) -> Result<()> {
   if c.dirty {
-    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, SEGNAM, c.sgdsc.clone());
-    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, MANDT , c.clien.clone());
-    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, DOCNUM, c.idocn.clone());
-    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, SEGNUM, c.sgnbk.clone());
-    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, PSGNUM, c.sghnb.clone());
-    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, HLEVEL, c.sglvl.clone());
+    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, SEGNAM, c.sgdsc.clone())?;
+    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, MANDT , c.clien.clone())?;
+    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, DOCNUM, c.idocn.clone())?;
+    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, SEGNUM, c.sgnbk.clone())?;
+    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, PSGNUM, c.sghnb.clone())?;
+    append_field_tosegmt(cnn, &c.idocx, lsegm, DATA, HLEVEL, c.sglvl.clone())?;
     let oline: String = lsegm.iter().collect();
-    of.write_all(format!("{}\r\n", oline).as_bytes()).expect("write failed");
+    of.write_all(format!("{}{}", oline, c.lnend).as_bytes())?;
     c.dirty = false;
   }
+  return Ok(());
 }
 
 // append_field_tocntrl.rs - Append a new field value to the output control record
 // line (2021-07-01 bar8tl)
 pub fn append_field_tocntrl(cnn: &Connection, idocx: &String, idocb: &String,
-  lctrl: &mut [char; 524], flkey: &str, mut flval: String) {
-  let mut strps: usize = 0;
-  cnn.query_row("SELECT strps FROM items WHERE idocn=?1 and rname=\"CONTROL\"
-    and dname=?2;", [idocx.to_string(), flkey.to_string()], |row| {
-      Ok(strps = row.get(0).unwrap()) })
-    .expect("Error: Idoc type not found in definition DB");
+  lctrl: &mut Vec<char>, flkey: &str, mut flval: String) -> Result<()> {
+  let (strps, endps): (usize, usize) = cnn.query_row("SELECT strps, endps FROM
+    items WHERE idocn=?1 and rname=\"CONTROL\" and dname=?2;",
+    [idocx.to_string(), flkey.to_string()], |row| Ok((row.get(0)?, row.get(1)?)))
+    .map_err(|e| defn_error(e, idocx, CONTROL, flkey))?;
   if flkey == IDOCTYP && flval == "14" {
     flval = idocb.to_string();
   }
   if flkey == CIMTYP  && flval == "14" {
     flval = idocx.to_string();
   }
-  let mut k: usize = strps - 1;
-  let temp: Vec<char> = flval.chars().collect();
-  for i in 0..temp.len() {
-    lctrl[k] = temp[i];
-    k += 1;
-  }
+  return put_field_value(lctrl, CONTROL, flkey, &flval, strps, endps);
 }
 
 // append_field_tosegmt.rs - Append a new field value to the output segment line
 // (2021-07-01 bar8tl)
 pub fn append_field_tosegmt(cnn: &Connection, idocx: &String,
-  lsegm: &mut [char; 1063], sgdsc: &str, flkey: &str, flval: String) {
-  let mut strps: usize = 0;
-  cnn.query_row("SELECT strps FROM items WHERE idocn=?1 and rname=?2 and dname=?3;",
-    [idocx.clone(), sgdsc.to_string(), flkey.to_string()], |row| {
-      Ok(strps = row.get(0).unwrap()) })
-    .expect("Error: Segment type not found in definition DB");
-  let mut k: usize = strps - 1;
-  let temp: Vec<char> = flval.chars().collect();
-  for i in 0..temp.len() {
-    lsegm[k] = temp[i];
-    k += 1;
+  lsegm: &mut Vec<char>, sgdsc: &str, flkey: &str, flval: String) ->
+  Result<()> {
+  let (strps, endps): (usize, usize) = cnn.query_row(
+    "SELECT strps, endps FROM items WHERE idocn=?1 and rname=?2 and dname=?3;",
+    [idocx.clone(), sgdsc.to_string(), flkey.to_string()],
+    |row| Ok((row.get(0)?, row.get(1)?)))
+    .map_err(|e| defn_error(e, idocx, sgdsc, flkey))?;
+  return put_field_value(lsegm, sgdsc, flkey, &flval, strps, endps);
+}
+
+// put_field_value.rs - Place field value in its positions of the output line.
+// Trailing blanks, as those padding codes in the structured listing, are left
+// to the blank line. A value not fitting in the field, or a field out of the
+// record, is reported with the field it belongs to (2021-07-01 bar8tl)
+pub fn put_field_value(line: &mut Vec<char>, rname: &str, flkey: &str,
+  flval: &String, strps: usize, endps: usize) -> Result<()> {
+  let temp: Vec<char> = flval.trim_end().chars().collect();
+  if strps == 0 || endps > line.len() || strps > endps {
+    return Err(format_error(rname, format!("Field {} at positions {}-{} out of \
+      record of length {}", flkey, strps, endps, line.len())));
+  }
+  if temp.len() > endps - strps + 1 {
+    return Err(format_error(rname, format!("Value '{}' overflows field {} of \
+      length {}", flval, flkey, endps - strps + 1)));
   }
+  line[strps-1..strps-1+temp.len()].copy_from_slice(&temp);
+  return Ok(());
+}
+
+// get_record_length.rs - Get length of a record (CONTROL, DATA, STATUS) as the
+// last position of its fields in the IDoc definition (2021-07-01 bar8tl)
+pub fn get_record_length(cnn: &Connection, idocx: &String, rname: &str) ->
+  Result<usize> {
+  let lngth: Option<usize> = cnn.query_row("SELECT max(endps) FROM items WHERE
+    idocn=?1 and rname=?2;", [idocx.to_uppercase(), rname.to_string()],
+    |row| row.get(0)).map_err(|e| defn_error(e, idocx, rname, ""))?;
+  return lngth.filter(|l| *l > 0).ok_or(ErrorTp::DefNotFound { file: String::new(),
+    line: 0, idocn: idocx.clone(), segmn: rname.to_string(), field: String::new() });
 }
 
 // get_idoc_basicid.rs - Retrieve the basi name of the IDOC type that is being
 // converted (2021-07-01 bar8tl)
-pub fn get_idoc_basicid(cnn: &Connection, idocx: &String) -> String {
-  let mut idocb: String = Default::default();
-  cnn.query_row("SELECT dname FROM items WHERE idocn=?1 and rname=\"IDOC\";",
-    [idocx.to_uppercase()], |row| { Ok(idocb = row.get(0).unwrap()) })
-    .expect("Error: Idoc type not found in definition DB");
-  return idocb;
+pub fn get_idoc_basicid(cnn: &Connection, idocx: &String) -> Result<String> {
+  let idocb: String = cnn.query_row(
+    "SELECT dname FROM items WHERE idocn=?1 and rname=\"IDOC\";",
+    [idocx.to_uppercase()], |row| row.get(0))
+    .map_err(|e| defn_error(e, idocx, "", ""))?;
+  return Ok(idocb);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::definitn::add_definitn;
+  use crate::readidoc::samples::{fixed_lines, sample_file, sample_path, sample_repo,
+    sample_stage, work_folder, FIXED, IDEFN, ITYPE, SHORT, STRUC};
+  use crate::reposit::add_table;
+  use std::fs::{read_to_string, write};
+
+  // convert.rs - Convert a structured file into an output work folder with the
+  // line end given, returning result and output path
+  fn convert(dbopt: &String, fl: &FilelistTp, lnend: &str) -> (Result<String>,
+    String) {
+    let mut st = sample_stage(&fl.fldir, &work_folder(&format!("fixed-{}-out",
+      lnend)));
+    st.wfstp.outtp = "txt".to_string();
+    st.wfhdr.lnend = lnend.to_string();
+    let rslt = flat_content_onefile(&Connection::open(dbopt).unwrap(), &st, fl);
+    return (rslt, format!("{}{}.txt", st.wfstp.outdr, fl.flnam));
+  }
+
+  // edited_file.rs - Structured sample with a change, in a work folder
+  fn edited_file(wrkdr: &String, from: &str, to: &str) -> FilelistTp {
+    let fl = sample_file(STRUC, wrkdr);
+    let text = read_to_string(&fl.flpth).unwrap();
+    assert!(text.contains(from));
+    write(&fl.flpth, text.replacen(from, to, 1)).unwrap();
+    return fl;
+  }
+
+  #[test]
+  fn get_record_length_takes_last_position_of_record() {
+    let dbopt = sample_repo(&work_folder("fixed-lengths"));
+    let cnn = Connection::open(dbopt).unwrap();
+    let idocx = ITYPE.to_lowercase();
+    assert_eq!(get_record_length(&cnn, &idocx, CONTROL).unwrap(), 524);
+    assert_eq!(get_record_length(&cnn, &idocx, DATA).unwrap(), 1063);
+    assert!(matches!(get_record_length(&cnn, &idocx, STATUS),
+      Err(ErrorTp::DefNotFound { .. })));
+  }
+
+  #[test]
+  fn put_field_value_reports_overflowing_field() {
+    let mut line = vec![' '; 10];
+    put_field_value(&mut line, DATA, "F1", &"ABC".to_string(), 2, 4).unwrap();
+    put_field_value(&mut line, DATA, "F2", &"1  ".to_string(), 5, 5).unwrap();
+    assert_eq!(line.iter().collect::<String>(), " ABC1     ");
+    let err = |strps: usize, endps: usize| put_field_value(&mut vec![' '; 10],
+      DATA, "F1", &"ABCD".to_string(), strps, endps).unwrap_err().to_string();
+    assert!(err(2, 4).contains("Value 'ABCD' overflows field F1 of length 3"));
+    for (strps, endps) in [(9, 12), (0, 3), (5, 4)] {
+      assert!(err(strps, endps).contains(&format!(
+        "Field F1 at positions {}-{} out of record of length 10", strps, endps)));
+    }
+  }
+
+  #[test]
+  fn flat_content_onefile_converts_structured_sample() {
+    let wrkdr = work_folder("fixed-sample");
+    let dbopt = sample_repo(&wrkdr);
+    let fl = sample_file(STRUC, &wrkdr);
+    let (rslt, opath) = convert(&dbopt, &fl, "lf");
+    assert_eq!(rslt.unwrap(), OKAY);
+    let mut expct = fixed_lines(&sample_path(FIXED));
+    // receiver partner in the structured listing and partner function of the
+    // workflow, where the fixed size sample has others
+    let mut cntrl: Vec<char> = expct[0].chars().collect();
+    cntrl[275..287].copy_from_slice(&"RERBRBME    ".chars().collect::<Vec<char>>());
+    expct[0] = cntrl.into_iter().collect();
+    assert_eq!(fixed_lines(&opath), expct);
+    let (rslt, opath) = convert(&dbopt, &fl, "crlf");
+    rslt.unwrap();
+    let odata = read_to_string(&opath).unwrap();
+    assert_eq!(odata.matches("\r\n").count(), expct.len() - 1);
+    assert_eq!(odata.matches('\n').count(), expct.len() - 1);
+  }
+
+  #[test]
+  fn flat_content_onefile_reports_value_overflowing_field() {
+    let wrkdr = work_folder("fixed-overflow");
+    let dbopt = sample_repo(&wrkdr);
+    let fl = edited_file(&wrkdr, "\tCurrency\tUSD\n", "\tCurrency\tUSDX\n");
+    let e = convert(&dbopt, &fl, "lf").0.unwrap_err().to_string();
+    assert!(e.contains("Value 'USDX' overflows field CURCY of length 3"), "{}", e);
+    assert!(e.contains(&fl.flpth), "{}", e);
+  }
+
+  #[test]
+  fn flat_content_onefile_closes_segments_after_level_jump() {
+    let wrkdr = work_folder("fixed-jump");
+    let dbopt = sample_repo(&wrkdr);
+    // first segment at level 3, followed by the level 1 header segment
+    let fl = edited_file(&wrkdr, "\tSEGNUM\tSegment Number\t000001\n",
+      "\tSEGNUM\tSegment Number\t000000\n\tSEGNAM\tSegment Name\tE1EDPA1\n\
+      \tPARVW\tPartner function\tWE\n\tSEGNUM\tSegment Number\t000001\n");
+    let (rslt, opath) = convert(&dbopt, &fl, "lf");
+    assert_eq!(rslt.unwrap(), OKAY);
+    let lines = fixed_lines(&opath);
+    assert_eq!(lines.len(), fixed_lines(&sample_path(FIXED)).len() + 1);
+    assert!(lines[1].starts_with("E2EDPA1003"));
+    assert!(lines[2].starts_with("E2EDK01005"));
+    assert_eq!(&lines[2][61..63], "01");
+  }
+
+  // edi_dc_definition.rs - Sample IDoc definition with a control record laid out
+  // as in 30-series EDI_DC: receiver fields ahead of message fields and message
+  // and basic types of 6 and 8 characters, shorter than the EDI_DC40 one
+  fn edi_dc_definition(wrkdr: &String) -> String {
+    let fields = [("TABNAM", 10), ("MANDT", 3), ("DOCNUM", 16), ("DOCREL", 4),
+      ("STATUS", 2), ("DOCTYP", 8), ("DIRECT", 1), ("RCVPOR", 10), ("RCVPRT", 2),
+      ("RCVPRN", 10), ("RCVSAD", 21), ("RCVLAD", 70), ("STD", 1), ("STDVRS", 6),
+      ("STDMES", 6), ("MESCOD", 3), ("MESFCT", 3), ("OUTMOD", 1), ("TEST", 1),
+      ("SNDPOR", 10), ("SNDPRT", 2), ("SNDPRN", 10), ("SNDSAD", 21),
+      ("SNDLAD", 70), ("REFINT", 14), ("REFGRP", 14), ("REFMES", 14),
+      ("ARCKEY", 70), ("CREDAT", 8), ("CRETIM", 6), ("MESTYP", 6), ("IDOCTYP", 8),
+      ("CIMTYP", 30), ("RCVPFC", 2), ("SNDPFC", 2), ("SERIAL", 20), ("EXPRSS", 1)];
+    let mut block = "  BEGIN_CONTROL_RECORD\n    BEGIN_FIELDS\n".to_string();
+    let mut endps = 0;
+    for (i, (name, lngth)) in fields.iter().enumerate() {
+      for (key, value) in [("NAME", name.to_string()), ("TEXT", name.to_string()),
+        ("TYPE", "CHARACTER".to_string()), ("LENGTH", format!("{:06}", lngth)),
+        ("FIELD_POS", format!("{:04}", i + 1)),
+        ("CHARACTER_FIRST", format!("{:06}", endps + 1)),
+        ("CHARACTER_LAST", format!("{:06}", endps + lngth))] {
+        block.push_str(&format!("      {:20}{}\n", key, value));
+      }
+      block.push('\n');
+      endps += lngth;
+    }
+    block.push_str("    END_FIELDS\n  END_CONTROL_RECORD");
+    let text = read_to_string(sample_path(IDEFN)).unwrap();
+    let (head, rest) = text.split_once("  BEGIN_CONTROL_RECORD").unwrap();
+    let (_, tail) = rest.split_once("  END_CONTROL_RECORD").unwrap();
+    let dpath = format!("{}edi_dc_definition.txt", wrkdr);
+    write(&dpath, format!("{}{}{}", head, block, tail)).unwrap();
+    return dpath;
+  }
+
+  #[test]
+  fn flat_content_onefile_takes_30_series_control_record() {
+    let wrkdr = work_folder("fixed-edidc");
+    let dbopt = format!("{}edimaps.db", wrkdr);
+    let cnn = Connection::open(&dbopt).unwrap();
+    for table in ["items", "struc", "idoctp"] {
+      add_table(&cnn, table).unwrap();
+    }
+    add_definitn(&dbopt, &edi_dc_definition(&wrkdr)).unwrap();
+    cnn.execute("INSERT INTO idoctp VALUES (?1, '', ?2, 'EDI_DC', '', '');",
+      [ITYPE, SHORT]).unwrap();
+    let idocx = ITYPE.to_string();
+    assert_eq!(get_record_length(&cnn, &idocx, CONTROL).unwrap(), 486);
+    let fl = sample_file(STRUC, &wrkdr);
+    let (rslt, opath) = convert(&dbopt, &fl, "lf");
+    assert_eq!(rslt.unwrap(), OKAY);
+    let lines = fixed_lines(&opath);
+    let cntrl = &lines[0];
+    assert_eq!(cntrl.len(), 486);
+    assert_eq!(&cntrl[..29], "EDI_DC    0110000002382843157");
+    assert_eq!(&cntrl[56..66], "RBRBME    ");
+    assert_eq!(&cntrl[417..431], "INVOICINVOIC02");
+    assert_eq!(&cntrl[431..461], format!("{:30}", ITYPE));
+    assert_eq!(lines[1..], fixed_lines(&sample_path(FIXED))[1..]);
+  }
 }
@@ -3,11 +3,14 @@
 // from an specific single file (2021-07-01 bar8tl)
 use crate::assets::IdoctpTp;
 use crate::definitn::{OutitmTp, OutstrTp};
-use crate::readidoc::read_idocs::next_stage;
-use crate::readidoc::read_idocs::{StageTp, get_idoctp};
-use rblib::files_infolder::{FilelistTp, files_infolder};
+use crate::error::{defn_error, format_error, io_error, json_error, Result, RunsmTp};
+use crate::readidoc::decode::{get_decoder, DecodTp};
+use crate::readidoc::read_idocs::{run_inbatch, StageTp, get_idoctp};
+use crate::readidoc::toxml::write_xml_file;
+use crate::readidoc::validate::EDI_DC;
+use rblib::files_infolder::FilelistTp;
 use rusqlite::Connection;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
@@ -23,9 +26,8 @@ pub const SDATA   : &str = "SDATA";
 pub const SEGNAM  : &str = "SEGNAM";
 pub const SGM     : &str = "SGM";
 pub const QUALF   : &str = "QUALF";
-pub const SAME    : &str = "SAME";
-pub const LOWER   : &str = "LOWER";
-pub const UPPER   : &str = "UPPER";
+pub const JSON    : &str = "json";
+pub const XML     : &str = "xml";
 
 // Flags
 pub const OUTCTRL : bool = false;
@@ -34,10 +36,12 @@ pub const OUTSEGM : bool = true;
 
 // types.rs - Data structures used in IDOC file conversion to JSON format
 // (2021-07-01 bar8tl)
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FieldTp {
   pub key: String,
-  pub val: String
+  pub val: String,
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub dtext: String          // Description of coded value
 }
 
 #[derive(Debug, Clone, Default, Serialize)]
@@ -60,7 +64,7 @@ pub struct LdataTp {
   pub sdata: Vec<SdataTp>
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RsegmTp {
   pub segmn: String,
   pub recno: usize,
@@ -72,14 +76,14 @@ pub struct RsegmTp {
   pub child: Vec<RsegmTp>
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SsegmTp {
   pub instn: usize,
   pub cntrl: Vec<FieldTp>,
   pub rsegm: Vec<RsegmTp>
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LsegmTp {
   pub ssegm: Vec<SsegmTp>
 }
@@ -105,12 +109,6 @@ pub struct LctrlTp {
   pub rctrl: Vec<RctrlTp>
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
-pub struct CountTp {
-  pub segmn: String,
-  pub instn: usize
-}
-
 // Data structures for functions of conversin to JSON format
 #[derive(Debug, Clone, Default)]
 pub struct DidocTp {
@@ -133,39 +131,37 @@ pub struct DidocTp {
   pub ssegm: SsegmTp, // Segmentset
   pub lsegm: LsegmTp, // Segmentset list
   pub sfild: SfildTp,
-  pub count: [Vec<CountTp>; 9],
-  pub l    : i32,
-  pub c1   : i32,
-  pub c2   : i32,
-  pub c3   : i32,
-  pub c4   : i32,
-  pub c5   : i32,
-  pub c6   : i32,
-  pub c7   : i32,
-  pub c8   : i32
+  pub stack: Vec<usize>, // Child index of each open node, from root to last segm
+  pub outfm: String,     // Output format: json or xml
+  pub decod: DecodTp,    // Descriptions of coded values
+  pub lvjmp: Vec<(usize, String)> // Line and segment skipping hierarchy levels
 }
 
 // json_idocs_inbatch.rs - Start batch process to convert IDOC files from fixed size
 // (flat file) format to JSON hierarchical format (2021-07-01 bar8tl)
 // Command lne: emi step -s json <IDOC-fxdsz-file>
-pub fn json_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) {
-  let cnn = Connection::open(dbopt).expect("DB Error");
-  let flist: Vec<FilelistTp> = files_infolder(&st.wfstp.inpdr, &st.wfstp.inptp,
-    idoct);
-  for fl in &flist {
-    let rtncd = json_content_onefile(&cnn, &st, &fl);
-    if st.wfstp.wkflw == "yes" {
-      next_stage(&rtncd, &st, &fl);
-    }
-  }
+pub fn json_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) ->
+  Result<RunsmTp> {
+  return run_inbatch(dbopt, &st, idoct, json_content_onefile);
 }
 
 // json_content_onefile.rs - Convert individual IDOC file from fixed size flat
 // format to JSON hierarchical format (2021-07-01 bar8tl)
 pub fn json_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
-  String {
-  let it: IdoctpTp = get_idoctp(cnn, &st.wfhdr, &fl.flnam);
+  Result<String> {
+  return conv_content_onefile(cnn, st, fl, JSON);
+}
+
+// conv_content_onefile.rs - Parse individual IDOC file in fixed size flat format
+// into control and segment structures, writing them in the requested output format.
+// Segments skipping hierarchy levels are kept in the output, and the file is
+// reported with the first of them (2021-07-01 bar8tl)
+pub fn conv_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp,
+  outfm: &str) -> Result<String> {
+  let it: IdoctpTp = get_idoctp(cnn, &st.wfhdr, fl)?;
   let mut d = DidocTp { ..Default::default() };
+  d.outfm = outfm.to_string();
+  d.decod = get_decoder(cnn)?;
   //d.dbopt = dbopt.clone();
   d.inpdr = st.wfstp.inpdr.clone();
   d.outdr = st.wfstp.outdr.clone();
@@ -178,58 +174,72 @@ pub fn json_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
   d.recnf =  0; // Initialize Number of data records in the file
   let mut cnt  : usize = 0;
   let mut first: bool  = true;
-  let ifile = File::open(d.inppt.clone()).unwrap();
+  let ifile = File::open(&d.inppt).map_err(|e| io_error(e, &fl.flpth))?;
   let rdr = BufReader::new(ifile);
   for wline in rdr.lines() {
-    let iline = wline.unwrap();
+    let iline = wline.map_err(|e| io_error(e, &fl.flpth))?;
     cnt += 1;
     if cnt == 1usize {
-      if &iline[0..8] == EDI_DC40 {
-        format_cntrl_record(cnn, &mut d, &iline, &it.itype, CONTROL, &mut first);
+      if iline.starts_with(EDI_DC) { // EDI_DC40 or 30-series EDI_DC
+        format_cntrl_record(cnn, &mut d, &iline, &it.itype, CONTROL, &mut first)
+          .map_err(|e| e.at(&fl.flpth, cnt))?;
       } else {
-        println!("IDOC File {} should start with Control Record", d.flide);
-        return RC01.to_string()
+        return Err(format_error("", "IDoc file should start with Control Record"
+          .to_string()).at(&fl.flpth, cnt));
       }
     } else {
-      format_data_record(cnn, &mut d, &iline, &it.itype, DATA);
+      format_data_record(cnn, &mut d, &iline, &it.itype, DATA)
+        .map_err(|e| e.at(&fl.flpth, cnt))?;
     }
   }
   if cnt == 0usize {
-    println!("Input IDOC file %s is empty: {}", d.flide);
-    return RC01.to_string();
+    return Err(format_error("", "Input IDoc file is empty".to_string())
+      .at(&fl.flpth, 0));
+  }
+  write_output_file(cnn, &mut d)?;
+  if let Some((line, segmn)) = d.lvjmp.first() {
+    return Err(format_error(segmn, format!("Segment skips hierarchy levels, added \
+      under last open segment ({} such segments in file)", d.lvjmp.len()))
+      .at(&fl.flpth, *line));
   }
-  write_json_file(&mut d);
-  return OKAY.to_string();
+  return Ok(OKAY.to_string());
 }
 
 // format_cntrl_record - Read Control Record line and prepare JSON output
 // (2021-07-01 bar8tl)
 pub fn format_cntrl_record(cnn: &Connection, d: &mut DidocTp, iline: &str,
-  idocn: &String, rname: &str, first: &mut bool) {
+  idocn: &String, rname: &str, first: &mut bool) -> Result<()> {
   let mut f    : OutitmTp = OutitmTp{ ..Default::default() };
   let mut rctrl: RctrlTp  = RctrlTp { ..Default::default() };
+  let chars: Vec<char> = iline.chars().collect(); // Positions in characters
   if *first {
     *first = false;
   } else {
-    write_json_file(d);
+    write_output_file(cnn, d)?;
   }
   d.recno  = 0; // Inits at Control Record level
-  d.l      = -1;
-  (d.c1, d.c2, d.c3, d.c4, d.c5, d.c6, d.c7, d.c8) = (-1,-1,-1,-1,-1,-1,-1,-1);
+  d.stack  = Vec::new();
   d.setno += 1;
   d.recnf += 1;
   let mut stmt = cnn.prepare("SELECT dname, strps, endps FROM items WHERE idocn=?1
-    and rname=?2 order by seqno;").expect("DB Err");
-  let mut rows = stmt.query([idocn, &rname.to_string(),]).expect("DB Err");
-  while let Some(row) = rows.next().expect("while row failed") {
-    f.dname = row.get(0).unwrap();
-    f.strps = row.get(1).unwrap();
-    f.endps = row.get(2).unwrap();
-    let cdval: String = iline[f.strps-1..f.endps].trim().to_string();
+    and rname=?2 order by seqno;")?;
+  let mut rows = stmt.query([idocn, &rname.to_string(),])?;
+  while let Some(row) = rows.next()? {
+    f.dname = row.get(0)?;
+    f.strps = row.get(1)?;
+    f.endps = row.get(2)?;
+    if f.endps >= chars.len() {
+      f.endps = chars.len();
+    }
+    if f.strps > f.endps {
+      continue
+    }
+    let cdval: String = chars[f.strps-1..f.endps].iter().collect::<String>().trim()
+      .to_string();
     if cdval.len() == 0 || cdval == "" {
       continue
     }
-    rctrl.field.push(FieldTp { key: f.dname, val: cdval });
+    rctrl.field.push(FieldTp { key: f.dname, val: cdval, ..Default::default() });
   }
   rctrl.instn = d.setno as usize;
   d.lctrl.rctrl.push(rctrl);
@@ -237,28 +247,34 @@ pub fn format_cntrl_record(cnn: &Connection, d: &mut DidocTp, iline: &str,
     qlkey: String::new(), qlval: String::new(), instn: 0, field: Vec::new(),
     child: Vec::new()
   };
+  return Ok(());
 }
 
 // read_data_record.rs - Read Data record line and prepare JSON output for pure
 // segment metadata portion (2021-07-01 bar8tl)
 pub fn format_data_record(cnn: &Connection, d: &mut DidocTp, iline: &str,
-  idocn: &String, rname: &str) {
+  idocn: &String, rname: &str) -> Result<()> {
   let mut f    : OutitmTp = OutitmTp { ..Default::default() };
   let mut g    : OutitmTp = OutitmTp { ..Default::default() };
   let mut rdata: RdataTp  = RdataTp  { ..Default::default() };
+  let chars: Vec<char> = iline.chars().collect();
   d.recnf += 1;
   d.recno += 1;
   let mut stmt = cnn.prepare("SELECT dname, strps, endps FROM items WHERE idocn=?1
-    AND rname=?2 order by seqno;").expect("DB Err");
-  let mut rows = stmt.query([idocn, &rname.to_string(),]).expect("DB Err");
-  while let Some(row) = rows.next().expect("while row failed") {
-    f.dname = row.get(0).unwrap();
-    f.strps = row.get(1).unwrap();
-    f.endps = row.get(2).unwrap();
-    if f.endps >= iline.len() {
-      f.endps = iline.len();
+    AND rname=?2 order by seqno;")?;
+  let mut rows = stmt.query([idocn, &rname.to_string(),])?;
+  while let Some(row) = rows.next()? {
+    f.dname = row.get(0)?;
+    f.strps = row.get(1)?;
+    f.endps = row.get(2)?;
+    if f.endps >= chars.len() {
+      f.endps = chars.len();
+    }
+    if f.strps > f.endps {
+      continue
     }
-    let cdval: String = iline[f.strps-1..f.endps].trim().to_string();
+    let cdval: String = chars[f.strps-1..f.endps].iter().collect::<String>().trim()
+      .to_string();
     if cdval.len() == 0 || cdval == "" {
       continue
     }
@@ -266,231 +282,161 @@ pub fn format_data_record(cnn: &Connection, d: &mut DidocTp, iline: &str,
       cnn.query_row("SELECT dname, dtype, dtext, level FROM items WHERE idocn=?1
         AND dname=?2 AND rname=\"SEGMENT\";", [idocn, &cdval,], |row| {
         Ok({
-          g.dname = row.get(0).unwrap();
-          g.dtype = row.get(1).unwrap();
-          g.dtext = row.get(2).unwrap();
-          g.level = row.get(3).unwrap();
+          g.dname = row.get(0)?;
+          g.dtype = row.get(1)?;
+          g.dtext = row.get(2)?;
+          g.level = row.get(3)?;
         })
-      }).expect("DB Err");
+      }).map_err(|e| defn_error(e, idocn, &cdval, ""))?;
       rdata.segmn = g.dtype.clone();
       rdata.qualf = g.dtext.clone();
       rdata.level = g.level.clone();
       rdata.recno = d.recno.clone();
     }
     if f.dname == SDATA {
-      calc_segmt_counters(cnn, d, iline, idocn, &g.dname, rdata.level);
+      add_tostruct(cnn, d, iline, idocn, g.dname.clone(), rdata.level)?;
       continue;
     }
-    rdata.field.push(FieldTp{ key: f.dname, val: cdval });
+    rdata.field.push(FieldTp{ key: f.dname, val: cdval, ..Default::default() });
   }
   d.sdata.rdata.push(rdata);
+  return Ok(());
 }
 
-// calc_segmt_counters.rs - Process segment data (2021-07-01 bar8tl)
-// proc_segmt.rs: Process Segment Data - Determines segment Qualifier and Instance
-// Number
-pub fn calc_segmt_counters(cnn: &Connection, d: &mut DidocTp, iline: &str,
-  idocn: &String, cdnam: &String, level: usize) {
-  let mut instn: i32    = -1;
-  let mut ident: String = String::new();
-  if level == d.l as usize {
-    instn = updt_counter(d, cdnam.to_string(), d.l as usize);
-    ident = SAME.to_string();
-  } else if level > d.l as usize || d.l < 0 {
-    d.l = level as i32;
-    d.count[d.l as usize].push(CountTp { segmn: cdnam.to_string(), instn: 1 });
-    instn = rtrv_counter(d, cdnam.to_string(), d.l as usize);
-    ident = LOWER.to_string();
-  } else if level < d.l as usize {
-    let goupl: usize = d.l as usize - level;
-    for _ in 0..goupl {
-      d.count[d.l as usize] = Default::default();
-      d.l -= 1;
-    }
-    instn = updt_counter(d, cdnam.to_string(), d.l as usize);
-    ident = UPPER.to_string();
-  }
-  add_tostruct(cnn, d, iline, idocn, ident, cdnam.to_string(), d.l, instn as usize);
-}
-
-// updt_counter.rs: Update counter of segment with equal segment ID in the current
-// struct level
-pub fn updt_counter(d: &mut DidocTp, segmn: String, l: usize) -> i32 {
-  for j in 0..d.count[l].len() {
-    if d.count[l][j].segmn == segmn {
-      d.count[l][j].instn += 1;
-      return d.count[l][j].instn as i32;
-    }
+// add_tostruct.rs - Build segment structure into the segment tree. The stack keeps
+// the child index of each open node from the root down to the last segment added,
+// so IDocs of any hierarchy depth and record count are fully converted. A segment
+// skipping levels goes under the last open segment, and is recorded
+// (2021-07-01 bar8tl)
+pub fn add_tostruct(cnn: &Connection, d: &mut DidocTp, iline: &str, idocn: &String,
+  segmn: String, level: usize) -> Result<()> {
+  d.sfild.qlkey = "".to_string();
+  d.sfild.qlval = "".to_string();
+  d.sfild.field = Default::default();
+  get_segmt_fields(cnn, d, iline, idocn, SGM.to_string(), &segmn)?;
+  let mut depth: usize = if level > 0 { level - 1 } else { 0 };
+  if depth > d.stack.len() {
+    d.lvjmp.push((d.recnf, segmn.clone()));
+    depth = d.stack.len();
   }
-  d.count[l].push(CountTp{ segmn: segmn, instn: 1 });
-  return 1;
+  d.stack.truncate(depth);
+  let child = push_segmt_node(&mut d.rsegm, &d.stack, RsegmTp {
+    segmn: segmn, recno: d.recno, level: level, qlkey: d.sfild.qlkey.clone(),
+    qlval: d.sfild.qlval.clone(), instn: 0, field: d.sfild.field.clone(),
+    child: Default::default() });
+  d.stack.push(child);
+  return Ok(());
 }
 
-// rtrv_counter.rs: Retrieve last counter of segment with equal segm ID in the
-// current struct lvl
-pub fn rtrv_counter(d: &mut DidocTp, segmn: String, l: usize) -> i32 {
-  for j in 0..d.count[l].len() {
-    if d.count[l][j].segmn == segmn {
-      return d.count[l][j].instn as i32;
-    }
+// push_segmt_node.rs - Append a segment node as last child of the node addressed
+// by the stack of child indexes. Instance number is determined counting previous
+// siblings with same segment ID. Returns index of the new child
+// (2021-07-01 bar8tl)
+pub fn push_segmt_node(root: &mut RsegmTp, stack: &[usize], mut rsegm: RsegmTp) ->
+  usize {
+  let mut parnt = root;
+  for i in stack {
+    parnt = &mut parnt.child[*i];
   }
-  return 0;
+  rsegm.instn = parnt.child.iter().filter(|c| c.segmn == rsegm.segmn).count() + 1;
+  parnt.child.push(rsegm);
+  return parnt.child.len() - 1;
 }
 
-// add_tostruct.rs - Build segment structure into an non-linked segment node
+// write_output_file.rs - Write output of the IDoc data set in the requested format
 // (2021-07-01 bar8tl)
-pub fn add_tostruct(cnn: &Connection, d: &mut DidocTp, iline: &str, idocn: &String,
-   _ident: String, segmn: String, l: i32, instn: usize) {
-  if d.recno <= 9999 {
-    d.sfild.qlkey = "".to_string();
-    d.sfild.qlval = "".to_string();
-    d.sfild.field = Default::default();
-    get_segmt_fields(cnn, d, iline, idocn, SGM.to_string(), &segmn);
-    if l == 1 {
-      d.rsegm.child.push(RsegmTp {
-        segmn: segmn, recno: d.recno, level: l as usize,
-        qlkey: d.sfild.qlkey.clone(), qlval: d.sfild.qlval.clone(), instn: instn,
-        field: d.sfild.field.clone(), child: Default::default() });
-      (d.c2, d.c3, d.c4, d.c5, d.c6, d.c7, d.c8) = (-1, -1, -1, -1, -1, -1, -1);
-      d.c1 += 1;
-    } else if l == 2 {
-      d.rsegm.child[d.c1 as usize].child.push(RsegmTp {
-        segmn: segmn, recno: d.recno, level: l as usize,
-        qlkey: d.sfild.qlkey.clone(), qlval: d.sfild.qlval.clone(), instn: instn,
-        field: d.sfild.field.clone(), child: Default::default() });
-      (d.c3, d.c4, d.c5, d.c6, d.c7, d.c8) = (-1, -1, -1, -1, -1, -1);
-      d.c2 += 1;
-    } else if l == 3 {
-      d.rsegm.child[d.c1 as usize].child[d.c2 as usize].child.push(RsegmTp {
-        segmn: segmn, recno: d.recno, level: l as usize,
-        qlkey: d.sfild.qlkey.clone(), qlval: d.sfild.qlval.clone(), instn: instn,
-        field: d.sfild.field.clone(), child: Default::default() });
-      (d.c4, d.c5, d.c6, d.c7, d.c8) = (-1, -1, -1, -1, -1);
-      d.c3 += 1;
-    } else if l == 4 {
-      d.rsegm.child[d.c1 as usize].child[d.c2 as usize].child[d.c3 as usize].
-        child.push(RsegmTp {
-        segmn: segmn, recno: d.recno, level: l as usize,
-        qlkey: d.sfild.qlkey.clone(), qlval: d.sfild.qlval.clone(), instn: instn,
-        field: d.sfild.field.clone(), child: Default::default() });
-      (d.c5, d.c6, d.c7, d.c8) = (-1, -1, -1, -1);
-      d.c4 += 1;
-    } else if l == 5 {
-      d.rsegm.child[d.c1 as usize].child[d.c2 as usize].child[d.c3 as usize].
-        child[d.c4 as usize].child.push(RsegmTp {
-        segmn: segmn, recno: d.recno, level: l as usize,
-        qlkey: d.sfild.qlkey.clone(), qlval: d.sfild.qlval.clone(), instn: instn,
-        field: d.sfild.field.clone(), child: Default::default() });
-      (d.c6, d.c7, d.c8) = (-1, -1, -1);
-      d.c5 += 1;
-    } else if l == 6 {
-      d.rsegm.child[d.c1 as usize].child[d.c2 as usize].child[d.c3 as usize].
-        child[d.c4 as usize].child[d.c5 as usize].child.push(RsegmTp {
-        segmn: segmn, recno: d.recno, level: l as usize,
-        qlkey: d.sfild.qlkey.clone(), qlval: d.sfild.qlval.clone(), instn: instn,
-        field: d.sfild.field.clone(), child: Default::default() });
-      (d.c7, d.c8) = (-1, -1);
-      d.c6 += 1;
-    } else if l == 7 {
-      d.rsegm.child[d.c1 as usize].child[d.c2 as usize].child[d.c3 as usize].
-        child[d.c4 as usize].child[d.c5 as usize].child[d.c6 as usize].
-        child.push(RsegmTp {
-        segmn: segmn, recno: d.recno, level: l as usize,
-        qlkey: d.sfild.qlkey.clone(), qlval: d.sfild.qlval.clone(), instn: instn,
-        field: d.sfild.field.clone(), child: Default::default() });
-      (d.c8) = -1;
-      d.c7 += 1;
-    } else if l == 8 {
-      d.rsegm.child[d.c1 as usize].child[d.c2 as usize].child[d.c3 as usize].
-        child[d.c4 as usize].child[d.c5 as usize].child[d.c6 as usize].
-        child[d.c7 as usize].child.push(RsegmTp {
-        segmn: segmn, recno: d.recno, level: l as usize,
-        qlkey: d.sfild.qlkey.clone(), qlval: d.sfild.qlval.clone(), instn: instn,
-        field: d.sfild.field.clone(), child: Default::default() });
-      d.c8 += 1;
-    } else if l == 9 {
-      d.rsegm.child[d.c1 as usize].child[d.c2 as usize].child[d.c3 as usize].
-        child[d.c4 as usize].child[d.c5 as usize].child[d.c6 as usize].
-        child[d.c7 as usize].child[d.c8 as usize].child.push(RsegmTp {
-        segmn: segmn, recno: d.recno, level: l as usize,
-        qlkey: d.sfild.qlkey.clone(), qlval: d.sfild.qlval.clone(), instn: instn,
-        field: d.sfild.field.clone(), child: Default::default() });
-    }
+pub fn write_output_file(cnn: &Connection, d: &mut DidocTp) -> Result<()> {
+  if d.outfm == XML {
+    return write_xml_file(cnn, d);
   }
+  return write_json_file(d);
 }
 
-// write_json_file.rs - Write JSON output for Control, Data and Segment structures
-// (2021-07-01 bar8tl)
-pub fn write_json_file(d: &mut DidocTp) {
+// write_json_file.rs - Write JSON output for Control, Data and Segment structures.
+// Coded values of the segment structure get their descriptions (2021-07-01 bar8tl)
+pub fn write_json_file(d: &mut DidocTp) -> Result<()> {
   d.ldata.sdata.push(SdataTp {
     instn: d.setno as usize,
     rdata: d.sdata.rdata.clone()
   });
+  let mut rsegm = d.rsegm.clone();
+  d.decod.annotate_tree(&mut rsegm);
   d.ssegm.rsegm.push(
-    d.rsegm.clone()
+    rsegm
   );
+  let mut cntrl = d.lctrl.rctrl[d.setno as usize].field.clone();
+  d.decod.annotate_cntrl(&mut cntrl);
   d.lsegm.ssegm.push(SsegmTp {
     instn: d.setno as usize,
-    cntrl: d.lctrl.rctrl[d.setno as usize].field.clone(),
+    cntrl: cntrl,
     rsegm: d.ssegm.rsegm.clone()
   });
   let ofnam = format!("{}{}-{}", d.outdr, d.flnam, format!("{}", d.setno));
   if OUTCTRL {
-    let mut file = File::create(format!("{}-control.json", ofnam)).expect("error");
-    let fctrl = serde_json::to_string_pretty(&d.lctrl).unwrap();
+    let opath = format!("{}-control.json", ofnam);
+    let mut file = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+    let fctrl = serde_json::to_string_pretty(&d.lctrl)
+      .map_err(|e| json_error(e, &opath))?;
     let bctrl: &[u8] = fctrl.as_bytes();
-    file.write_all(&bctrl).unwrap();
+    file.write_all(&bctrl).map_err(|e| io_error(e, &opath))?;
   }
   if OUTDATA {
-    let mut file = File::create(format!("{}-data.json", ofnam)).expect("error");
-    let fdata = serde_json::to_string_pretty(&d.ldata).unwrap();
+    let opath = format!("{}-data.json", ofnam);
+    let mut file = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+    let fdata = serde_json::to_string_pretty(&d.ldata)
+      .map_err(|e| json_error(e, &opath))?;
     let bdata: &[u8] = fdata.as_bytes();
-    file.write_all(&bdata).unwrap();
+    file.write_all(&bdata).map_err(|e| io_error(e, &opath))?;
   }
   if OUTSEGM {
-    let mut file = File::create(format!("{}-segment.json", ofnam)).expect("error");
-    let fsegm = serde_json::to_string_pretty(&d.lsegm).unwrap();
+    let opath = format!("{}-segment.json", ofnam);
+    let mut file = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+    let fsegm = serde_json::to_string_pretty(&d.lsegm)
+      .map_err(|e| json_error(e, &opath))?;
     let bsegm: &[u8] = fsegm.as_bytes();
-    file.write_all(&bsegm).unwrap();
+    file.write_all(&bsegm).map_err(|e| io_error(e, &opath))?;
   }
   d.sdata.rdata = Default::default();
   d.ldata.sdata = Default::default();
   d.ssegm.rsegm = Default::default();
   d.lsegm.ssegm = Default::default();
+  return Ok(());
 }
 
 // get_segmt_fields.rs - Get field values of a segment into the IDOC structure
 // (2021-07-01 bar8tl)
 pub fn get_segmt_fields(cnn: &Connection, d: &mut DidocTp, iline: &str,
-  idocn: &String, strtp: String, cdnam: &String) {
+  idocn: &String, strtp: String, cdnam: &String) -> Result<()> {
   let mut f    : OutitmTp = Default::default();
   let mut e    : OutstrTp = Default::default();
   let mut fitem: bool     = true;
+  let chars: Vec<char> = iline.chars().collect();
   let mut stmt = cnn.prepare("SELECT a.idocn, a.level, a.pseqn, a.pdnam, a.pdtyp,
     a.pdqlf, a.cseqn, a.cdnam, a.cdtyp, a.cdqlf, b.dname, b.seqno, b.strps, b.endps
     FROM struc a LEFT JOIN items b ON (a.idocn = b.idocn and a.cdtyp = b.rname)
     WHERE a.idocn=?1 and a.strtp=?2 and a.cdtyp=?3  ORDER BY a.idocn, a.strtp,
-    a.pseqn, a.prnam, a.pdnam, b.seqno;").expect("DB Err");
-  let mut rows = stmt.query([idocn, &strtp, &cdnam.to_string(),]).expect("DB Err");
-  while let Some(row) = rows.next().expect("while row failed") {
-    e.idocn = row.get( 0).unwrap();
-    e.level = row.get( 1).unwrap();
-    e.pseqn = row.get( 2).unwrap();
-    e.pdnam = row.get( 3).unwrap();
-    e.pdtyp = row.get( 4).unwrap();
-    e.pdqlf = row.get( 5).unwrap();
-    e.cseqn = row.get( 6).unwrap();
-    e.cdnam = row.get( 7).unwrap();
-    e.cdtyp = row.get( 8).unwrap();
-    e.cdqlf = row.get( 9).unwrap();
-    f.dname = row.get(10).unwrap();
-    f.seqno = row.get(11).unwrap();
-    f.strps = row.get(12).unwrap();
-    f.endps = row.get(13).unwrap();
-    if f.endps >= iline.len() {
+    a.pseqn, a.prnam, a.pdnam, b.seqno;")?;
+  let mut rows = stmt.query([idocn, &strtp, &cdnam.to_string(),])?;
+  while let Some(row) = rows.next()? {
+    e.idocn = row.get( 0)?;
+    e.level = row.get( 1)?;
+    e.pseqn = row.get( 2)?;
+    e.pdnam = row.get( 3)?;
+    e.pdtyp = row.get( 4)?;
+    e.pdqlf = row.get( 5)?;
+    e.cseqn = row.get( 6)?;
+    e.cdnam = row.get( 7)?;
+    e.cdtyp = row.get( 8)?;
+    e.cdqlf = row.get( 9)?;
+    f.dname = row.get(10)?;
+    f.seqno = row.get(11)?;
+    f.strps = row.get(12)?;
+    f.endps = row.get(13)?;
+    if f.endps >= chars.len() {
       break;
     }
-    let cdval: String = iline[f.strps-1..f.endps].trim().to_string();
+    let cdval: String = chars[f.strps-1..f.endps].iter().collect::<String>().trim()
+      .to_string();
     if cdval.len() == 0 || cdval == "" {
       continue;
     }
@@ -507,6 +453,150 @@ pub fn get_segmt_fields(cnn: &Connection, d: &mut DidocTp, iline: &str,
       }
       fitem = false;
     }
-    d.sfild.field.push(FieldTp { key: f.dname, val: cdval });
+    d.sfild.field.push(FieldTp { key: f.dname, val: cdval, ..Default::default()
+    });
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readidoc::samples::{sample_file, sample_path, sample_repo,
+    sample_stage, work_folder, FIXED, JSONF};
+  use crate::error::ErrorTp;
+  use crate::reposit::ini_repo;
+  use std::fs::{read_to_string, write};
+  use std::path::Path;
+
+  fn node(segmn: &str) -> RsegmTp {
+    return RsegmTp { segmn: segmn.to_string(), ..Default::default() };
+  }
+
+  fn empty_repo(name: &str) -> Connection {
+    let dbopt = format!("{}edimaps.db", work_folder(name));
+    ini_repo(&dbopt, &"items".to_string()).unwrap();
+    ini_repo(&dbopt, &"struc".to_string()).unwrap();
+    return Connection::open(&dbopt).unwrap();
+  }
+
+  #[test]
+  fn push_segmt_node_numbers_instances_of_repeated_siblings() {
+    let mut root = node("IDOC");
+    push_segmt_node(&mut root, &[], node("E2EDK02"));
+    push_segmt_node(&mut root, &[], node("E2EDK03"));
+    push_segmt_node(&mut root, &[], node("E2EDK02"));
+    let child = push_segmt_node(&mut root, &[2], node("E2EDP19"));
+    push_segmt_node(&mut root, &[0], node("E2EDP19"));
+    assert_eq!(child, 0);
+    let instn: Vec<usize> = root.child.iter().map(|c| c.instn).collect();
+    assert_eq!(instn, vec![1, 1, 2]);
+    assert_eq!(root.child[0].child[0].instn, 1);
+    assert_eq!(root.child[2].child[0].instn, 1);
+  }
+
+  #[test]
+  fn push_segmt_node_keeps_more_than_9999_records() {
+    let mut root = node("IDOC");
+    push_segmt_node(&mut root, &[], node("E2EDP01"));
+    for _ in 0..10500 {
+      push_segmt_node(&mut root, &[0], node("E2EDP26"));
+    }
+    assert_eq!(root.child[0].child.len(), 10500);
+    assert_eq!(root.child[0].child[10499].instn, 10500);
+  }
+
+  #[test]
+  fn add_tostruct_nests_more_than_nine_levels() {
+    let cnn = empty_repo("tojson-levels");
+    let mut d = DidocTp { rsegm: node("IDOC"), ..Default::default() };
+    for level in 1..=12 {
+      d.recno = level;
+      add_tostruct(&cnn, &mut d, "", &"IDOC".to_string(), format!("SEG{:02}",
+        level), level).unwrap();
+    }
+    assert_eq!(d.stack.len(), 12);
+    let mut n = &d.rsegm;
+    for level in 1..=12 {
+      assert_eq!(n.child.len(), 1);
+      n = &n.child[0];
+      assert_eq!((n.segmn.clone(), n.level, n.recno), (format!("SEG{:02}", level),
+        level, level));
+    }
+    d.recno = 13;
+    add_tostruct(&cnn, &mut d, "", &"IDOC".to_string(), "SEG02".to_string(), 2)
+      .unwrap();
+    assert_eq!(d.stack, vec![0, 1]);
+    assert_eq!(d.rsegm.child[0].child[1].instn, 2);
+  }
+
+  #[test]
+  fn add_tostruct_puts_level_jump_under_last_open_segment() {
+    let cnn = empty_repo("tojson-lvjmp");
+    let mut d = DidocTp { rsegm: node("IDOC"), ..Default::default() };
+    add_tostruct(&cnn, &mut d, "", &"IDOC".to_string(), "E2EDK01".to_string(), 1)
+      .unwrap();
+    add_tostruct(&cnn, &mut d, "", &"IDOC".to_string(), "E2EDKA1".to_string(), 4)
+      .unwrap();
+    add_tostruct(&cnn, &mut d, "", &"IDOC".to_string(), "E2EDK02".to_string(), 1)
+      .unwrap();
+    assert_eq!(d.rsegm.child.len(), 2);
+    assert_eq!(d.rsegm.child[0].child[0].segmn, "E2EDKA1");
+    assert_eq!(d.rsegm.child[0].child[0].level, 4);
+    assert_eq!(d.stack, vec![1]);
+    assert_eq!(d.lvjmp, vec![(0, "E2EDKA1".to_string())]);
+  }
+
+  #[test]
+  fn json_content_onefile_converts_fixed_size_sample() {
+    let wrkdr = work_folder("tojson");
+    let dbopt = sample_repo(&wrkdr);
+    let cnn = Connection::open(&dbopt).unwrap();
+    let fl = sample_file(FIXED, &wrkdr);
+    let rtncd = json_content_onefile(&cnn, &sample_stage(&wrkdr, &wrkdr), &fl)
+      .unwrap();
+    assert_eq!(rtncd, OKAY);
+    let opath = format!("{}{}-0-segment.json", wrkdr, fl.flnam);
+    let otext = read_to_string(&opath).unwrap();
+    let etext = read_to_string(sample_path(JSONF)).unwrap();
+    let out: serde_json::Value = serde_json::from_str(&otext).unwrap();
+    let exp: serde_json::Value = serde_json::from_str(&etext).unwrap();
+    assert_eq!(out, exp);
+  }
+
+  #[test]
+  fn json_content_onefile_reports_level_jump_after_writing_output() {
+    let wrkdr = work_folder("tojson-jump");
+    let dbopt = sample_repo(&wrkdr);
+    let cnn = Connection::open(&dbopt).unwrap();
+    let fl = sample_file(FIXED, &wrkdr);
+    let mut lines: Vec<String> = read_to_string(&fl.flpth).unwrap().split('\n')
+      .map(|l| l.to_string()).collect();
+    lines.remove(1); // E2EDK01005, parent of the segments that follow
+    write(&fl.flpth, lines.join("\n")).unwrap();
+    let rslt = json_content_onefile(&cnn, &sample_stage(&wrkdr, &wrkdr), &fl);
+    match rslt {
+      Err(ErrorTp::IdocFormat { file, line, segmn, detail }) => {
+        assert_eq!((file, line), (fl.flpth.clone(), 2));
+        assert_eq!(segmn, "/RB04/YP3_E1EDK01_XML001");
+        assert!(detail.contains("skips hierarchy levels"));
+      },
+      other => panic!("Level jump not reported: {:?}", other)
+    }
+    assert!(Path::new(&format!("{}{}-0-segment.json", wrkdr, fl.flnam)).exists());
+  }
+
+  #[test]
+  fn json_content_onefile_reports_empty_file() {
+    let wrkdr = work_folder("tojson-empty");
+    let dbopt = sample_repo(&wrkdr);
+    let cnn = Connection::open(&dbopt).unwrap();
+    let fl = sample_file(FIXED, &wrkdr);
+    write(&fl.flpth, "").unwrap();
+    let rslt = json_content_onefile(&cnn, &sample_stage(&wrkdr, &wrkdr), &fl);
+    assert!(matches!(rslt, Err(ErrorTp::IdocFormat { .. })));
+    write(&fl.flpth, "E2EDK01005\n").unwrap();
+    let rslt = json_content_onefile(&cnn, &sample_stage(&wrkdr, &wrkdr), &fl);
+    assert!(matches!(rslt, Err(ErrorTp::IdocFormat { line: 1, .. })));
   }
 }
@@ -0,0 +1,144 @@
+// toxml.rs - Starts proper function to convert IDOC content from fixed size format
+// to SAP IDoc XML format (IDOC/EDI_DC40 XML schema). Either from a set of files
+// contained within a folder or from an specific single file. Parsing of the fixed
+// size content is the same used for JSON conversion (2021-07-01 bar8tl)
+use crate::error::{io_error, repo_error, Result, RunsmTp};
+use crate::readidoc::read_idocs::{run_inbatch, StageTp};
+use crate::readidoc::tojson::{conv_content_onefile, DidocTp, FieldTp, RsegmTp,
+  EDI_DC40, XML};
+use rblib::files_infolder::FilelistTp;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+// symbols.rs - Symbolic constants for IDOC file conversion to XML format
+// (2021-07-01 bar8tl)
+pub const XML_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>";
+pub const IDOC      : &str = "IDOC";
+pub const BEGIN     : &str = "BEGIN";
+pub const SEGMENT   : &str = "SEGMENT";
+pub const TABNAM    : &str = "TABNAM";
+pub const SLASH     : &str = "/";
+pub const SLASH_XML : &str = "_-"; // SAP replacement for '/' in XML tag names
+
+// xml_content_inbatch.rs - Start batch process to convert IDOC files from fixed
+// size (flat file) format to SAP IDoc XML format (2021-07-01 bar8tl)
+// Command line: edimaps step xml <IDOC-fxdsz-file>
+pub fn xml_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) ->
+  Result<RunsmTp> {
+  return run_inbatch(dbopt, &st, idoct, xml_content_onefile);
+}
+
+// xml_content_onefile.rs - Convert individual IDOC file from fixed size flat
+// format to SAP IDoc XML format (2021-07-01 bar8tl)
+pub fn xml_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
+  Result<String> {
+  return conv_content_onefile(cnn, st, fl, XML);
+}
+
+// write_xml_file.rs - Write XML output of an IDoc data set: root element named by
+// the IDoc type, control record element and nested segment elements named by the
+// segment type (2021-07-01 bar8tl)
+pub fn write_xml_file(cnn: &Connection, d: &mut DidocTp) -> Result<()> {
+  let mut sgtyp: HashMap<String, String> = HashMap::new();
+  let cntrl: &Vec<FieldTp> = &d.lctrl.rctrl[d.setno as usize].field;
+  let mut tabnm = EDI_DC40.to_string();
+  if let Some(f) = cntrl.iter().find(|f| f.key == TABNAM) {
+    tabnm = f.val.clone();
+  }
+  let mut x = String::new();
+  x.push_str(&format!("{}\n", XML_HEADER));
+  x.push_str(&format!("<{}>\n", xml_tagname(&d.idocn)));
+  x.push_str(&format!("  <{} {}=\"1\">\n", IDOC, BEGIN));
+  x.push_str(&format!("    <{} {}=\"1\">\n", tabnm, SEGMENT));
+  for f in cntrl {
+    write_xml_field(&mut x, f, 6);
+  }
+  x.push_str(&format!("    </{}>\n", tabnm));
+  for c in &d.rsegm.child {
+    write_xml_segmt(cnn, &d.idocn, c, 4, &mut sgtyp, &mut x)?;
+  }
+  x.push_str(&format!("  </{}>\n", IDOC));
+  x.push_str(&format!("</{}>\n", xml_tagname(&d.idocn)));
+  let opath = format!("{}{}-{}.xml", d.outdr, d.flnam, d.setno);
+  let mut file = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+  file.write_all(x.as_bytes()).map_err(|e| io_error(e, &opath))?;
+  d.sdata.rdata = Default::default();
+  return Ok(());
+}
+
+// write_xml_segmt.rs - Write segment element with its fields and, nested inside,
+// its child segments (2021-07-01 bar8tl)
+pub fn write_xml_segmt(cnn: &Connection, idocn: &String, rsegm: &RsegmTp,
+  indnt: usize, sgtyp: &mut HashMap<String, String>, x: &mut String) ->
+  Result<()> {
+  if !sgtyp.contains_key(&rsegm.segmn) {
+    sgtyp.insert(rsegm.segmn.clone(), get_segmt_type(cnn, idocn, &rsegm.segmn)?);
+  }
+  let tagnm = xml_tagname(&sgtyp[&rsegm.segmn]);
+  x.push_str(&format!("{:w$}<{} {}=\"1\">\n", "", tagnm, SEGMENT, w = indnt));
+  for f in &rsegm.field {
+    write_xml_field(x, f, indnt + 2);
+  }
+  for c in &rsegm.child {
+    write_xml_segmt(cnn, idocn, c, indnt + 2, sgtyp, x)?;
+  }
+  x.push_str(&format!("{:w$}</{}>\n", "", tagnm, w = indnt));
+  return Ok(());
+}
+
+// write_xml_field.rs - Write a field element (2021-07-01 bar8tl)
+pub fn write_xml_field(x: &mut String, f: &FieldTp, indnt: usize) {
+  let tagnm = xml_tagname(&f.key);
+  x.push_str(&format!("{:w$}<{}>{}</{}>\n", "", tagnm, xml_escape(&f.val), tagnm,
+    w = indnt));
+}
+
+// get_segmt_type.rs - Get segment type (E1...) of a segment definition (E2...) as
+// SAP uses it to name XML segment elements. Segments not in the definition keep
+// their name (2021-07-01 bar8tl)
+pub fn get_segmt_type(cnn: &Connection, idocn: &String, segmn: &String) ->
+  Result<String> {
+  let dtype = cnn.query_row("SELECT dtype FROM items WHERE idocn=?1 and
+    rname=\"SEGMENT\" and dname=?2;", [idocn, segmn], |row| row.get(0));
+  match dtype {
+    Ok(dtype) => return Ok(dtype),
+    Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(segmn.clone()),
+    Err(e) => return Err(repo_error(e, "items"))
+  }
+}
+
+// xml_tagname.rs - Format IDoc names as XML tag names: SAP replaces the namespace
+// slashes by "_-" (2021-07-01 bar8tl)
+pub fn xml_tagname(name: &str) -> String {
+  return name.to_uppercase().replace(SLASH, SLASH_XML);
+}
+
+// xml_escape.rs - Escape XML special characters in field values
+// (2021-07-01 bar8tl)
+pub fn xml_escape(val: &str) -> String {
+  return val.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+    .replace('"', "&quot;").replace('\'', "&apos;");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::error::ErrorTp;
+  use crate::readidoc::samples::{sample_repo, work_folder, ITYPE};
+
+  #[test]
+  fn get_segmt_type_falls_back_only_for_segments_not_defined() {
+    let wrkdr = work_folder("toxml");
+    let cnn = Connection::open(sample_repo(&wrkdr)).unwrap();
+    let idocn = ITYPE.to_string();
+    assert_eq!(get_segmt_type(&cnn, &idocn, &"E2EDK01005".to_string()).unwrap(),
+      "E1EDK01");
+    assert_eq!(get_segmt_type(&cnn, &idocn, &"Z2UNKNOWN".to_string()).unwrap(),
+      "Z2UNKNOWN");
+    let empty = Connection::open_in_memory().unwrap();
+    assert!(matches!(get_segmt_type(&empty, &idocn, &"E2EDK01005".to_string()),
+      Err(ErrorTp::Repository { .. })));
+  }
+}
@@ -0,0 +1,182 @@
+// unjson.rs - Starts proper function to convert IDOC content from JSON segment
+// format back to fixed size format. Either from a set of files contained within a
+// folder or from an specific single file (2021-07-01 bar8tl)
+use crate::assets::IdoctpTp;
+use crate::error::{io_error, json_error, repo_error, ErrorTp, Result, RunsmTp};
+use crate::readidoc::read_idocs::{run_inbatch, StageTp, get_idoctp};
+use crate::config::line_end;
+use crate::readidoc::tofixdsz::{get_record_length, put_field_value, DATA, DOCNUM,
+  HLEVEL, MANDT, PSGNUM, SEGNAM, SEGNUM, TABNAM};
+use crate::readidoc::tojson::{LsegmTp, RsegmTp, SsegmTp, CONTROL, OKAY};
+use rblib::files_infolder::FilelistTp;
+use rusqlite::Connection;
+use serde_json::from_reader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+// symbols.rs - Symbolic constants for IDOC file conversion from JSON format
+// (2021-07-01 bar8tl)
+pub const SEGMENT_SFX: &str = "-segment";
+
+// types.rs - Data structures used in IDOC file conversion from JSON format
+// (2021-07-01 bar8tl)
+pub type FposnTp = HashMap<String, (usize, usize)>; // Field: start, end position
+
+#[derive(Debug, Clone, Default)]
+pub struct UnjsonTp {
+  pub idocx: String, // IDoc type (extension or basic type)
+  pub clien: String, // Client taken from control record
+  pub idocn: String, // IDoc number taken from control record
+  pub sgnum: usize,  // Last segment number assigned
+  pub lnend: String, // Line terminator
+  pub dposn: FposnTp // Field positions of data record
+}
+
+// unjson_content_inbatch.rs - Start batch process to convert IDOC files from JSON
+// segment format to fixed-size (flat) format (2021-07-01 bar8tl)
+// Command line: edimaps step unjson <IDOC-json-file>
+pub fn unjson_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) ->
+  Result<RunsmTp> {
+  return run_inbatch(dbopt, &st, idoct, unjson_content_onefile);
+}
+
+// unjson_content_onefile.rs - Convert individual IDOC file from JSON segment format
+// to flat text file format. Each data set in the file produces one control record
+// line followed by its data segment lines (2021-07-01 bar8tl)
+pub fn unjson_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
+  Result<String> {
+  let it: IdoctpTp = get_idoctp(cnn, &st.wfhdr, fl)?;
+  let ifile = File::open(&fl.flpth).map_err(|e| io_error(e, &fl.flpth))?;
+  let lsegm: LsegmTp = from_reader(ifile).map_err(|e| json_error(e, &fl.flpth))?;
+  let opath = format!("{}{}.{}", st.wfstp.outdr,
+    fl.flnam.trim_end_matches(SEGMENT_SFX), st.wfstp.outtp);
+  let mut of = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+  for ssegm in &lsegm.ssegm {
+    write_fixed_idoc(cnn, &it, ssegm, line_end(&st.wfhdr.lnend), &mut of)?;
+  }
+  return Ok(OKAY.to_string());
+}
+
+// write_fixed_idoc.rs - Write control record and data segment lines of one IDOC
+// data set. SEGNUM, PSGNUM and HLEVEL are recomputed from the segment tree
+// (2021-07-01 bar8tl)
+pub fn write_fixed_idoc(cnn: &Connection, it: &IdoctpTp, ssegm: &SsegmTp,
+  lnend: &str, of: &mut File) -> Result<()> {
+  let mut u = UnjsonTp { ..Default::default() };
+  u.lnend = lnend.to_string();
+  u.idocx = it.itype.to_uppercase();
+  u.clien = it.clien.clone();
+  u.dposn = field_positions(cnn, &u.idocx, DATA)?;
+  let cposn = field_positions(cnn, &u.idocx, CONTROL)?;
+  let mut lctrl = vec![' '; get_record_length(cnn, &u.idocx, CONTROL)?];
+  put_field_inline(&cposn, &u.idocx, CONTROL, TABNAM, &it.cntrl, &mut lctrl)?;
+  for f in &ssegm.cntrl {
+    if f.key == MANDT {
+      u.clien = f.val.clone();
+    }
+    if f.key == DOCNUM {
+      u.idocn = f.val.clone();
+    }
+    put_field_inline(&cposn, &u.idocx, CONTROL, &f.key, &f.val, &mut lctrl)?;
+  }
+  put_field_inline(&cposn, &u.idocx, CONTROL, MANDT, &u.clien, &mut lctrl)?;
+  let oline: String = lctrl.iter().collect();
+  of.write_all(format!("{}{}", oline, u.lnend).as_bytes())?;
+  for rsegm in &ssegm.rsegm { // Root node stands for the IDoc type itself
+    for c in &rsegm.child {
+      write_segmt_tree(cnn, &mut u, c, 0, 1, of)?;
+    }
+  }
+  return Ok(());
+}
+
+// write_segmt_tree.rs - Write data segment line of a segment node, followed by the
+// lines of its child segments (depth first, as in IDoc sequence)
+// (2021-07-01 bar8tl)
+pub fn write_segmt_tree(cnn: &Connection, u: &mut UnjsonTp, rsegm: &RsegmTp,
+  psgnm: usize, hlevl: usize, of: &mut File) -> Result<()> {
+  u.sgnum += 1;
+  let sgnum = u.sgnum;
+  let mut lsegm = vec![' '; get_record_length(cnn, &u.idocx, DATA)?];
+  put_field_inline(&u.dposn, &u.idocx, DATA, SEGNAM, &rsegm.segmn, &mut lsegm)?;
+  put_field_inline(&u.dposn, &u.idocx, DATA, MANDT , &u.clien, &mut lsegm)?;
+  put_field_inline(&u.dposn, &u.idocx, DATA, DOCNUM, &u.idocn, &mut lsegm)?;
+  put_field_inline(&u.dposn, &u.idocx, DATA, SEGNUM, &format!("{:06}", sgnum),
+    &mut lsegm)?;
+  put_field_inline(&u.dposn, &u.idocx, DATA, PSGNUM, &format!("{:06}", psgnm),
+    &mut lsegm)?;
+  put_field_inline(&u.dposn, &u.idocx, DATA, HLEVEL, &format!("{:02}", hlevl),
+    &mut lsegm)?;
+  let sposn = field_positions(cnn, &u.idocx, &rsegm.segmn)?;
+  for f in &rsegm.field {
+    put_field_inline(&sposn, &u.idocx, &rsegm.segmn, &f.key, &f.val, &mut lsegm)?;
+  }
+  let oline: String = lsegm.iter().collect();
+  of.write_all(format!("{}{}", oline, u.lnend).as_bytes())?;
+  for c in &rsegm.child {
+    write_segmt_tree(cnn, u, c, sgnum, hlevl + 1, of)?;
+  }
+  return Ok(());
+}
+
+// field_positions.rs - Start and end positions of the fields of a record or
+// segment in the IDoc definition (2021-07-01 bar8tl)
+pub fn field_positions(cnn: &Connection, idocx: &String, rname: &str) ->
+  Result<FposnTp> {
+  let mut stmt = cnn.prepare("SELECT dname, strps, endps FROM items WHERE idocn=?1
+    and rname=?2;").map_err(|e| repo_error(e, "items"))?;
+  let posns: FposnTp = stmt.query_map([idocx.clone(), rname.to_string()], |row|
+    Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))
+    .map_err(|e| repo_error(e, "items"))?.collect::<rusqlite::Result<_>>()
+    .map_err(|e| repo_error(e, "items"))?;
+  return Ok(posns);
+}
+
+// put_field_inline.rs - Place a field value into an output line at the start/end
+// positions defined for it in the IDoc definition. A field not in the definition
+// or a value longer than the field is reported (2021-07-01 bar8tl)
+pub fn put_field_inline(posns: &FposnTp, idocx: &String, rname: &str,
+  dname: &str, flval: &String, line: &mut Vec<char>) -> Result<()> {
+  let (strps, endps) = posns.get(dname).ok_or(ErrorTp::DefNotFound {
+    file: String::new(), line: 0, idocn: idocx.clone(), segmn: rname.to_string(),
+    field: dname.to_string() })?;
+  return put_field_value(line, rname, dname, flval, *strps, *endps);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readidoc::samples::{fixed_lines, sample_file, sample_path,
+    sample_repo, sample_stage, work_folder, FIXED, JSONF};
+  use std::fs::{read_to_string, write};
+
+  #[test]
+  fn unjson_content_onefile_rebuilds_fixed_size_sample() {
+    let wrkdr = work_folder("unjson");
+    let dbopt = sample_repo(&wrkdr);
+    let cnn = Connection::open(&dbopt).unwrap();
+    let fl = sample_file(JSONF, &wrkdr);
+    let mut st = sample_stage(&wrkdr, &wrkdr);
+    st.wfstp.outtp = "txt".to_string();
+    assert_eq!(unjson_content_onefile(&cnn, &st, &fl).unwrap(), OKAY);
+    let opath = format!("{}i2xr4_idoc0000002382843157-0.txt", wrkdr);
+    assert_eq!(fixed_lines(&opath), fixed_lines(&sample_path(FIXED)));
+  }
+
+  #[test]
+  fn unjson_content_onefile_reports_overflowing_value() {
+    let wrkdr = work_folder("unjson-overflow");
+    let dbopt = sample_repo(&wrkdr);
+    let cnn = Connection::open(&dbopt).unwrap();
+    let fl = sample_file(JSONF, &wrkdr);
+    let text = read_to_string(&fl.flpth).unwrap().replacen("\"val\": \"USD\"",
+      "\"val\": \"USDOLLAR\"", 1);
+    write(&fl.flpth, text).unwrap();
+    let mut st = sample_stage(&wrkdr, &wrkdr);
+    st.wfstp.outtp = "txt".to_string();
+    let e = unjson_content_onefile(&cnn, &st, &fl).unwrap_err().to_string();
+    assert!(e.contains("Value 'USDOLLAR' overflows field CURCY of length 3"),
+      "{}", e);
+  }
+}
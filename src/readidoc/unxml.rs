@@ -0,0 +1,349 @@
+// unxml.rs - Starts proper function to convert IDOC content from SAP IDoc XML
+// format back to fixed size format. Either from a set of files contained within a
+// folder or from an specific single file (2021-07-01 bar8tl)
+use crate::assets::IdoctpTp;
+use crate::config::line_end;
+use crate::error::{defn_error, format_error, io_error, ErrorTp, Result, RunsmTp};
+use crate::readidoc::read_idocs::{run_inbatch, StageTp, get_idoctp};
+use crate::readidoc::tojson::{FieldTp, RsegmTp, SsegmTp, OKAY, RC01};
+use crate::readidoc::toxml::{IDOC, SEGMENT, SLASH, SLASH_XML};
+use crate::readidoc::unjson::write_fixed_idoc;
+use rblib::files_infolder::FilelistTp;
+use rusqlite::Connection;
+use std::fs::{File, read_to_string};
+
+// symbols.rs - Symbolic constants for IDOC file conversion from XML format
+// (2021-07-01 bar8tl)
+pub const EDI_DC: &str = "EDI_DC"; // Prefix of control record element
+
+// types.rs - Data structures used in IDOC file conversion from XML format
+// (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default)]
+pub struct XnodeTp {
+  pub name : String,
+  pub attrs: Vec<FieldTp>,
+  pub text : String,
+  pub child: Vec<XnodeTp>
+}
+
+// unxml_content_inbatch.rs - Start batch process to convert IDOC files from SAP
+// IDoc XML format to fixed-size (flat) format (2021-07-01 bar8tl)
+// Command line: edimaps step unxml <IDOC-xml-file>
+pub fn unxml_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) ->
+  Result<RunsmTp> {
+  return run_inbatch(dbopt, &st, idoct, unxml_content_onefile);
+}
+
+// unxml_content_onefile.rs - Convert individual IDOC file from SAP IDoc XML format
+// to flat text file format. Each IDOC element in the file produces one control
+// record line followed by its data segment lines (2021-07-01 bar8tl)
+pub fn unxml_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
+  Result<String> {
+  let it: IdoctpTp = get_idoctp(cnn, &st.wfhdr, fl)?;
+  let xtext = read_to_string(&fl.flpth).map_err(|e| io_error(e, &fl.flpth))?;
+  let xroot: XnodeTp = parse_xml(&xtext).map_err(|e| e.at(&fl.flpth, 0))?;
+  let mut lsegm: Vec<SsegmTp> = Vec::new();
+  for xdocm in &xroot.child {
+    for xidoc in xdocm.child.iter().filter(|x| x.name == IDOC) {
+      lsegm.push(get_idoc_set(cnn, &it, xidoc, lsegm.len())?);
+    }
+  }
+  if lsegm.len() == 0 {
+    println!("Input IDOC file {} has no IDOC elements", fl.flide);
+    return Ok(RC01.to_string());
+  }
+  let opath = format!("{}{}.{}", st.wfstp.outdr, fl.flnam, st.wfstp.outtp);
+  let mut of = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+  for ssegm in &lsegm {
+    write_fixed_idoc(cnn, &it, ssegm, line_end(&st.wfhdr.lnend), &mut of)?;
+  }
+  return Ok(OKAY.to_string());
+}
+
+// get_idoc_set.rs - Build control fields and segment tree of one IDOC element, in
+// the same shape used for the JSON segment format (2021-07-01 bar8tl)
+pub fn get_idoc_set(cnn: &Connection, it: &IdoctpTp, xidoc: &XnodeTp, instn: usize)
+  -> Result<SsegmTp> {
+  let mut ssegm = SsegmTp { instn: instn, ..Default::default() };
+  let mut root = RsegmTp { segmn: it.itype.clone(), ..Default::default() };
+  for x in &xidoc.child {
+    if x.name.starts_with(EDI_DC) {
+      ssegm.cntrl = get_xml_fields(x);
+    } else if is_segment(x) {
+      root.child.push(get_xml_segmt(cnn, it, x)?);
+    }
+  }
+  ssegm.rsegm.push(root);
+  return Ok(ssegm);
+}
+
+// get_xml_segmt.rs - Convert a segment element and its nested segment elements.
+// Elements are named by segment type (E1...), which is translated to the segment
+// ID (E2...) of the IDoc definition (2021-07-01 bar8tl)
+pub fn get_xml_segmt(cnn: &Connection, it: &IdoctpTp, x: &XnodeTp) ->
+  Result<RsegmTp> {
+  let sgtyp = x.name.replace(SLASH_XML, SLASH);
+  let segmn: String = cnn.query_row("SELECT dname FROM items WHERE idocn=?1 and
+    rname=\"SEGMENT\" and dtype=?2;", [&it.itype, &sgtyp], |row| row.get(0))
+    .map_err(|e| defn_error(e, &it.itype, &sgtyp, ""))?;
+  let mut rsegm = RsegmTp { segmn: segmn, ..Default::default() };
+  rsegm.field = get_xml_fields(x);
+  for c in x.child.iter().filter(|c| is_segment(c)) {
+    let mut child = get_xml_segmt(cnn, it, c)?;
+    child.instn = rsegm.child.iter().filter(|s| s.segmn == child.segmn).count() + 1;
+    rsegm.child.push(child);
+  }
+  return Ok(rsegm);
+}
+
+// get_xml_fields.rs - Get field elements (no segment children) of a record element
+// (2021-07-01 bar8tl)
+pub fn get_xml_fields(x: &XnodeTp) -> Vec<FieldTp> {
+  let mut field: Vec<FieldTp> = Vec::new();
+  for c in x.child.iter().filter(|c| !is_segment(c) && c.child.len() == 0) {
+    if c.text.len() > 0 {
+      field.push(FieldTp { key: c.name.replace(SLASH_XML, SLASH),
+        val: c.text.clone(), ..Default::default() });
+    }
+  }
+  return field;
+}
+
+// is_segment.rs - Check whether an element stands for a segment (2021-07-01 bar8tl)
+pub fn is_segment(x: &XnodeTp) -> bool {
+  return x.attrs.iter().any(|a| a.key == SEGMENT);
+}
+
+// parse_xml.rs - Parse XML text into an element tree. Declarations, comments and
+// DOCTYPE are skipped; CDATA sections and entities are taken as text. Returned
+// node is the document, holding the root element as child (2021-07-01 bar8tl)
+pub fn parse_xml(xtext: &str) -> Result<XnodeTp> {
+  let mut stack: Vec<XnodeTp> = vec![XnodeTp { ..Default::default() }];
+  let mut rest: &str = xtext;
+  while let Some(p) = rest.find('<') {
+    let l = stack.len()-1;
+    stack[l].text.push_str(&xml_unescape(&rest[..p]));
+    rest = &rest[p..];
+    rest = parse_xml_node(&mut stack, rest).map_err(|e| e.at("",
+      xtext[..xtext.len()-rest.len()].matches('\n').count() + 1))?;
+  }
+  if stack.len() != 1 {
+    return Err(xml_error(format!("element {} not closed",
+      stack[stack.len()-1].name)));
+  }
+  let mut xdocm = stack.remove(0);
+  xdocm.text = String::new();
+  return Ok(xdocm);
+}
+
+// parse_xml_node.rs - Parse the markup starting at the text given: skip
+// declarations and comments, take CDATA as text, open or close an element.
+// Returns the text following the markup (2021-07-01 bar8tl)
+pub fn parse_xml_node<'a>(stack: &mut Vec<XnodeTp>, rest: &'a str) ->
+  Result<&'a str> {
+  if rest.starts_with("<?") {
+    return skip_past(rest, "?>");
+  }
+  if rest.starts_with("<!--") {
+    return skip_past(rest, "-->");
+  }
+  if rest.starts_with("<![CDATA[") {
+    let e = rest.find("]]>").ok_or(xml_error("CDATA not closed".to_string()))?;
+    let l = stack.len()-1;
+    stack[l].text.push_str(&rest[9..e]);
+    return Ok(&rest[e+3..]);
+  }
+  if rest.starts_with("<!") {
+    return skip_past(rest, ">");
+  }
+  let e = tag_end(rest).ok_or(xml_error("tag not closed".to_string()))?;
+  let tag = rest[1..e].trim();
+  if tag.starts_with('/') {
+    if stack.len() == 1 || stack[stack.len()-1].name != tag[1..].trim() {
+      return Err(xml_error(format!("unexpected closing tag {}", tag)));
+    }
+    let node = stack.pop().unwrap_or_default();
+    let l = stack.len()-1;
+    stack[l].child.push(node);
+    return Ok(&rest[e+1..]);
+  }
+  let node = parse_xml_tag(tag.trim_end_matches('/'))?;
+  if tag.ends_with('/') {
+    let l = stack.len()-1;
+    stack[l].child.push(node);
+  } else {
+    stack.push(node);
+  }
+  return Ok(&rest[e+1..]);
+}
+
+// parse_xml_tag.rs - Get element name and attributes from an opening tag
+// (2021-07-01 bar8tl)
+pub fn parse_xml_tag(tag: &str) -> Result<XnodeTp> {
+  let mut x = XnodeTp { ..Default::default() };
+  let atokn: Vec<&str> = tag.splitn(2, char::is_whitespace).collect();
+  x.name = atokn[0].to_string();
+  if atokn.len() == 1 {
+    return Ok(x);
+  }
+  let mut rest: &str = atokn[1].trim();
+  while let Some(p) = rest.find('=') {
+    let key = rest[..p].trim().to_string();
+    let vals = rest[p+1..].trim_start();
+    let quot = match vals.chars().next() {
+      Some(q) if q == '"' || q == '\'' => q,
+      _ => return Err(xml_error(format!("attribute {} in {} not quoted", key,
+        x.name)))
+    };
+    let e = vals[1..].find(quot).ok_or(xml_error(format!(
+      "attribute {} in {} not closed", key, x.name)))? + 1;
+    x.attrs.push(FieldTp { key: key, val: xml_unescape(&vals[1..e]),
+      ..Default::default() });
+    rest = vals[e+1..].trim_start();
+  }
+  return Ok(x);
+}
+
+// tag_end.rs - Position of the '>' closing a tag, skipping those inside quoted
+// attribute values (2021-07-01 bar8tl)
+pub fn tag_end(rest: &str) -> Option<usize> {
+  let mut quot: Option<char> = None;
+  for (i, c) in rest.char_indices() {
+    match quot {
+      Some(q) => if c == q { quot = None },
+      None    => match c {
+        '"' | '\'' => quot = Some(c),
+        '>'        => return Some(i),
+        _          => {}
+      }
+    }
+  }
+  return None;
+}
+
+// skip_past.rs - Skip text up to and including the given delimiter
+// (2021-07-01 bar8tl)
+pub fn skip_past<'a>(rest: &'a str, delim: &str) -> Result<&'a str> {
+  let e = rest.find(delim).ok_or(xml_error(format!("'{}' expected", delim)))?;
+  return Ok(&rest[e+delim.len()..]);
+}
+
+// xml_error.rs - Build error for XML text not well-formed (2021-07-01 bar8tl)
+pub fn xml_error(detail: String) -> ErrorTp {
+  return format_error("", format!("XML not well-formed: {}", detail));
+}
+
+// xml_unescape.rs - Replace XML predefined and numeric entities by their
+// characters (2021-07-01 bar8tl)
+pub fn xml_unescape(val: &str) -> String {
+  if !val.contains('&') {
+    return val.to_string();
+  }
+  let mut s = String::new();
+  let mut rest: &str = val;
+  while let Some(p) = rest.find('&') {
+    s.push_str(&rest[..p]);
+    rest = &rest[p..];
+    let e = match rest.find(';') {
+      Some(e) => e,
+      None    => break
+    };
+    let c = match &rest[1..e] {
+      "amp"  => Some('&'),
+      "lt"   => Some('<'),
+      "gt"   => Some('>'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      n if n.starts_with("#x") => u32::from_str_radix(&n[2..], 16).ok()
+        .and_then(char::from_u32),
+      n if n.starts_with('#')  => n[1..].parse::<u32>().ok()
+        .and_then(char::from_u32),
+      _ => None
+    };
+    match c {
+      Some(c) => { s.push(c); rest = &rest[e+1..]; },
+      None    => { s.push('&'); rest = &rest[1..]; }
+    }
+  }
+  s.push_str(rest);
+  return s;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readidoc::samples::{fixed_lines, sample_file, sample_path,
+    sample_repo, sample_stage, work_folder, FIXED};
+  use crate::readidoc::toxml::{xml_content_onefile, xml_escape};
+  use rblib::files_infolder::FilelistTp;
+
+  #[test]
+  fn parse_xml_skips_prolog_and_comments() {
+    let x = parse_xml("<?xml version=\"1.0\"?>\n<!DOCTYPE IDOC>\n<!-- a <b> -->\
+      <A><B>1</B><!-- <C>2</C> --></A>").unwrap();
+    assert_eq!(x.child.len(), 1);
+    assert_eq!(x.child[0].name, "A");
+    assert_eq!(x.child[0].child.len(), 1);
+    let b = &x.child[0].child[0];
+    assert_eq!((b.name.as_str(), b.text.as_str()), ("B", "1"));
+  }
+
+  #[test]
+  fn parse_xml_takes_cdata_and_entities_as_text() {
+    let x = parse_xml("<A><B><![CDATA[x < y && <z>]]></B><C>&amp;&lt;&gt;&quot;\
+      &apos;&#233;&#xE9;&nbsp;</C><D> a &amp b </D></A>").unwrap();
+    let a = &x.child[0];
+    assert_eq!(a.child[0].text, "x < y && <z>");
+    assert_eq!(a.child[1].text, "&<>\"'éé&nbsp;");
+    assert_eq!(a.child[2].text, " a &amp b ");
+  }
+
+  #[test]
+  fn parse_xml_reads_attributes_and_self_closing_tags() {
+    let x = parse_xml("<A SEGMENT=\"1\" b='x \"y\"' c = \"a>b\"><E/><F g='&lt;'\
+      /><H></H></A>").unwrap();
+    let a = &x.child[0];
+    let attrs: Vec<(&str, &str)> = a.attrs.iter().map(|f| (f.key.as_str(),
+      f.val.as_str())).collect();
+    assert_eq!(attrs, vec![("SEGMENT", "1"), ("b", "x \"y\""), ("c", "a>b")]);
+    assert!(is_segment(a));
+    let names: Vec<&str> = a.child.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["E", "F", "H"]);
+    assert_eq!(a.child[1].attrs[0].val, "<");
+    assert!(a.child.iter().all(|c| c.child.len() == 0 && c.text.len() == 0));
+  }
+
+  #[test]
+  fn parse_xml_rejects_malformed_text() {
+    for xtext in ["<A><B></A>", "<A>", "<A b=1></A>", "<A b='1></A>",
+      "<A><![CDATA[x</A>", "<A"] {
+      let e = parse_xml(xtext).unwrap_err().to_string();
+      assert!(e.contains("XML not well-formed"), "{}: {}", xtext, e);
+    }
+  }
+
+  #[test]
+  fn xml_unescape_reverts_xml_escape() {
+    let val = "A&B <C> \"D\" 'E' &amp;";
+    assert_eq!(xml_unescape(&xml_escape(val)), val);
+  }
+
+  #[test]
+  fn unxml_content_onefile_round_trips_fixed_size_sample() {
+    let wrkdr = work_folder("unxml");
+    let dbopt = sample_repo(&wrkdr);
+    let cnn = Connection::open(&dbopt).unwrap();
+    let fl = sample_file(FIXED, &wrkdr);
+    let mut st = sample_stage(&wrkdr, &wrkdr);
+    st.wfstp.outtp = "out".to_string();
+    assert_eq!(xml_content_onefile(&cnn, &st, &fl).unwrap(), OKAY);
+    let xl = FilelistTp { flpth: format!("{}{}-0.xml", wrkdr, fl.flnam),
+      fldir: wrkdr.clone(), flide: format!("{}-0.xml", fl.flnam),
+      flnam: format!("{}-0", fl.flnam), flext: "xml".to_string() };
+    assert_eq!(unxml_content_onefile(&cnn, &st, &xl).unwrap(), OKAY);
+    let opath = format!("{}{}-0.out", wrkdr, fl.flnam);
+    let lines: Vec<String> = fixed_lines(&opath).iter()
+      .map(|l| l.trim_end_matches('\r').to_string()).collect();
+    assert_eq!(lines, fixed_lines(&sample_path(FIXED)));
+  }
+}
@@ -0,0 +1,559 @@
+// validate.rs - Starts proper function to validate IDOC content against the IDoc
+// definition uploaded in the repository: segments hierarchy, mandatory segments,
+// loop limits and field lengths. Either from a set of files contained within a
+// folder or from an specific single file, in fixed size or structured hierarchical
+// format (2021-07-01 bar8tl)
+use crate::assets::IdoctpTp;
+use crate::error::{io_error, json_error, ErrorTp, Result, RunsmTp};
+use crate::readidoc::read_idocs::{run_inbatch, StageTp, get_idoctp};
+use crate::readidoc::strtojson::{get_field_value, IDOC_NUMBER};
+use crate::readidoc::tofixdsz::{DOCNUM, EDIDC, EDIDD, SEGNAM, SEGNUM};
+use crate::readidoc::tojson::{CONTROL, DATA, OKAY, RC01};
+use rblib::files_infolder::FilelistTp;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+// symbols.rs - Symbolic constants for IDOC content validation (2021-07-01 bar8tl)
+pub const EDI_DC           : &str = "EDI_DC"; // Start of control record line
+pub const MANDATORY        : &str = "MANDATORY";
+pub const MISSING_MANDATORY: &str = "MISSING_MANDATORY";
+pub const LOOPMAX_EXCEEDED : &str = "LOOPMAX_EXCEEDED";
+pub const WRONG_PARENT     : &str = "WRONG_PARENT";
+pub const FIELD_TOO_LONG   : &str = "FIELD_TOO_LONG";
+pub const UNKNOWN_SEGMENT  : &str = "UNKNOWN_SEGMENT";
+pub const UNKNOWN_FIELD    : &str = "UNKNOWN_FIELD";
+
+// types.rs - Data structures used in IDOC content validation (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default)]
+pub struct SgdefTp {         // Segment definition
+  pub dname: String,         // Segment ID (E2...)
+  pub dtype: String,         // Segment type (E1...)
+  pub level: usize,
+  pub stats: String,         // MANDATORY/OPTIONAL, from group if group head
+  pub minlp: usize,
+  pub maxlp: usize,
+  pub prnt : Option<usize>,  // Parent segment definition, None for IDoc root
+  pub chlds: Vec<usize>      // Child segment definitions
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FldefTp {         // Field definition
+  pub dname: String,
+  pub lngth: usize,
+  pub strps: usize,
+  pub endps: usize
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpnodTp {         // Open segment instance in the IDoc being read
+  pub sgdef: Option<usize>,  // Segment definition, None for IDoc root
+  pub recno: usize,
+  pub count: HashMap<usize, usize> // Occurrences of each child definition
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VfindTp {         // Validation finding
+  pub setno: usize,          // Data set within the file
+  pub docnm: String,         // IDoc number
+  pub recno: usize,          // Segment record number within data set
+  pub segmn: String,
+  pub flkey: String,
+  pub rulid: String,
+  pub dtext: String
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LfindTp {         // Validation report of an IDoc file
+  pub flide: String,
+  pub idocn: String,
+  pub nsets: usize,
+  pub nfind: usize,
+  pub findg: Vec<VfindTp>
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ValidTp {
+  pub sgdef: Vec<SgdefTp>,
+  pub sgidx: HashMap<String, usize>,      // Segment ID -> definition
+  pub sgtyp: HashMap<String, usize>,      // Segment type -> definition
+  pub rcdef: HashMap<String, Vec<FldefTp>>, // Record/segment ID -> fields
+  pub roots: Vec<usize>,                  // Top level segment definitions
+  pub stack: Vec<OpnodTp>,
+  pub cdef : Option<usize>,               // Definition of current segment
+  pub isopn: bool,
+  pub setno: usize,
+  pub docnm: String,
+  pub recno: usize,
+  pub lfind: LfindTp
+}
+
+// valid_content_inbatch.rs - Start batch process to validate IDOC files against
+// the IDoc definition (2021-07-01 bar8tl)
+// Command line: edimaps step validate <IDOC-file>
+pub fn valid_content_inbatch(dbopt: &String, st: StageTp, idoct: &String) ->
+  Result<RunsmTp> {
+  return run_inbatch(dbopt, &st, idoct, valid_content_onefile);
+}
+
+// valid_content_onefile.rs - Validate individual IDOC file, fixed size or
+// structured hierarchical. Findings are written into file
+// <outdr><idoc-file>_valid.json. Files with findings are not passed to the next
+// workflow stage (2021-07-01 bar8tl)
+pub fn valid_content_onefile(cnn: &Connection, st: &StageTp, fl: &FilelistTp) ->
+  Result<String> {
+  let it: IdoctpTp = get_idoctp(cnn, &st.wfhdr, fl)?;
+  let mut v = ValidTp { ..Default::default() };
+  load_definition(cnn, &mut v, &it.itype)?;
+  v.lfind.flide = fl.flide.clone();
+  v.lfind.idocn = it.itype.clone();
+  let ifile = File::open(&fl.flpth).map_err(|e| io_error(e, &fl.flpth))?;
+  let lines: Vec<String> = BufReader::new(ifile).lines()
+    .collect::<std::io::Result<Vec<String>>>()
+    .map_err(|e| io_error(e, &fl.flpth))?;
+  let fixed = match lines.iter().find(|l| l.trim().len() > 0) {
+    Some(l) => l.starts_with(EDI_DC),
+    None    => false
+  };
+  if fixed {
+    read_fixed_lines(&mut v, &lines);
+  } else {
+    read_struc_lines(&mut v, &lines);
+  }
+  if v.isopn {
+    close_valid_set(&mut v);
+  }
+  v.lfind.nfind = v.lfind.findg.len();
+  let opath = format!("{}{}_valid.json", st.wfstp.outdr, fl.flnam);
+  let mut file = File::create(&opath).map_err(|e| io_error(e, &opath))?;
+  let fdata = serde_json::to_string_pretty(&v.lfind)
+    .map_err(|e| json_error(e, &opath))?;
+  file.write_all(fdata.as_bytes()).map_err(|e| io_error(e, &opath))?;
+  if v.lfind.nfind > 0 {
+    println!("IDOC File {}: {} findings in {} data sets", fl.flide, v.lfind.nfind,
+      v.lfind.nsets);
+    return Ok(RC01.to_string());
+  }
+  return Ok(OKAY.to_string());
+}
+
+// load_definition.rs - Get segments hierarchy, loop limits and field layouts of
+// the IDoc type. Parent of each segment is the last previous segment one level
+// up; group head segments take status and loop limits of their group
+// (2021-07-01 bar8tl)
+pub fn load_definition(cnn: &Connection, v: &mut ValidTp, idocn: &String) ->
+  Result<()> {
+  let mut grups: HashMap<String, (String, usize, usize)> = HashMap::new();
+  let mut stmt = cnn.prepare("SELECT dtype, stats, minlp, maxlp FROM items WHERE
+    idocn=?1 and rname=\"GROUP\";")?;
+  let mut rows = stmt.query([idocn,])?;
+  while let Some(row) = rows.next()? {
+    let head: String = row.get(0)?;
+    if head.len() > 0 {
+      grups.insert(head, (row.get(1)?, row.get(2)?,
+        row.get(3)?));
+    }
+  }
+  let mut lastl: Vec<usize> = Vec::new(); // Last definition seen at each level
+  let mut stmt = cnn.prepare("SELECT dname, dtype, level, stats, minlp, maxlp FROM
+    items WHERE idocn=?1 and rname=\"SEGMENT\" order by seqno;")?;
+  let mut rows = stmt.query([idocn,])?;
+  while let Some(row) = rows.next()? {
+    let mut s = SgdefTp { ..Default::default() };
+    s.dname = row.get(0)?;
+    s.dtype = row.get(1)?;
+    s.level = row.get(2)?;
+    s.stats = row.get(3)?;
+    s.minlp = row.get(4)?;
+    s.maxlp = row.get(5)?;
+    if let Some((stats, minlp, maxlp)) = grups.get(&s.dname) {
+      s.stats = stats.clone();
+      s.minlp = *minlp;
+      s.maxlp = *maxlp;
+    }
+    let i = v.sgdef.len();
+    let level = if s.level > 0 { s.level } else { 1 };
+    lastl.truncate(level - 1);
+    s.prnt = lastl.last().copied();
+    match s.prnt {
+      Some(p) => v.sgdef[p].chlds.push(i),
+      None    => v.roots.push(i)
+    }
+    lastl.push(i);
+    v.sgidx.insert(s.dname.clone(), i);
+    v.sgtyp.insert(s.dtype.clone(), i);
+    v.sgdef.push(s);
+  }
+  let mut stmt = cnn.prepare("SELECT rname, dname, lngth, strps, endps FROM items
+    WHERE idocn=?1 and rtype=\"FIELDS\" order by rname, seqno;")?;
+  let mut rows = stmt.query([idocn,])?;
+  while let Some(row) = rows.next()? {
+    let rname: String = row.get(0)?;
+    v.rcdef.entry(rname).or_insert(Vec::new()).push(FldefTp {
+      dname: row.get(1)?,
+      lngth: row.get(2)?,
+      strps: row.get(3)?,
+      endps: row.get(4)?
+    });
+  }
+  if v.sgdef.len() == 0 {
+    return Err(ErrorTp::DefNotFound { file: String::new(), line: 0,
+      idocn: idocn.clone(), segmn: String::new(), field: String::new() });
+  }
+  return Ok(());
+}
+
+// read_fixed_lines.rs - Feed validation from fixed size lines: control record line
+// starts a new data set, data record lines give segment ID and field values at
+// the positions of the definition (2021-07-01 bar8tl)
+pub fn read_fixed_lines(v: &mut ValidTp, lines: &Vec<String>) {
+  for line in lines {
+    if line.trim().len() == 0 {
+      continue;
+    }
+    let lchar: Vec<char> = line.chars().collect();
+    if line.starts_with(EDI_DC) {
+      if v.isopn {
+        close_valid_set(v);
+      }
+      let docnm = get_fixed_value(&lchar, v.rcdef.get(CONTROL), DOCNUM);
+      open_valid_set(v, &docnm);
+      continue;
+    }
+    if !v.isopn {
+      open_valid_set(v, "");
+    }
+    let segmn = get_fixed_value(&lchar, v.rcdef.get(DATA), SEGNAM);
+    add_valid_segmt(v, &segmn, false);
+    let flds: Vec<FldefTp> = match &v.cdef {
+      Some(i) => v.rcdef.get(&v.sgdef[*i].dname).cloned().unwrap_or_default(),
+      None    => Vec::new()
+    };
+    for f in &flds {
+      let flval = get_fixed_value(&lchar, Some(&vec![f.clone()]), &f.dname);
+      if flval.len() > 0 {
+        add_valid_field(v, &f.dname, &flval);
+      }
+    }
+  }
+}
+
+// read_struc_lines.rs - Feed validation from structured hierarchical listing: each
+// "IDoc Number" heading starts a data set, SEGNAM lines give segment types and
+// the following lines their field values (2021-07-01 bar8tl)
+pub fn read_struc_lines(v: &mut ValidTp, lines: &Vec<String>) {
+  let mut sectn = String::new();
+  for wlin in lines {
+    let line = wlin.trim();
+    let tokn: Vec<&str> = line.split('\t').collect();
+    if line.len() == 0 {
+      continue;
+    }
+    if tokn.len() == 1 && line.starts_with(IDOC_NUMBER) {
+      if v.isopn {
+        close_valid_set(v);
+      }
+      let idtkn: Vec<&str> = line.splitn(2, " : ").collect();
+      open_valid_set(v, idtkn[idtkn.len()-1].trim());
+      continue;
+    }
+    if tokn.len() <= 1 {
+      continue;
+    }
+    if !v.isopn {
+      open_valid_set(v, "");
+    }
+    if tokn[0].starts_with("EDID") {
+      sectn = tokn[0].to_string();
+      continue;
+    }
+    if tokn[0] == SEGNUM && tokn.len() == 3 {
+      continue;
+    }
+    if tokn[0] == SEGNAM && tokn.len() == 3 {
+      add_valid_segmt(v, tokn[2].trim(), true);
+      continue;
+    }
+    let flval = get_field_value(&tokn);
+    if sectn == EDIDD && flval.len() > 0 {
+      add_valid_field(v, tokn[0], &flval);
+    } else if sectn == EDIDC && tokn[0] == DOCNUM && v.docnm.len() == 0 {
+      v.docnm = flval;
+    }
+  }
+}
+
+// get_fixed_value.rs - Get value of a field from a fixed size line, by character
+// positions (2021-07-01 bar8tl)
+pub fn get_fixed_value(lchar: &Vec<char>, flds: Option<&Vec<FldefTp>>, dname: &str)
+  -> String {
+  if let Some(flds) = flds {
+    if let Some(f) = flds.iter().find(|f| f.dname == dname) {
+      if f.strps == 0 || f.strps > lchar.len() {
+        return String::new();
+      }
+      let endps = if f.endps > lchar.len() { lchar.len() } else { f.endps };
+      return lchar[f.strps-1..endps].iter().collect::<String>().trim().to_string();
+    }
+  }
+  return String::new();
+}
+
+// open_valid_set.rs - Start validation of a new IDoc data set (2021-07-01 bar8tl)
+pub fn open_valid_set(v: &mut ValidTp, docnm: &str) {
+  v.isopn = true;
+  v.setno += 1;
+  v.docnm = docnm.to_string();
+  v.recno = 0;
+  v.cdef  = None;
+  v.stack = vec![OpnodTp { ..Default::default() }];
+  v.lfind.nsets += 1;
+}
+
+// close_valid_set.rs - Conclude validation of the data set, closing all open
+// segment instances (2021-07-01 bar8tl)
+pub fn close_valid_set(v: &mut ValidTp) {
+  while v.stack.len() > 0 {
+    close_valid_node(v);
+  }
+  v.isopn = false;
+}
+
+// add_valid_segmt.rs - Check an incoming segment: known in definition, placed
+// under its defined parent and within loop limits. Segment is given by ID in
+// fixed size files and by type in structured listings (2021-07-01 bar8tl)
+pub fn add_valid_segmt(v: &mut ValidTp, segmn: &str, bytyp: bool) {
+  v.recno += 1;
+  let found = if bytyp { v.sgtyp.get(segmn) } else { v.sgidx.get(segmn) };
+  v.cdef = found.copied();
+  let i = match v.cdef {
+    Some(i) => i,
+    None    => {
+      let recno = v.recno;
+      add_finding(v, recno, segmn, "", UNKNOWN_SEGMENT,
+        format!("Segment {} not defined for IDoc type", segmn));
+      return;
+    }
+  };
+  let prnt = v.sgdef[i].prnt;
+  match v.stack.iter().rposition(|n| n.sgdef == prnt) {
+    Some(k) => {
+      while v.stack.len() > k + 1 {
+        close_valid_node(v);
+      }
+    },
+    None    => {
+      let pname = match prnt {
+        Some(p) => v.sgdef[p].dname.clone(),
+        None    => v.lfind.idocn.clone()
+      };
+      let cname = match v.stack.last().and_then(|n| n.sgdef) {
+        Some(c) => v.sgdef[c].dname.clone(),
+        None    => v.lfind.idocn.clone()
+      };
+      let dname = v.sgdef[i].dname.clone();
+      let recno = v.recno;
+      add_finding(v, recno, &dname, "", WRONG_PARENT,
+        format!("Segment expected under {}, found under {}", pname, cname));
+      let level = if v.sgdef[i].level > 0 { v.sgdef[i].level } else { 1 };
+      while v.stack.len() > level {
+        close_valid_node(v);
+      }
+    }
+  }
+  let count = {
+    let top = v.stack.last_mut().unwrap();
+    let count = top.count.entry(i).or_insert(0);
+    *count += 1;
+    *count
+  };
+  let s = v.sgdef[i].clone();
+  if s.maxlp > 0 && count == s.maxlp + 1 {
+    let recno = v.recno;
+    add_finding(v, recno, &s.dname, "", LOOPMAX_EXCEEDED,
+      format!("Segment occurs more than {} times", s.maxlp));
+  }
+  v.stack.push(OpnodTp { sgdef: Some(i), recno: v.recno, count: HashMap::new() });
+}
+
+// add_valid_field.rs - Check a field value of current segment: known in segment
+// definition and not longer than defined length (2021-07-01 bar8tl)
+pub fn add_valid_field(v: &mut ValidTp, flkey: &str, flval: &String) {
+  let i = match v.cdef {
+    Some(i) => i,
+    None    => return
+  };
+  let dname = v.sgdef[i].dname.clone();
+  let recno = v.recno;
+  let lngth = match v.rcdef.get(&dname).and_then(|l| l.iter()
+    .find(|f| f.dname == flkey)) {
+    Some(f) => f.lngth,
+    None    => {
+      add_finding(v, recno, &dname, flkey, UNKNOWN_FIELD,
+        format!("Field {} not defined for segment", flkey));
+      return;
+    }
+  };
+  let flgth = flval.chars().count();
+  if lngth > 0 && flgth > lngth {
+    add_finding(v, recno, &dname, flkey, FIELD_TOO_LONG,
+      format!("Value length {} exceeds field length {}", flgth, lngth));
+  }
+}
+
+// close_valid_node.rs - Close last open segment instance, checking that all its
+// mandatory child segments were present (2021-07-01 bar8tl)
+pub fn close_valid_node(v: &mut ValidTp) {
+  let node = v.stack.pop().unwrap();
+  let chlds: Vec<usize> = match node.sgdef {
+    Some(p) => v.sgdef[p].chlds.clone(),
+    None    => v.roots.clone()
+  };
+  let pname = match node.sgdef {
+    Some(p) => v.sgdef[p].dname.clone(),
+    None    => v.lfind.idocn.clone()
+  };
+  for c in chlds {
+    let s = v.sgdef[c].clone();
+    let count = *node.count.get(&c).unwrap_or(&0);
+    let minlp = if s.minlp > 0 { s.minlp } else { 1 };
+    if s.stats == MANDATORY && count < minlp {
+      add_finding(v, node.recno, &s.dname, "", MISSING_MANDATORY,
+        format!("Mandatory segment under {} occurs {} times, minimum {}", pname,
+        count, minlp));
+    }
+  }
+}
+
+// add_finding.rs - Record a finding at given segment record (2021-07-01 bar8tl)
+pub fn add_finding(v: &mut ValidTp, recno: usize, segmn: &str, flkey: &str,
+  rulid: &str, dtext: String) {
+  v.lfind.findg.push(VfindTp { setno: v.setno, docnm: v.docnm.clone(),
+    recno: recno, segmn: segmn.to_string(), flkey: flkey.to_string(),
+    rulid: rulid.to_string(), dtext: dtext });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readidoc::samples::{sample_file, sample_repo, sample_stage,
+    strict_repo, work_folder, FIXED, ITYPE, STRUC};
+  use std::fs::{read_to_string, write};
+
+  // Rule IDs found validating a sample file, after applying a change to its lines
+  fn findings(dbopt: &String, wrkdr: &String, file: &str,
+    chnge: impl Fn(&mut Vec<String>)) -> Vec<String> {
+    let cnn = Connection::open(dbopt).unwrap();
+    let fl = sample_file(file, wrkdr);
+    let mut lines: Vec<String> = read_to_string(&fl.flpth).unwrap().split('\n')
+      .map(|l| l.to_string()).collect();
+    chnge(&mut lines);
+    write(&fl.flpth, lines.join("\n")).unwrap();
+    let rtncd = valid_content_onefile(&cnn, &sample_stage(wrkdr, wrkdr), &fl)
+      .unwrap();
+    let opath = format!("{}{}_valid.json", wrkdr, fl.flnam);
+    let lfind: serde_json::Value =
+      serde_json::from_str(&read_to_string(&opath).unwrap()).unwrap();
+    let rulid: Vec<String> = lfind["findg"].as_array().unwrap().iter()
+      .map(|f| f["rulid"].as_str().unwrap().to_string()).collect();
+    assert_eq!(rtncd, if rulid.len() == 0 { OKAY } else { RC01 });
+    return rulid;
+  }
+
+  // Position of the first line starting with a segment name or field key
+  fn line_of(lines: &Vec<String>, start: &str) -> usize {
+    return lines.iter().position(|l| l.trim_start().starts_with(start)).unwrap();
+  }
+
+  #[test]
+  fn load_definition_takes_group_limits_for_group_head() {
+    let wrkdr = work_folder("validate-defn");
+    let cnn = Connection::open(strict_repo(&wrkdr)).unwrap();
+    let head: String = cnn.query_row("SELECT dtype FROM items WHERE idocn=?1 and
+      rname=\"GROUP\" and dname=\"1\";", [ITYPE], |row| row.get(0)).unwrap();
+    assert_eq!(head, "E2EDP01011");
+    let mut v = ValidTp { ..Default::default() };
+    load_definition(&cnn, &mut v, &ITYPE.to_string()).unwrap();
+    let item = &v.sgdef[v.sgidx["E2EDP01011"]];
+    assert_eq!((item.stats.as_str(), item.minlp, item.maxlp), (MANDATORY, 1, 3));
+    assert_eq!(item.prnt, Some(v.sgidx["E2EDK01005"]));
+    assert_eq!(item.chlds.len(), 9);
+    assert_eq!(v.sgdef[v.sgidx["E2EDS01"]].stats, MANDATORY);
+    assert_eq!(v.roots, vec![v.sgidx["E2EDK01005"]]);
+    assert_eq!(v.sgtyp["E1EDP01"], v.sgidx["E2EDP01011"]);
+  }
+
+  #[test]
+  fn valid_content_onefile_finds_nothing_in_samples() {
+    let wrkdr = work_folder("validate-clean");
+    let dbopt = sample_repo(&wrkdr);
+    assert_eq!(findings(&dbopt, &wrkdr, FIXED, |_| {}), Vec::<String>::new());
+    assert_eq!(findings(&dbopt, &wrkdr, STRUC, |_| {}), Vec::<String>::new());
+    let wrkdr = work_folder("validate-clean-strict");
+    let dbopt = strict_repo(&wrkdr);
+    assert_eq!(findings(&dbopt, &wrkdr, FIXED, |_| {}), Vec::<String>::new());
+    assert_eq!(findings(&dbopt, &wrkdr, STRUC, |_| {}), Vec::<String>::new());
+  }
+
+  #[test]
+  fn valid_content_onefile_finds_missing_mandatory_segment() {
+    let wrkdr = work_folder("validate-mandatory");
+    let dbopt = strict_repo(&wrkdr);
+    assert_eq!(findings(&dbopt, &wrkdr, FIXED, |l| l.retain(|l|
+      !l.starts_with("E2EDS01"))), vec![MISSING_MANDATORY]);
+  }
+
+  #[test]
+  fn valid_content_onefile_finds_loop_exceeded() {
+    let wrkdr = work_folder("validate-loopmax");
+    let dbopt = strict_repo(&wrkdr);
+    assert_eq!(findings(&dbopt, &wrkdr, FIXED, |l| {
+      let item = l[line_of(l, "E2EDP01011")].clone();
+      let k = line_of(l, "E2EDS01");
+      l.insert(k, item);
+    }), vec![LOOPMAX_EXCEEDED]);
+  }
+
+  #[test]
+  fn valid_content_onefile_finds_segment_under_wrong_parent() {
+    let wrkdr = work_folder("validate-parent");
+    let dbopt = sample_repo(&wrkdr);
+    assert_eq!(findings(&dbopt, &wrkdr, FIXED, |l| {
+      let partn = l[line_of(l, "E2EDPA1003")].clone();
+      let k = line_of(l, "E2EDP01011");
+      l.insert(k, partn);
+    }), vec![WRONG_PARENT]);
+  }
+
+  #[test]
+  fn valid_content_onefile_finds_unknown_segment() {
+    let wrkdr = work_folder("validate-segment");
+    let dbopt = sample_repo(&wrkdr);
+    assert_eq!(findings(&dbopt, &wrkdr, FIXED, |l| {
+      let k = line_of(l, "E2EDK14");
+      l[k] = l[k].replacen("E2EDK14", "Z2EDK99", 1);
+    }), vec![UNKNOWN_SEGMENT]);
+  }
+
+  #[test]
+  fn valid_content_onefile_finds_unknown_field_in_structured_sample() {
+    let wrkdr = work_folder("validate-field");
+    let dbopt = sample_repo(&wrkdr);
+    assert_eq!(findings(&dbopt, &wrkdr, STRUC, |l| {
+      let k = line_of(l, "CURCY\t");
+      l.insert(k + 1, "\tZZFIELD\tUnknown field\tX".to_string());
+    }), vec![UNKNOWN_FIELD]);
+  }
+
+  #[test]
+  fn valid_content_onefile_finds_too_long_value_in_structured_sample() {
+    let wrkdr = work_folder("validate-length");
+    let dbopt = sample_repo(&wrkdr);
+    assert_eq!(findings(&dbopt, &wrkdr, STRUC, |l| {
+      let k = line_of(l, "CURCY\t");
+      l[k] = l[k].replacen("\tUSD", "\tUSDX", 1);
+    }), vec![FIELD_TOO_LONG]);
+  }
+}
@@ -0,0 +1,157 @@
+// watch.rs - Watch mode of workflow steps: the input folder of a step is scanned
+// periodically and each new file of its input type is processed once completely
+// written, until the program is interrupted. A file is taken as completely written
+// when its size and modification time did not change between two scans and it can
+// be opened for writing (2021-07-01 bar8tl)
+use crate::config::WkflowTp;
+use crate::error::{Result, RunsmTp};
+use crate::readidoc::read_idocs::{get_onefile, get_stage, next_stage, OnefileFn,
+  StageTp, OKAY};
+use rblib::files_infolder::files_infolder;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::fs::{metadata, OpenOptions};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+// types.rs - Data types of watch mode (2021-07-01 bar8tl)
+pub type FstmpTp = (u64, SystemTime); // File stamp: size, modification time
+
+// watch_idocs.rs - Run a step over the files arriving to its input folder, whose
+// name starts with the prefix given (all files if empty). Files found at start are
+// processed as well. A file left in the folder (step without workflow handling or
+// not concluded okay) is processed again only if it is rewritten
+// (2021-07-01 bar8tl)
+// Command line: edimaps step <step> <prefix> --watch
+pub fn watch_idocs(dbopt: &String, step: &str, wkflow: &WkflowTp, idoct: &String)
+  -> Result<RunsmTp> {
+  let cnn = Connection::open(dbopt)?;
+  let st = get_stage(&cnn, step, wkflow)?;
+  let onefile = get_onefile(step)?;
+  let mut pendg: HashMap<String, FstmpTp> = HashMap::new(); // Seen, not processed
+  let mut procd: HashMap<String, FstmpTp> = HashMap::new(); // Processed
+  println!("Watching {}{}*.{} for step {}, every {}s (Ctrl-C to stop)",
+    st.wfstp.inpdr, idoct, st.wfstp.inptp, step, wkflow.pollt);
+  loop {
+    (pendg, procd) = scan_folder(&cnn, &st, onefile, idoct, pendg, procd);
+    sleep(Duration::from_secs(wkflow.pollt));
+  }
+}
+
+// scan_folder.rs - Scan the input folder of a step once, processing the files
+// pending since the previous scan whose stamp did not change. Returns the files
+// pending and processed after the scan (2021-07-01 bar8tl)
+pub fn scan_folder(cnn: &Connection, st: &StageTp, onefile: OnefileFn,
+  idoct: &String, pendg: HashMap<String, FstmpTp>,
+  mut procd: HashMap<String, FstmpTp>) ->
+  (HashMap<String, FstmpTp>, HashMap<String, FstmpTp>) {
+  let flist = files_infolder(&st.wfstp.inpdr, &st.wfstp.inptp, idoct);
+  let mut seen: HashMap<String, FstmpTp> = HashMap::new();
+  for fl in &flist {
+    let stamp = match file_stamp(&fl.flpth) {
+      Some(s) => s,
+      None    => continue              // Removed since the scan
+    };
+    if procd.get(&fl.flide) == Some(&stamp) {
+      continue;
+    }
+    if pendg.get(&fl.flide) != Some(&stamp) || !writable(&fl.flpth) {
+      seen.insert(fl.flide.clone(), stamp);
+      continue;
+    }
+    let rslt = onefile(cnn, st, fl);
+    if let Ok(rtncd) = &rslt {
+      if st.wfstp.wkflw == "yes" {
+        next_stage(rtncd, st, fl);
+      }
+    }
+    report_file(&fl.flide, rslt.map_err(|e| e.at(&fl.flpth, 0)));
+    procd.insert(fl.flide.clone(), stamp);
+  }
+  procd.retain(|k, _| flist.iter().any(|f| &f.flide == k));
+  return (seen, procd);
+}
+
+// file_stamp.rs - Size and modification time of a file, none if it cannot be
+// read (2021-07-01 bar8tl)
+pub fn file_stamp(flpth: &String) -> Option<FstmpTp> {
+  let m = metadata(flpth).ok()?;
+  return Some((m.len(), m.modified().ok()?));
+}
+
+// writable.rs - Check a file can be opened for writing, not the case on Windows
+// while another process still writes it (2021-07-01 bar8tl)
+pub fn writable(flpth: &String) -> bool {
+  return OpenOptions::new().append(true).open(flpth).is_ok();
+}
+
+// report_file.rs - Show outcome of a file processed in watch mode
+// (2021-07-01 bar8tl)
+pub fn report_file(flide: &String, rslt: Result<String>) {
+  match rslt {
+    Ok(rtncd) if rtncd == OKAY => println!("{}: okay", flide),
+    Ok(rtncd) => println!("{}: rejected, return code {}", flide, rtncd),
+    Err(e)    => eprintln!("{}: failed, {}", flide, e)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::readidoc::samples::{sample_stage, work_folder};
+  use rblib::files_infolder::FilelistTp;
+  use std::fs::{create_dir_all, read_to_string, write, OpenOptions};
+  use std::io::Write;
+
+  // log_file.rs - Step logging the name of each file it processes into its
+  // output folder
+  fn log_file(_cnn: &Connection, st: &StageTp, fl: &FilelistTp) -> Result<String> {
+    let mut log = OpenOptions::new().create(true).append(true)
+      .open(format!("{}processed.log", st.wfstp.outdr)).unwrap();
+    writeln!(log, "{}", fl.flide).unwrap();
+    return Ok(OKAY.to_string());
+  }
+
+  #[test]
+  fn scan_folder_processes_a_file_once_it_stops_growing() {
+    let wrkdr = work_folder("watch-scan");
+    let inpdr = format!("{}inp/", wrkdr);
+    create_dir_all(&inpdr).unwrap();
+    let mut st = sample_stage(&inpdr, &wrkdr);
+    st.wfstp.inptp = "txt".to_string();
+    let cnn = Connection::open_in_memory().unwrap();
+    let flpth = format!("{}i2xr4_IDOC01.txt", inpdr);
+    let logpt = format!("{}processed.log", wrkdr);
+    let (mut pendg, mut procd) = (HashMap::new(), HashMap::new());
+    write(&flpth, "EDI_DC40\n").unwrap();
+    for growg in [true, true, false, false, false] {
+      (pendg, procd) = scan_folder(&cnn, &st, log_file, &String::new(), pendg,
+        procd);
+      if growg {
+        OpenOptions::new().append(true).open(&flpth).unwrap()
+          .write_all(b"E2EDK01005\n").unwrap();
+      }
+    }
+    assert_eq!(read_to_string(&logpt).unwrap(), "i2xr4_IDOC01.txt\n");
+    assert!(pendg.is_empty());
+    assert!(procd.contains_key("i2xr4_IDOC01.txt"));
+  }
+
+  #[test]
+  fn scan_folder_leaves_growing_file_pending() {
+    let wrkdr = work_folder("watch-growing");
+    let mut st = sample_stage(&wrkdr, &wrkdr);
+    st.wfstp.inptp = "txt".to_string();
+    let cnn = Connection::open_in_memory().unwrap();
+    let flpth = format!("{}i2xr4_IDOC02.txt", wrkdr);
+    let (mut pendg, mut procd) = (HashMap::new(), HashMap::new());
+    for i in 0..4 {
+      write(&flpth, "E2EDK01005\n".repeat(i + 1)).unwrap();
+      (pendg, procd) = scan_folder(&cnn, &st, log_file, &String::new(), pendg,
+        procd);
+      assert!(pendg.contains_key("i2xr4_IDOC02.txt"));
+    }
+    assert!(procd.is_empty());
+    assert!(read_to_string(format!("{}processed.log", wrkdr)).is_err());
+  }
+}
@@ -0,0 +1,430 @@
+// refdata.rs - View, update and export individual entries of the reference tables
+// of the repository: code tables (cdcodes), EDI code equivalences (cddata), coded
+// fields (cdfield), IDoc types (idoctp), workflow steps (wkflow) and pipelines
+// (pipeln). Export gives the JSON files read by 'add -r' (2021-07-01 bar8tl)
+use crate::assets::{CdfieldTp, CdfldTp, CodesTp, IdoctplTp, IdoctpTp, PipelnTp,
+  PipelnsTp, PipstpTp, SapcdTp, SapcodesTp, StepTp, StepsTp, TranslTp, TranspTp};
+use crate::error::{io_error, json_error, repo_error, ErrorTp, Result};
+use crate::queries::table_text;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use std::fs::File;
+use std::io::Write;
+
+// symbols.rs - Constants used to manage reference tables (2021-07-01 bar8tl)
+pub const CDCODES: &str = "cdcodes";
+pub const CDINDEX: &str = "cdindex";
+pub const CDDATA : &str = "cddata";
+pub const CDFIELD: &str = "cdfield";
+pub const IDOCTP : &str = "idoctp";
+pub const WKFLOW : &str = "wkflow";
+pub const PIPELN : &str = "pipeln";
+pub const EDITRANSP: &str = "editransp";   // Code equivalences loaded by add -r
+
+// types.rs - Data types of reference tables (2021-07-01 bar8tl)
+#[derive(Debug, Clone, Default)]
+pub struct RtablTp {         // Reference table
+  pub table: &'static str,
+  pub keys : usize,          // Number of key columns, the first ones
+  pub colms: Vec<&'static str>
+}
+
+// get_reftable.rs - Get layout of a reference table (2021-07-01 bar8tl)
+pub fn get_reftable(table: &String) -> Result<RtablTp> {
+  let (table, keys, colms) = match table.as_str() {
+    CDCODES => (CDCODES, 2, vec!["tabid", "cdkey", "cdtext"]),
+    CDDATA  => (CDDATA , 2, vec!["tabid", "cdkey", "relshp1", "relshp2"]),
+    CDFIELD => (CDFIELD, 2, vec!["segmn", "dname", "tabid"]),
+    IDOCTP  => (IDOCTP , 1, vec!["itype", "idefn", "short", "cntrl", "clien",
+      "rcvpf"]),
+    WKFLOW  => (WKFLOW , 1, vec!["step", "inpdr", "inptp", "outdr", "outtp",
+      "refdr", "reftp", "wkflw", "pcddr", "ifilt"]),
+    PIPELN  => (PIPELN , 2, vec!["pipnm", "seqno", "step", "ifilt"]),
+    _       => return Err(ErrorTp::Config { file: String::new(), detail: format!(
+      "Reference table '{}' not supported, use cdcodes, cddata, cdfield, idoctp, \
+      wkflow or pipeln", table) })
+  };
+  return Ok(RtablTp { table, keys, colms });
+}
+
+// list_refdata.rs - List entries of a reference table (2021-07-01 bar8tl)
+// Command line: edimaps ref list <table>
+pub fn list_refdata(dbopt: &String, table: &String) -> Result<()> {
+  let t = get_reftable(table)?;
+  let cnn = Connection::open(dbopt)?;
+  let lines = read_refdata(&cnn, &t, &Vec::new())?;
+  let colms: Vec<String> = t.colms.iter().map(|c| c.to_string()).collect();
+  print!("{}", table_text(&colms, &lines));
+  return Ok(());
+}
+
+// show_refdata.rs - Show entries of a reference table by key, one column per line.
+// A partial key shows all entries starting with it, e.g. all codes of a code
+// table (2021-07-01 bar8tl)
+// Command line: edimaps ref show <table> <key> [<key2>]
+pub fn show_refdata(dbopt: &String, table: &String, keys: &Vec<String>) ->
+  Result<()> {
+  let t = get_reftable(table)?;
+  check_keys(&t, keys, false)?;
+  let cnn = Connection::open(dbopt)?;
+  print!("{}", show_text(&cnn, &t, keys)?);
+  return Ok(());
+}
+
+// show_text.rs - Format entries of a reference table starting with the keys
+// given, one column per line, headed by the usage of the code table for cdcodes
+// (2021-07-01 bar8tl)
+fn show_text(cnn: &Connection, t: &RtablTp, keys: &Vec<String>) -> Result<String> {
+  let lines = read_refdata(cnn, t, keys)?;
+  if lines.len() == 0 {
+    return Err(not_found(t, keys));
+  }
+  let mut s = String::new();
+  if t.table == CDCODES {
+    let tabus: String = cnn.query_row("SELECT tabus FROM cdindex WHERE tabid=?1;",
+      [&keys[0]], |row| row.get(0)).unwrap_or_default();
+    s.push_str(&format!("{:8}: {}\n", "tabus", tabus));
+  }
+  for l in &lines {
+    for (c, v) in t.colms.iter().zip(l.iter()) {
+      s.push_str(&format!("{:8}: {}\n", c, v));
+    }
+    s.push('\n');
+  }
+  return Ok(s);
+}
+
+// set_refdata.rs - Add or update an entry of a reference table: columns given as
+// column=value replace those of an existing entry; a new entry gets empty values
+// in the columns not given. For cdcodes the code table is added to cdindex if new;
+// with the table key only, 'tabus=<usage>' sets the usage of the code table
+// (2021-07-01 bar8tl)
+// Command line: edimaps ref set <table> <key> [<key2>] -v <column>=<value> ...
+pub fn set_refdata(dbopt: &String, table: &String, keys: &Vec<String>,
+  values: &Vec<String>) -> Result<()> {
+  let t = get_reftable(table)?;
+  let cdidx = t.table == CDCODES && keys.len() == 1;
+  check_keys(&t, keys, !cdidx)?;
+  let mut colvl: Vec<(String, String)> = Vec::new();
+  for v in values {
+    let (c, v) = v.split_once('=').ok_or(ErrorTp::Config { file: String::new(),
+      detail: format!("Value '{}' not in the form <column>=<value>", v) })?;
+    let valid = if cdidx { c == "tabus" } else {
+      t.colms[t.keys..].contains(&c) };
+    if !valid {
+      return Err(ErrorTp::Config { file: String::new(), detail: format!(
+        "Column '{}' not valid for table {}, use {}", c, t.table, if cdidx {
+        "tabus".to_string() } else { t.colms[t.keys..].join(", ") }) });
+    }
+    colvl.push((c.to_string(), v.to_string()));
+  }
+  let cnn = Connection::open(dbopt)?;
+  if t.table == CDCODES {
+    cnn.execute("INSERT OR IGNORE INTO cdindex VALUES (?1, '');", [&keys[0]])
+      .map_err(|e| repo_error(e, CDINDEX))?;
+    if cdidx {
+      for (_, v) in &colvl {
+        cnn.execute("UPDATE cdindex SET tabus=?2 WHERE tabid=?1;", [&keys[0], v])
+          .map_err(|e| repo_error(e, CDINDEX))?;
+      }
+      println!("Entry {} of {} updated", keys[0], CDINDEX);
+      return Ok(());
+    }
+  }
+  let exist = read_refdata(&cnn, &t, keys)?.len() > 0;
+  let wkeys = key_condition(&t, keys.len());
+  if !exist {
+    let mark: Vec<String> = (1..=t.colms.len()).map(|i| format!("?{}", i))
+      .collect();
+    let mut parms: Vec<String> = keys.clone();
+    parms.resize(t.colms.len(), String::new());
+    cnn.execute(&format!("INSERT INTO {} VALUES ({});", t.table, mark.join(",")),
+      rusqlite::params_from_iter(parms.iter()))
+      .map_err(|e| repo_error(e, t.table))?;
+  }
+  for (c, v) in &colvl {
+    let mut parms: Vec<String> = keys.clone();
+    parms.push(v.clone());
+    cnn.execute(&format!("UPDATE {} SET {}=?{} WHERE {};", t.table, c, keys.len()
+      + 1, wkeys), rusqlite::params_from_iter(parms.iter()))
+      .map_err(|e| repo_error(e, t.table))?;
+  }
+  println!("Entry {} of {} {}", keys.join("|"), t.table, if exist { "updated" }
+    else { "added" });
+  return Ok(());
+}
+
+// delete_refdata.rs - Delete an entry of a reference table. For cdcodes, the
+// table key only deletes the whole code table (2021-07-01 bar8tl)
+// Command line: edimaps ref delete <table> <key> [<key2>]
+pub fn delete_refdata(dbopt: &String, table: &String, keys: &Vec<String>) ->
+  Result<()> {
+  let t = get_reftable(table)?;
+  let cdidx = t.table == CDCODES && keys.len() == 1;
+  check_keys(&t, keys, !cdidx)?;
+  let cnn = Connection::open(dbopt)?;
+  let n = cnn.execute(&format!("DELETE FROM {} WHERE {};", t.table,
+    key_condition(&t, keys.len())), rusqlite::params_from_iter(keys.iter()))
+    .map_err(|e| repo_error(e, t.table))?;
+  let mut m = 0;
+  if cdidx {
+    m = cnn.execute("DELETE FROM cdindex WHERE tabid=?1;", [&keys[0]])
+      .map_err(|e| repo_error(e, CDINDEX))?;
+  }
+  if n + m == 0 {
+    return Err(not_found(&t, keys));
+  }
+  println!("Entry {} of {} deleted |{:6} rows|", keys.join("|"), t.table, n);
+  return Ok(());
+}
+
+// export_refdata.rs - Export a reference table in the JSON format read by
+// 'add -r <table>' (2021-07-01 bar8tl)
+// Command line: edimaps ref export <table> [-o <file>]
+pub fn export_refdata(dbopt: &String, table: &String, opath: &Option<String>) ->
+  Result<()> {
+  let t = get_reftable(table)?;
+  let cnn = Connection::open(dbopt)?;
+  let lines = read_refdata(&cnn, &t, &Vec::new())?;
+  let jerr = |e: serde_json::Error| json_error(e, "");
+  let s = match t.table {
+    CDCODES => {
+      let mut sapcd = SapcodesTp { sapcodes: Vec::new() };
+      let mut stmt = cnn.prepare("SELECT tabid, tabus FROM cdindex UNION SELECT
+        DISTINCT tabid, '' FROM cdcodes WHERE tabid NOT IN (SELECT tabid FROM
+        cdindex) ORDER BY tabid;").map_err(|e| repo_error(e, CDINDEX))?;
+      let mut rows = stmt.query([]).map_err(|e| repo_error(e, CDINDEX))?;
+      while let Some(row) = rows.next().map_err(|e| repo_error(e, CDINDEX))? {
+        let ctype: String = row.get(0)?;
+        let codes = lines.iter().filter(|l| l[0] == ctype).map(|l| CodesTp {
+          key: l[1].clone(), val: l[2].clone() }).collect();
+        sapcd.sapcodes.push(SapcdTp { ctype, usage: row.get(1)?, codes });
+      }
+      serde_json::to_string_pretty(&sapcd).map_err(jerr)?
+    },
+    CDDATA  => serde_json::to_string_pretty(&TranspTp { transp: lines.iter()
+      .filter(|l| l[0] == EDITRANSP).map(|l| TranslTp { tmedi: l[1].clone(),
+      tmode: l[2].clone(), tmean: l[3].clone() }).collect() }).map_err(jerr)?,
+    CDFIELD => serde_json::to_string_pretty(&CdfieldTp { cdfield: lines.iter()
+      .map(|l| CdfldTp { segmn: l[0].clone(), field: l[1].clone(),
+      tabid: l[2].clone() }).collect() }).map_err(jerr)?,
+    IDOCTP  => serde_json::to_string_pretty(&IdoctplTp { idoct: lines.iter()
+      .map(|l| IdoctpTp { itype: l[0].clone(), idefn: l[1].clone(),
+      short: l[2].clone(), cntrl: l[3].clone(), clien: l[4].clone(),
+      rcvpf: l[5].clone() }).collect() }).map_err(jerr)?,
+    PIPELN  => {
+      let mut pipls = PipelnsTp { pipelines: Vec::new() };
+      for l in &lines {
+        if pipls.pipelines.last().map(|p| p.pipnm != l[0]).unwrap_or(true) {
+          pipls.pipelines.push(PipelnTp { pipnm: l[0].clone(), steps: Vec::new() });
+        }
+        pipls.pipelines.last_mut().unwrap().steps.push(PipstpTp {
+          step: l[2].clone(), ifilt: l[3].clone() });
+      }
+      serde_json::to_string_pretty(&pipls).map_err(jerr)?
+    },
+    _       => serde_json::to_string_pretty(&StepsTp { steps: lines.iter()
+      .map(|l| StepTp { step: l[0].clone(), inpdr: l[1].clone(),
+      inptp: l[2].clone(), outdr: l[3].clone(), outtp: l[4].clone(),
+      refdr: l[5].clone(), reftp: l[6].clone(), wkflw: l[7].clone(),
+      pcddr: l[8].clone(), ifilt: l[9].clone() }).collect() }).map_err(jerr)?
+  };
+  match opath {
+    Some(p) => {
+      let mut file = File::create(p).map_err(|e| io_error(e, p))?;
+      file.write_all(s.as_bytes()).map_err(|e| io_error(e, p))?;
+      println!("Table '{}' exported |{:6} rows|", t.table, lines.len());
+    },
+    None => println!("{}", s)
+  }
+  return Ok(());
+}
+
+// read_refdata.rs - Read entries of a reference table, those starting with the
+// keys given (all with no keys), in key order (2021-07-01 bar8tl)
+pub fn read_refdata(cnn: &Connection, t: &RtablTp, keys: &Vec<String>) ->
+  Result<Vec<Vec<String>>> {
+  let cond = if keys.len() > 0 { key_condition(t, keys.len()) } else {
+    "1=1".to_string() };
+  let mut stmt = cnn.prepare(&format!("SELECT {} FROM {} WHERE {} ORDER BY {};",
+    t.colms.join(", "), t.table, cond, t.colms[..t.keys].join(", ")))
+    .map_err(|e| repo_error(e, t.table))?;
+  let mut rows = stmt.query(rusqlite::params_from_iter(keys.iter()))
+    .map_err(|e| repo_error(e, t.table))?;
+  let mut lines: Vec<Vec<String>> = Vec::new();
+  while let Some(row) = rows.next().map_err(|e| repo_error(e, t.table))? {
+    let mut line: Vec<String> = Vec::with_capacity(t.colms.len());
+    for i in 0..t.colms.len() {
+      line.push(match row.get::<_, Value>(i)? {
+        Value::Text(s)    => s,
+        Value::Integer(n) => n.to_string(),
+        Value::Real(n)    => n.to_string(),
+        _                 => String::new()
+      });
+    }
+    lines.push(line);
+  }
+  return Ok(lines);
+}
+
+// key_condition.rs - SQL condition on the first n key columns, as parameters ?1..
+// (2021-07-01 bar8tl)
+fn key_condition(t: &RtablTp, n: usize) -> String {
+  return t.colms[..n].iter().enumerate().map(|(i, c)| format!("{}=?{}", c, i + 1))
+    .collect::<Vec<String>>().join(" and ");
+}
+
+// check_keys.rs - Check number of keys given for an entry of a reference table:
+// all keys, or at least one if partial keys are allowed (2021-07-01 bar8tl)
+fn check_keys(t: &RtablTp, keys: &Vec<String>, full: bool) -> Result<()> {
+  let valid = if full { keys.len() == t.keys } else {
+    keys.len() > 0 && keys.len() <= t.keys };
+  if !valid {
+    return Err(ErrorTp::Config { file: String::new(), detail: format!(
+      "Table {} is keyed by {}, {} key values given", t.table,
+      t.colms[..t.keys].join(" and "), keys.len()) });
+  }
+  return Ok(());
+}
+
+// not_found.rs - Error for entry not found in a reference table
+// (2021-07-01 bar8tl)
+fn not_found(t: &RtablTp, keys: &Vec<String>) -> ErrorTp {
+  return ErrorTp::Repository { table: t.table.to_string(), detail: format!(
+    "Entry {} not found", keys.join("|")) };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::assets::{add_cdcodes, add_idoctp, add_pipeln};
+  use crate::readidoc::samples::work_folder;
+  use crate::reposit::add_table;
+
+  fn strs(v: &[&str]) -> Vec<String> {
+    return v.iter().map(|s| s.to_string()).collect();
+  }
+
+  // ref_repo.rs - Repository with the reference tables
+  fn ref_repo(wrkdr: &String) -> String {
+    let dbopt = format!("{}edimaps.db", wrkdr);
+    let cnn = Connection::open(&dbopt).unwrap();
+    for table in [CDCODES, CDINDEX, IDOCTP, PIPELN] {
+      add_table(&cnn, table).unwrap();
+    }
+    return dbopt;
+  }
+
+  // table_rows.rs - All rows of a table, in key order
+  fn table_rows(dbopt: &String, table: &str) -> Vec<Vec<String>> {
+    let cnn = Connection::open(dbopt).unwrap();
+    let t = match table {
+      CDINDEX => RtablTp { table: CDINDEX, keys: 1, colms: vec!["tabid", "tabus"] },
+      _       => get_reftable(&table.to_string()).unwrap()
+    };
+    return read_refdata(&cnn, &t, &Vec::new()).unwrap();
+  }
+
+  // reimport.rs - Export a table, empty it and load the export with 'add -r'
+  fn reimport(dbopt: &String, wrkdr: &String, table: &str,
+    add: fn(&String, String) -> Result<()>) {
+    let opath = format!("{}{}.json", wrkdr, table);
+    export_refdata(dbopt, &table.to_string(), &Some(opath.clone())).unwrap();
+    Connection::open(dbopt).unwrap().execute(&format!("DELETE FROM {};", table), ())
+      .unwrap();
+    add(dbopt, opath).unwrap();
+  }
+
+  #[test]
+  fn cdcodes_entries_survive_export_and_reimport() {
+    let wrkdr = work_folder("refdata-cdcodes");
+    let dbopt = ref_repo(&wrkdr);
+    let table = CDCODES.to_string();
+    set_refdata(&dbopt, &table, &strs(&["INCO1"]), &strs(&["tabus=Incoterms"]))
+      .unwrap();
+    set_refdata(&dbopt, &table, &strs(&["INCO1", "FOB"]),
+      &strs(&["cdtext=Free on board"])).unwrap();
+    set_refdata(&dbopt, &table, &strs(&["INCO1", "CIF"]), &strs(&["cdtext=Cost"]))
+      .unwrap();
+    set_refdata(&dbopt, &table, &strs(&["INCO1", "CIF"]),
+      &strs(&["cdtext=Cost, insurance and freight"])).unwrap();
+    set_refdata(&dbopt, &table, &strs(&["PARVW", "AG"]), &strs(&["cdtext=Buyer"]))
+      .unwrap();
+    assert!(set_refdata(&dbopt, &table, &strs(&["INCO1", "EXW"]),
+      &strs(&["tabus=X"])).is_err());
+    assert!(set_refdata(&dbopt, &table, &strs(&["INCO1"]), &strs(&["cdtext=X"]))
+      .is_err());
+    let cnn = Connection::open(&dbopt).unwrap();
+    let t = get_reftable(&table).unwrap();
+    assert_eq!(show_text(&cnn, &t, &strs(&["INCO1"])).unwrap(), [
+      "tabus   : Incoterms",
+      "tabid   : INCO1", "cdkey   : CIF", "cdtext  : Cost, insurance and freight",
+      "",
+      "tabid   : INCO1", "cdkey   : FOB", "cdtext  : Free on board",
+      "", ""].join("\n"));
+    assert!(show_text(&cnn, &t, &strs(&["INCO2"])).is_err());
+    let codes = table_rows(&dbopt, CDCODES);
+    let index = table_rows(&dbopt, CDINDEX);
+    assert_eq!(index, vec![strs(&["INCO1", "Incoterms"]), strs(&["PARVW", ""])]);
+    reimport(&dbopt, &wrkdr, CDCODES, add_cdcodes);
+    assert_eq!(table_rows(&dbopt, CDCODES), codes);
+    assert_eq!(table_rows(&dbopt, CDINDEX), index);
+    delete_refdata(&dbopt, &table, &strs(&["INCO1", "FOB"])).unwrap();
+    delete_refdata(&dbopt, &table, &strs(&["PARVW"])).unwrap();
+    assert_eq!(table_rows(&dbopt, CDCODES), vec![codes[0].clone()]);
+    assert_eq!(table_rows(&dbopt, CDINDEX), vec![index[0].clone()]);
+    assert!(delete_refdata(&dbopt, &table, &strs(&["PARVW"])).is_err());
+  }
+
+  #[test]
+  fn idoctp_entries_survive_export_and_reimport() {
+    let wrkdr = work_folder("refdata-idoctp");
+    let dbopt = ref_repo(&wrkdr);
+    let table = IDOCTP.to_string();
+    set_refdata(&dbopt, &table, &strs(&["/RB04/YP3_INVOIC02_XML"]),
+      &strs(&["short=i2xr4", "cntrl=EDI_DC40"])).unwrap();
+    set_refdata(&dbopt, &table, &strs(&["DELVRY07"]), &strs(&["short=dlv07",
+      "clien=011", "rcvpf=LS"])).unwrap();
+    set_refdata(&dbopt, &table, &strs(&["DELVRY07"]), &strs(&["rcvpf=RE"]))
+      .unwrap();
+    assert!(set_refdata(&dbopt, &table, &strs(&["DELVRY07"]), &strs(&["itype=X"]))
+      .is_err());
+    let cnn = Connection::open(&dbopt).unwrap();
+    let t = get_reftable(&table).unwrap();
+    assert_eq!(show_text(&cnn, &t, &strs(&["DELVRY07"])).unwrap(), [
+      "itype   : DELVRY07", "idefn   : ", "short   : dlv07", "cntrl   : ",
+      "clien   : 011", "rcvpf   : RE", "", ""].join("\n"));
+    let types = table_rows(&dbopt, IDOCTP);
+    assert_eq!(types.len(), 2);
+    reimport(&dbopt, &wrkdr, IDOCTP, add_idoctp);
+    assert_eq!(table_rows(&dbopt, IDOCTP), types);
+  }
+
+  #[test]
+  fn pipeln_entries_survive_export_and_reimport() {
+    let wrkdr = work_folder("refdata-pipeln");
+    let dbopt = ref_repo(&wrkdr);
+    let table = PIPELN.to_string();
+    for (pipnm, seqno, step) in [("inbound", "2", "json"), ("inbound", "1", "fixed"),
+      ("audit", "1", "validate")] {
+      set_refdata(&dbopt, &table, &strs(&[pipnm, seqno]),
+        &strs(&[&format!("step={}", step)])).unwrap();
+    }
+    set_refdata(&dbopt, &table, &strs(&["inbound", "2"]), &strs(&["ifilt=i2*"]))
+      .unwrap();
+    assert!(set_refdata(&dbopt, &table, &strs(&["inbound"]), &strs(&["step=x"]))
+      .is_err());
+    let cnn = Connection::open(&dbopt).unwrap();
+    let t = get_reftable(&table).unwrap();
+    assert_eq!(show_text(&cnn, &t, &strs(&["inbound"])).unwrap(), [
+      "pipnm   : inbound", "seqno   : 1", "step    : fixed", "ifilt   : ", "",
+      "pipnm   : inbound", "seqno   : 2", "step    : json", "ifilt   : i2*", "", ""]
+      .join("\n"));
+    let steps = table_rows(&dbopt, PIPELN);
+    assert_eq!(steps.len(), 3);
+    reimport(&dbopt, &wrkdr, PIPELN, add_pipeln);
+    assert_eq!(table_rows(&dbopt, PIPELN), steps);
+    delete_refdata(&dbopt, &table, &strs(&["audit", "1"])).unwrap();
+    assert_eq!(table_rows(&dbopt, PIPELN), steps[1..].to_vec());
+  }
+}
@@ -1,7 +1,9 @@
 // reposit.rs - Function modules to create and maintain the data-files or
 // db-tables of the edimaps program repository (2021-07-01 bar8tl)
+use crate::error::{repo_error, Result};
 use rblib::create_sqlite3_tablelist::{TlistTp, create_sqlite3_tablelist};
 use rblib::create_sqlite3_table::create_sqlite3_table;
+use rusqlite::Connection;
 use serde::Deserialize;
 use serde_json::from_str;
 
@@ -22,8 +24,8 @@ struct ItablesTp {
 // ini_repo.rs - Sqlite3 DB tables creation for local IDOC definitions and EDI
 // mapping specifications archive (2021-07-01 bar8tl)
 // Command line: edimaps init [<table>|ALL]
-pub fn ini_repo(dbopt: &String, table: &String) {
-  let it: ItablesTp = from_str(ITABLES).unwrap();
+pub fn ini_repo(dbopt: &String, table: &String) -> Result<()> {
+  let it: ItablesTp = from_str(ITABLES)?;
   if table == "." || table == "*" || table.to_lowercase() == "all" {
     let mut tlist: Vec<TlistTp> = Vec::with_capacity(it.sqlst.len());
     for sql in &it.sqlst {
@@ -42,4 +44,16 @@ pub fn ini_repo(dbopt: &String, table: &String) {
       }
     }
   }
+  return Ok(());
+}
+
+// add_table.rs - Create a repository table if it does not exist, keeping the data
+// of an existing one. For tables kept by the program, as the search index of field
+// mappings or the run status of pipelines (2021-07-01 bar8tl)
+pub fn add_table(cnn: &Connection, table: &str) -> Result<()> {
+  let it: ItablesTp = from_str(ITABLES)?;
+  if let Some(sql) = it.sqlst.iter().find(|s| s.table == table) {
+    cnn.execute(&sql.sqlst, ()).map_err(|e| repo_error(e, table))?;
+  }
+  return Ok(());
 }